@@ -0,0 +1,616 @@
+use std::collections::{HashMap, HashSet};
+
+use bool_algebra::{bool_to_u32, update_values};
+
+use crate::elaborate::{Cell, Netlist, State};
+use crate::primitive::Primitive;
+use crate::token::error;
+use crate::{Error, LookupTable};
+
+/// an and-inverter graph, a literal is `2 * variable` or `2 * variable + 1` for the negated variable
+/// variable 0 is the constant, so literal 0 is false and literal 1 is true
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Aig {
+    /// (literal, name)
+    pub inputs: Vec<(u32, String)>,
+    /// (literal, next state literal, init, name)
+    pub latches: Vec<(u32, u32, bool, String)>,
+    /// (literal, name)
+    pub outputs: Vec<(u32, String)>,
+    /// (lhs, rhs0, rhs1)
+    pub ands: Vec<(u32, u32, u32)>,
+    // maps the operands of an and to its literal
+    strash: HashMap<(u32, u32), u32>,
+}
+
+impl Aig {
+    /// creates a graph without ands, the inputs get the literals 2, 4, ... and the latches the literals after them
+    /// the next state of every latch is false until it is set
+    pub fn new(inputs: Vec<String>, latches: Vec<(bool, String)>) -> Self {
+        let n = inputs.len() as u32;
+        Self {
+            inputs: (1..).map(|var| 2 * var).zip(inputs).collect(),
+            latches: latches
+                .into_iter()
+                .enumerate()
+                .map(|(i, (init, name))| (2 * (n + i as u32 + 1), 0, init, name))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    fn max_var(&self) -> u32 {
+        (self.inputs.len() + self.latches.len() + self.ands.len()) as u32
+    }
+
+    /// gets the literal of `a & b`, constants are folded and equal ands are only added once
+    pub fn and(&mut self, a: u32, b: u32) -> u32 {
+        // the larger operand comes first like in the binary format
+        let (a, b) = if a >= b { (a, b) } else { (b, a) };
+        if b == 0 || a == b ^ 1 {
+            return 0;
+        }
+        if b == 1 || a == b {
+            return a;
+        }
+        if let Some(&lit) = self.strash.get(&(a, b)) {
+            return lit;
+        }
+
+        let lit = 2 * (self.max_var() + 1);
+        self.ands.push((lit, a, b));
+        self.strash.insert((a, b), lit);
+        lit
+    }
+
+    /// gets the literal of `if sel { a } else { b }`
+    fn mux(&mut self, sel: u32, a: u32, b: u32) -> u32 {
+        let a = self.and(sel, a);
+        let b = self.and(sel ^ 1, b);
+        self.and(a ^ 1, b ^ 1) ^ 1
+    }
+
+    /// gets the literal of a function of `lits` by shannon expansion
+    /// `truth` has a row for every combination of `lits`, bit `j` of the row index is the value of `lits[j]`
+    pub fn function(&mut self, truth: &[bool], lits: &[u32]) -> u32 {
+        if truth.iter().all(|&bit| bit == truth[0]) {
+            return truth[0] as u32;
+        }
+
+        let (lo, hi) = truth.split_at(truth.len() / 2);
+        let (sel, rest) = lits.split_last().unwrap();
+        if lo == hi {
+            return self.function(lo, rest);
+        }
+        let hi = self.function(hi, rest);
+        let lo = self.function(lo, rest);
+        self.mux(*sel, hi, lo)
+    }
+
+    fn header(&self, format: &str) -> String {
+        format!(
+            "{} {} {} {} {} {}\n",
+            format,
+            self.max_var(),
+            self.inputs.len(),
+            self.latches.len(),
+            self.outputs.len(),
+            self.ands.len()
+        )
+    }
+
+    fn latch_line(next: u32, init: bool) -> String {
+        if init {
+            format!("{} 1\n", next)
+        } else {
+            format!("{}\n", next)
+        }
+    }
+
+    fn symbols(&self) -> String {
+        let mut symbols = String::new();
+        for (i, (_, name)) in self.inputs.iter().enumerate() {
+            symbols += &format!("i{} {}\n", i, name);
+        }
+        for (i, (_, _, _, name)) in self.latches.iter().enumerate() {
+            symbols += &format!("l{} {}\n", i, name);
+        }
+        for (i, (_, name)) in self.outputs.iter().enumerate() {
+            symbols += &format!("o{} {}\n", i, name);
+        }
+        symbols
+    }
+
+    /// writes the graph in the ascii `aag` format with a symbol for every input, latch and output
+    pub fn to_ascii(&self) -> String {
+        let mut aag = self.header("aag");
+        for (lit, _) in self.inputs.iter() {
+            aag += &format!("{}\n", lit);
+        }
+        for (lit, next, init, _) in self.latches.iter() {
+            aag += &format!("{} {}", lit, Self::latch_line(*next, *init));
+        }
+        for (lit, _) in self.outputs.iter() {
+            aag += &format!("{}\n", lit);
+        }
+        for (lhs, rhs0, rhs1) in self.ands.iter() {
+            aag += &format!("{} {} {}\n", lhs, rhs0, rhs1);
+        }
+        aag + &self.symbols()
+    }
+
+    /// writes the graph in the binary `aig` format, the ands have to be in the order `and` creates them
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut text = self.header("aig");
+        for (_, next, init, _) in self.latches.iter() {
+            text += &Self::latch_line(*next, *init);
+        }
+        for (lit, _) in self.outputs.iter() {
+            text += &format!("{}\n", lit);
+        }
+
+        let mut aig = text.into_bytes();
+        for (lhs, rhs0, rhs1) in self.ands.iter() {
+            for mut delta in [lhs - rhs0, rhs0 - rhs1] {
+                while delta >= 0x80 {
+                    aig.push((delta & 0x7f) as u8 | 0x80);
+                    delta >>= 7;
+                }
+                aig.push(delta as u8);
+            }
+        }
+        aig.extend(self.symbols().into_bytes());
+        aig
+    }
+}
+
+/// reads `src` line by line and keeps track of the line number
+struct Reader<'a> {
+    src: &'a [u8],
+    pos: usize,
+    /// number of the next line, starting at 1
+    line: usize,
+}
+
+impl Reader<'_> {
+    fn at_end(&self) -> bool {
+        self.pos >= self.src.len()
+    }
+
+    /// reads the next line without the line break, a `\r` before the `\n` is part of the line break
+    fn line(&mut self) -> Option<String> {
+        if self.at_end() {
+            return None;
+        }
+        let end = self.src[self.pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(self.src.len(), |i| self.pos + i);
+        let text = &self.src[self.pos..end];
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        let line = String::from_utf8_lossy(text).to_string();
+        self.pos = end + 1;
+        self.line += 1;
+        Some(line)
+    }
+
+    /// reads a line of `min` to `max` numbers and returns them with their columns
+    fn numbers(&mut self, what: &str, min: usize, max: usize) -> Result<Vec<(u32, usize)>, Error> {
+        let line = self.line;
+        let text = match self.line() {
+            Some(text) => text,
+            None => return Err(error(line, 1, format!("expected {}", what))),
+        };
+
+        let mut numbers = Vec::new();
+        let mut column = 1;
+        for word in text.split(' ') {
+            if !word.is_empty() {
+                match word.parse() {
+                    Ok(number) => numbers.push((number, column)),
+                    Err(_) => {
+                        return Err(error(
+                            line,
+                            column,
+                            format!("expected a number but found {}", word),
+                        ))
+                    }
+                }
+            }
+            column += word.chars().count() + 1;
+        }
+        if numbers.len() < min || numbers.len() > max {
+            return Err(error(line, 1, format!("expected {}", what)));
+        }
+        Ok(numbers)
+    }
+
+    /// reads a number of the binary and section, 7 bits per byte with the least significant bits first
+    fn delta(&mut self) -> Result<u32, Error> {
+        let mut value: u32 = 0;
+        for shift in (0..32).step_by(7) {
+            let byte = match self.src.get(self.pos) {
+                Some(&byte) => byte,
+                None => return Err(error(self.line, 1, "the ands end too early".to_string())),
+            };
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(error(
+            self.line,
+            1,
+            "a delta of the ands is too large".to_string(),
+        ))
+    }
+}
+
+/// parses an aiger file in the ascii `aag` or the binary `aig` format
+/// bad state and constraint properties are read as outputs, justice and fairness properties are not supported
+/// inputs, latches and outputs without a symbol are called `i0`, `l0`, `o0`, `b0` and `c0`
+pub(crate) fn parse(src: &[u8]) -> Result<Aig, Error> {
+    let mut reader = Reader {
+        src,
+        pos: 0,
+        line: 1,
+    };
+
+    let header = reader.line().unwrap_or_default();
+    let words: Vec<&str> = header.split(' ').collect();
+    let binary = match words[0] {
+        "aag" => false,
+        "aig" => true,
+        _ => return Err(error(1, 1, "expected aag or aig".to_string())),
+    };
+    let mut counts = Vec::new();
+    let mut column = 5;
+    for word in words[1..].iter() {
+        match word.parse::<u32>() {
+            Ok(count) => counts.push(count),
+            Err(_) => {
+                return Err(error(
+                    1,
+                    column,
+                    format!("expected a number but found {}", word),
+                ))
+            }
+        }
+        column += word.chars().count() + 1;
+    }
+    if counts.len() < 5 || counts.len() > 9 {
+        return Err(error(1, 1, "expected M I L O A [B C J F]".to_string()));
+    }
+    counts.resize(9, 0);
+    let (m, i, l, o, a, b, c) = (
+        counts[0], counts[1], counts[2], counts[3], counts[4], counts[5], counts[6],
+    );
+    if counts[7] != 0 || counts[8] != 0 {
+        return Err(error(
+            1,
+            1,
+            "justice and fairness properties are not supported".to_string(),
+        ));
+    }
+    if binary && m != i + l + a {
+        return Err(error(
+            1,
+            1,
+            format!("M has to be {} in the binary format", i + l + a),
+        ));
+    }
+
+    // (literal, line, column) of every use of a literal
+    let mut uses: Vec<(u32, usize, usize)> = Vec::new();
+    let mut defined: HashSet<u32> = HashSet::new();
+    let mut define = |lit: u32, line: usize, column: usize| {
+        if lit < 2 || lit & 1 == 1 || lit / 2 > m {
+            Err(error(line, column, format!("{} can not be defined", lit)))
+        } else if !defined.insert(lit / 2) {
+            Err(error(line, column, format!("{} is defined twice", lit)))
+        } else {
+            Ok(())
+        }
+    };
+    let check = |lit: u32, line: usize, column: usize| {
+        if lit / 2 > m {
+            Err(error(
+                line,
+                column,
+                format!("{} is larger than 2 * M + 1", lit),
+            ))
+        } else {
+            Ok(lit)
+        }
+    };
+
+    let mut aig = Aig::default();
+    for k in 0..i {
+        let lit = if binary {
+            2 * (k + 1)
+        } else {
+            let line = reader.line;
+            let (lit, column) = reader.numbers("an input", 1, 1)?[0];
+            define(lit, line, column)?;
+            lit
+        };
+        aig.inputs.push((lit, format!("i{}", k)));
+    }
+    for k in 0..l {
+        let line = reader.line;
+        let numbers = if binary {
+            reader.numbers("a latch", 1, 2)?
+        } else {
+            reader.numbers("a latch", 2, 3)?
+        };
+        let (lit, numbers) = if binary {
+            (2 * (i + k + 1), &numbers[..])
+        } else {
+            define(numbers[0].0, line, numbers[0].1)?;
+            (numbers[0].0, &numbers[1..])
+        };
+        let (next, column) = numbers[0];
+        uses.push((check(next, line, column)?, line, column));
+        // a latch that is initialized with its own literal has no defined initial value and starts with 0
+        let init = match numbers.get(1) {
+            None | Some((0, _)) => false,
+            Some((1, _)) => true,
+            Some(&(init, _)) if init == lit => false,
+            Some(&(init, column)) => {
+                return Err(error(
+                    line,
+                    column,
+                    format!("{} is not an initial value", init),
+                ))
+            }
+        };
+        aig.latches.push((lit, next, init, format!("l{}", k)));
+    }
+    for (prefix, count) in [("o", o), ("b", b), ("c", c)] {
+        for k in 0..count {
+            let line = reader.line;
+            let (lit, column) = reader.numbers("an output", 1, 1)?[0];
+            uses.push((check(lit, line, column)?, line, column));
+            aig.outputs.push((lit, format!("{}{}", prefix, k)));
+        }
+    }
+    for k in 0..a {
+        if binary {
+            let line = reader.line;
+            let lhs = 2 * (i + l + k + 1);
+            let rhs0 = lhs.checked_sub(reader.delta()?);
+            let delta = reader.delta()?;
+            let rhs1 = rhs0.and_then(|rhs0| rhs0.checked_sub(delta));
+            match (rhs0, rhs1) {
+                (Some(rhs0), Some(rhs1)) => aig.ands.push((lhs, rhs0, rhs1)),
+                _ => return Err(error(line, 1, format!("and {} has an invalid delta", lhs))),
+            }
+        } else {
+            let line = reader.line;
+            let numbers = reader.numbers("an and", 3, 3)?;
+            define(numbers[0].0, line, numbers[0].1)?;
+            for &(lit, column) in numbers[1..].iter() {
+                uses.push((check(lit, line, column)?, line, column));
+            }
+            aig.ands.push((numbers[0].0, numbers[1].0, numbers[2].0));
+        }
+    }
+
+    if !binary {
+        for (lit, line, column) in uses {
+            if lit >= 2 && !defined.contains(&(lit / 2)) {
+                return Err(error(line, column, format!("{} is never defined", lit)));
+            }
+        }
+    }
+
+    // the symbol table ends at the end of the file or at the comment section
+    while let Some(text) = reader.line() {
+        let line = reader.line - 1;
+        if text == "c" {
+            break;
+        }
+        let (position, name) = match text.split_once(' ') {
+            Some(split) if !split.1.is_empty() => split,
+            _ => {
+                return Err(error(
+                    line,
+                    1,
+                    format!("expected a symbol but found {}", text),
+                ))
+            }
+        };
+        let index = position[1..].parse::<usize>().ok();
+        let entry = match (&position[..1], index) {
+            ("i", Some(index)) => aig.inputs.get_mut(index).map(|(_, name)| name),
+            ("l", Some(index)) => aig.latches.get_mut(index).map(|(_, _, _, name)| name),
+            ("o", Some(index)) => aig.outputs.get_mut(index).map(|(_, name)| name),
+            ("b", Some(index)) => aig
+                .outputs
+                .get_mut(o as usize + index)
+                .map(|(_, name)| name),
+            ("c", Some(index)) => aig
+                .outputs
+                .get_mut((o + b) as usize + index)
+                .map(|(_, name)| name),
+            _ => None,
+        };
+        match entry {
+            Some(entry) => *entry = name.to_string(),
+            None => {
+                return Err(error(
+                    line,
+                    1,
+                    format!("{} is not a valid symbol", position),
+                ))
+            }
+        }
+    }
+
+    Ok(aig)
+}
+
+/// creates a lookup table named `name` from `function`
+fn gate(name: &str, in_names: Vec<&str>, function: fn(&[bool]) -> bool) -> LookupTable {
+    let mut values = vec![false; in_names.len()];
+    let mut table = vec![false; 2_usize.pow(in_names.len() as u32)];
+    loop {
+        table[bool_to_u32(values.clone()) as usize] = function(&values);
+        if !update_values(&mut values) {
+            break;
+        }
+    }
+    LookupTable::new(vec![table], in_names, vec!["out"], name).unwrap()
+}
+
+/// creates the cells of the ands, the inverters and the constants of a `Netlist`
+struct Cells<'a> {
+    /// nets of the inputs, latches and ands by variable
+    nets: &'a HashMap<u32, String>,
+    cells: Vec<Cell>,
+    /// instances that were already added
+    added: HashSet<String>,
+}
+
+impl Cells<'_> {
+    /// adds a cell named `instance` if it dose not exist yet and returns the net of its output
+    fn add(
+        &mut self,
+        instance: String,
+        lut: impl FnOnce() -> LookupTable,
+        inputs: Vec<String>,
+    ) -> String {
+        let net = format!("{}.out", instance);
+        if self.added.insert(instance.clone()) {
+            self.cells.push(Cell {
+                name: instance,
+                lut: lut(),
+                inputs: inputs.into_iter().enumerate().collect(),
+                outputs: vec![(0, net.clone())],
+            });
+        }
+        net
+    }
+
+    /// gets the net of `lit`, negated literals get an inverter and the constants a lookup table without inputs
+    fn net(&mut self, lit: u32) -> String {
+        match lit {
+            0 => self.add(
+                "false".to_string(),
+                || gate("False", vec![], |_| false),
+                vec![],
+            ),
+            1 => self.add(
+                "true".to_string(),
+                || gate("True", vec![], |_| true),
+                vec![],
+            ),
+            _ if lit & 1 == 0 => self.nets[&(lit / 2)].clone(),
+            _ => {
+                let input = self.nets[&(lit / 2)].clone();
+                self.add(
+                    format!("not{}", lit / 2),
+                    || gate("Not", vec!["in"], |v| !v[0]),
+                    vec![input],
+                )
+            }
+        }
+    }
+
+    /// gets the net of the variable of `lit`, the constant is always false
+    fn var_net(&mut self, lit: u32) -> String {
+        self.net(lit & !1)
+    }
+}
+
+/// puts bits like `a[0]`, `a[1]` together in to a bus if every bit from 0 up is there
+/// returns the (name, width) of the buses in the order they are first used
+pub(crate) fn buses(names: &[String]) -> Vec<(String, usize)> {
+    // an index like `01` is not written by `bit_names`, such names stay single bits
+    let bit = |name: &str| -> Option<(String, usize)> {
+        let (base, index) = name.strip_suffix(']')?.split_once('[')?;
+        let bit = index.parse::<usize>().ok()?;
+        (bit.to_string() == index).then(|| (base.to_string(), bit))
+    };
+
+    let mut buses: Vec<(String, usize)> = Vec::new();
+    for name in names {
+        let bus = bit(name).and_then(|(base, _)| {
+            let indices: Vec<usize> = names
+                .iter()
+                .filter_map(|name| bit(name).filter(|(b, _)| b == &base).map(|(_, i)| i))
+                .collect();
+            let width = indices.len();
+            let complete =
+                width > 1 && (0..width).all(|i| indices.contains(&i)) && !names.contains(&base);
+            complete.then_some((base, width))
+        });
+        match bus {
+            Some(bus) if buses.contains(&bus) => (),
+            Some(bus) => buses.push(bus),
+            None => buses.push((name.clone(), 1)),
+        }
+    }
+    buses
+}
+
+/// creates the `Netlist` of a graph read by `parse`
+/// every and becomes a 2 input lookup table named `n` and its variable, inverted inputs are part of the table
+pub(crate) fn to_netlist(aig: &Aig, name: &str) -> Netlist {
+    let mut nets: HashMap<u32, String> = HashMap::new();
+    for (lit, name) in aig.inputs.iter() {
+        nets.insert(lit / 2, name.clone());
+    }
+    for (lit, _, _, name) in aig.latches.iter() {
+        nets.insert(lit / 2, format!("{}.out", name));
+    }
+    for (lhs, _, _) in aig.ands.iter() {
+        nets.insert(lhs / 2, format!("n{}.out", lhs / 2));
+    }
+
+    let mut cells = Cells {
+        nets: &nets,
+        cells: Vec::new(),
+        added: HashSet::new(),
+    };
+    for &(lhs, rhs0, rhs1) in aig.ands.iter() {
+        let inputs = vec![cells.var_net(rhs0), cells.var_net(rhs1)];
+        let lut = || match (rhs0 & 1 == 1, rhs1 & 1 == 1) {
+            (false, false) => gate("And", vec!["a", "b"], |v| v[0] && v[1]),
+            (true, false) => gate("AndNotA", vec!["a", "b"], |v| !v[0] && v[1]),
+            (false, true) => gate("AndNotB", vec!["a", "b"], |v| v[0] && !v[1]),
+            (true, true) => gate("Nor", vec!["a", "b"], |v| !v[0] && !v[1]),
+        };
+        cells.add(format!("n{}", lhs / 2), lut, inputs);
+    }
+
+    let states = aig
+        .latches
+        .iter()
+        .map(|(_, next, init, name)| State {
+            name: name.clone(),
+            primitive: Primitive::Dff { init: *init },
+            inputs: vec![(0, cells.net(*next))],
+            outputs: vec![(0, format!("{}.out", name))],
+        })
+        .collect();
+    let aliases = aig
+        .outputs
+        .iter()
+        .map(|(lit, name)| (name.clone(), cells.net(*lit)))
+        .collect();
+
+    let inputs: Vec<String> = aig.inputs.iter().map(|(_, name)| name.clone()).collect();
+    let outputs: Vec<String> = aig.outputs.iter().map(|(_, name)| name.clone()).collect();
+    Netlist {
+        name: name.to_string(),
+        in_buses: buses(&inputs),
+        out_buses: buses(&outputs),
+        inputs,
+        outputs,
+        cells: cells.cells,
+        states,
+        aliases,
+    }
+}
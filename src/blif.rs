@@ -0,0 +1,493 @@
+use bool_algebra::{bool_to_u32, update_values};
+
+use crate::elaborate::pin_names;
+use crate::token::error;
+use crate::{ChipDef, Component, Error, Library, LookupTable};
+
+/// the most inputs a `.names` can have, its lookup table has 2^n rows
+const MAX_INPUTS: usize = 20;
+
+/// timing and load commands that do not change the logic
+const IGNORED: [&str; 14] = [
+    ".clock",
+    ".area",
+    ".delay",
+    ".wire_load_slope",
+    ".wire",
+    ".input_arrival",
+    ".output_required",
+    ".input_drive",
+    ".output_load",
+    ".max_input_load",
+    ".default_input_arrival",
+    ".default_output_required",
+    ".default_input_drive",
+    ".default_output_load",
+];
+
+/// a word of a command with the position of its first char
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Word {
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Word {
+    pub fn error(&self, msg: String) -> Error {
+        error(self.line, self.column, msg)
+    }
+}
+
+/// a model parsed from blif
+#[derive(Debug, Clone, PartialEq)]
+struct Model {
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    /// the lookup table of a `.names` cover, the signals connected to its inputs and the signal it drives
+    Names(Box<LookupTable>, Vec<String>, String),
+    /// `.latch input output [type control]`, the latch starts with `init`
+    Latch {
+        input: String,
+        output: String,
+        control: Option<(String, String)>,
+        init: bool,
+    },
+    /// `.subckt model formal=actual ...`
+    Subckt {
+        model: String,
+        pins: Vec<(String, String)>,
+    },
+}
+
+/// adds every model of the blif `src` as a chip and every `.names` cover as a lookup table named `model.signal`
+/// latches without a type become `DFF` or `DFF1` parts clocked by the tick
+/// latches of type `re` or `ah` that start with 0 and whose control is a signal of the model become `DFFC` or `DLatch` parts
+/// other latches return `Error::Unsupported`
+/// signals like `a[3]` are bits of the bus `a`, the bits `a[0]` to `a[n - 1]` of a bus port must all be declared
+pub(crate) fn add_blif(library: &mut Library, src: &str) -> Result<(), Error> {
+    let models = parse(src)?;
+    for model in models.iter() {
+        for part in model.parts.iter() {
+            if let Part::Names(lut, _, _) = part {
+                library.add_lut(*lut.clone());
+            }
+        }
+    }
+
+    let chip_defs = models
+        .iter()
+        .map(|model| to_chip_def(model, &models, library))
+        .collect::<Result<Vec<ChipDef>, Error>>()?;
+    for chip_def in chip_defs {
+        library.add_chip(chip_def);
+    }
+    Ok(())
+}
+
+/// gets the cubes of a `.names` cover for output `output` of `lut`
+/// every row that is 1 is written as its own cube, the cubes are sorted
+pub(crate) fn cover(lut: &LookupTable, output: usize) -> Vec<String> {
+    let table = &lut.get_table()[output];
+    let mut values = vec![false; lut.in_names().len()];
+    let mut cubes = Vec::new();
+    loop {
+        if table[bool_to_u32(values.clone()) as usize] {
+            let pattern: String = values.iter().map(|&v| if v { '1' } else { '0' }).collect();
+            if pattern.is_empty() {
+                cubes.push("1".to_string());
+            } else {
+                cubes.push(format!("{} 1", pattern));
+            }
+        }
+        if !update_values(&mut values) {
+            break;
+        }
+    }
+    cubes.sort();
+    cubes
+}
+
+fn to_chip_def(model: &Model, models: &[Model], library: &Library) -> Result<ChipDef, Error> {
+    let parts = model
+        .parts
+        .iter()
+        .map(|part| match part {
+            Part::Names(lut, inputs, output) => Ok((
+                Component::Def {
+                    inputs: lut.in_names().into_iter().zip(inputs.clone()).collect(),
+                    outputs: vec![("out".to_string(), output.clone())],
+                    name: lut.name(),
+                },
+                Some(output.clone()),
+            )),
+            Part::Latch {
+                input,
+                output,
+                control,
+                init,
+            } => {
+                let mut inputs = vec![("in".to_string(), input.clone())];
+                let name = match control {
+                    None if *init => "DFF1",
+                    None => "DFF",
+                    Some((kind, control)) if !init && kind == "re" && driven(model, control) => {
+                        inputs.push(("clk".to_string(), control.clone()));
+                        "DFFC"
+                    }
+                    Some((kind, control)) if !init && kind == "ah" && driven(model, control) => {
+                        inputs.push(("en".to_string(), control.clone()));
+                        "DLatch"
+                    }
+                    Some((kind, control)) => {
+                        return Err(Error::Unsupported {
+                            part: output.clone(),
+                            msg: format!(
+                                "a {} latch on {} that starts with {} is not a DFFC or DLatch",
+                                kind, control, *init as u8
+                            ),
+                        })
+                    }
+                };
+                Ok((
+                    Component::Def {
+                        inputs,
+                        outputs: vec![("out".to_string(), output.clone())],
+                        name: name.to_string(),
+                    },
+                    Some(output.clone()),
+                ))
+            }
+            // pins of unknown models are treated as inputs, elaboration reports the missing part
+            Part::Subckt { model: name, pins } => {
+                let in_pins = match models.iter().find(|model| &model.name == name) {
+                    Some(model) => Some(model.inputs.iter().map(|pin| base(pin)).collect()),
+                    None => pin_names(library, name).map(|(inputs, _)| inputs),
+                };
+                let (inputs, outputs) = pins.iter().cloned().partition(|(pin, _)| match &in_pins {
+                    Some(in_pins) => in_pins.iter().any(|name| name == &base(pin)),
+                    None => true,
+                });
+                Ok((
+                    Component::Def {
+                        inputs,
+                        outputs,
+                        name: name.clone(),
+                    },
+                    None,
+                ))
+            }
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(ChipDef::with_instances(
+        model.name.clone(),
+        ports(model, &model.inputs)?,
+        ports(model, &model.outputs)?,
+        parts,
+    ))
+}
+
+/// checks if `signal` is an input of the model or driven by one of its parts
+fn driven(model: &Model, signal: &str) -> bool {
+    model.inputs.iter().any(|input| input == signal)
+        || model.parts.iter().any(|part| match part {
+            Part::Names(_, _, output) | Part::Latch { output, .. } => output == signal,
+            Part::Subckt { pins, .. } => pins.iter().any(|(_, actual)| actual == signal),
+        })
+}
+
+/// gets the bus of a signal like `a[3]`
+fn base(signal: &str) -> String {
+    signal.split('[').next().unwrap_or(signal).to_string()
+}
+
+/// declares the ports of a model, bits like `a[0]`, `a[1]` are put together in to a bus
+/// returns `Error::Unsupported` if a bit below the highest bit of a bus is not declared
+fn ports(model: &Model, signals: &[String]) -> Result<Vec<String>, Error> {
+    let mut ports: Vec<(String, Vec<usize>)> = Vec::new();
+    for signal in signals {
+        let (name, bits) = match signal.strip_suffix(']').and_then(|s| s.split_once('[')) {
+            Some((name, bit)) => (name.to_string(), bit.parse().ok().into_iter().collect()),
+            None => (signal.clone(), Vec::new()),
+        };
+        match ports.iter_mut().find(|(port, _)| port == &name) {
+            Some((_, declared)) => declared.extend(bits),
+            None => ports.push((name, bits)),
+        }
+    }
+    ports
+        .into_iter()
+        .map(|(name, bits)| {
+            if bits.is_empty() {
+                return Ok(name);
+            }
+            let width = bits.iter().max().unwrap() + 1;
+            match (0..width).find(|bit| !bits.contains(bit)) {
+                Some(bit) => Err(Error::Unsupported {
+                    part: model.name.clone(),
+                    msg: format!("the port {}[{}] is not declared", name, bit),
+                }),
+                None => Ok(format!("{}[{}]", name, width)),
+            }
+        })
+        .collect()
+}
+
+/// splits `src` in to commands made of words
+/// comments start with `#` and a `\` at the end of a line continues the command on the next line
+pub(crate) fn commands(src: &str) -> Vec<Vec<Word>> {
+    let mut commands = Vec::new();
+    let mut words = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let (line, continued) = match line.trim_end().strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut start = None;
+        for (j, c) in chars.iter().chain([' '].iter()).enumerate() {
+            match start {
+                Some(s) if c.is_whitespace() => {
+                    words.push(Word {
+                        text: chars[s..j].iter().collect(),
+                        line: i + 1,
+                        column: s + 1,
+                    });
+                    start = None;
+                }
+                None if !c.is_whitespace() => start = Some(j),
+                _ => (),
+            }
+        }
+
+        if !continued && !words.is_empty() {
+            commands.push(std::mem::take(&mut words));
+        }
+    }
+    if !words.is_empty() {
+        commands.push(words);
+    }
+    commands
+}
+
+/// parses every model in `src`
+fn parse(src: &str) -> Result<Vec<Model>, Error> {
+    let mut models: Vec<Model> = Vec::new();
+    // true while the last model has not been closed by `.end`
+    let mut open = false;
+    let mut commands = commands(src).into_iter().peekable();
+
+    while let Some(command) = commands.next() {
+        let (keyword, args) = (&command[0], &command[1..]);
+        if keyword.text == ".model" {
+            let name = match args.first() {
+                Some(name) => name.text.clone(),
+                None => return Err(keyword.error(".model needs a name".to_string())),
+            };
+            if let Some(other) = models.iter().find(|model| model.name == name) {
+                return Err(args[0].error(format!("model {} is defined twice", other.name)));
+            }
+            models.push(Model {
+                name,
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                parts: Vec::new(),
+            });
+            open = true;
+            continue;
+        }
+
+        let model = match models.last_mut() {
+            Some(model) if open => model,
+            _ => return Err(keyword.error(format!("expected .model but found {}", keyword.text))),
+        };
+        match keyword.text.as_str() {
+            ".inputs" => model.inputs.extend(signals(args)?),
+            ".outputs" => model.outputs.extend(signals(args)?),
+            ".names" => {
+                let mut cubes = Vec::new();
+                while let Some(next) = commands.peek() {
+                    if next[0].text.starts_with('.') {
+                        break;
+                    }
+                    cubes.push(commands.next().unwrap());
+                }
+                let (inputs, output) = match signals(args)?.split_last() {
+                    Some((output, inputs)) => (inputs.to_vec(), output.clone()),
+                    None => return Err(keyword.error(".names needs an output".to_string())),
+                };
+                if inputs.len() > MAX_INPUTS {
+                    return Err(keyword.error(format!(
+                        ".names has {} inputs but at most {} are supported",
+                        inputs.len(),
+                        MAX_INPUTS
+                    )));
+                }
+                let lut = names(&format!("{}.{}", model.name, output), inputs.len(), &cubes)?;
+                model.parts.push(Part::Names(Box::new(lut), inputs, output));
+            }
+            ".latch" => model.parts.push(latch(keyword, args)?),
+            ".subckt" => {
+                let name = match args.first() {
+                    Some(name) => name.text.clone(),
+                    None => return Err(keyword.error(".subckt needs a model".to_string())),
+                };
+                let mut pins = Vec::new();
+                for arg in args[1..].iter() {
+                    match arg.text.split_once('=') {
+                        Some((formal, actual)) => {
+                            signal(arg, formal)?;
+                            signal(arg, actual)?;
+                            pins.push((formal.to_string(), actual.to_string()));
+                        }
+                        None => {
+                            return Err(
+                                arg.error(format!("expected formal=actual but found {}", arg.text))
+                            )
+                        }
+                    }
+                }
+                model.parts.push(Part::Subckt { model: name, pins });
+            }
+            ".end" => open = false,
+            text if IGNORED.contains(&text) => (),
+            text if text.starts_with('.') => {
+                return Err(keyword.error(format!("{} is not supported", text)))
+            }
+            text => return Err(keyword.error(format!("{} is not part of a .names cover", text))),
+        }
+    }
+
+    Ok(models)
+}
+
+/// checks that a signal is a name or a bit like `a[3]`, names can not contain other brackets
+fn signal(word: &Word, text: &str) -> Result<(), Error> {
+    let name = match text.strip_suffix(']').and_then(|s| s.split_once('[')) {
+        Some((name, bit)) if bit.parse::<usize>().is_ok() => name,
+        _ => text,
+    };
+    if name.is_empty() || name.contains(['[', ']']) {
+        Err(word.error(format!("{} is not a valid signal name", text)))
+    } else {
+        Ok(())
+    }
+}
+
+fn signals(words: &[Word]) -> Result<Vec<String>, Error> {
+    words
+        .iter()
+        .map(|word| signal(word, &word.text).map(|_| word.text.clone()))
+        .collect()
+}
+
+/// creates the lookup table of a `.names` cover with `n` inputs
+/// the cubes of the cover either all list the rows that are 1 or all list the rows that are 0
+fn names(name: &str, n: usize, cubes: &[Vec<Word>]) -> Result<LookupTable, Error> {
+    // (pattern, output) of every cube
+    let mut patterns: Vec<&str> = Vec::new();
+    let mut on_set = None;
+    for cube in cubes {
+        let (pattern, output) = match cube.as_slice() {
+            [output] if n == 0 => ("", output),
+            [pattern, output] if n > 0 => (pattern.text.as_str(), output),
+            _ => {
+                return Err(
+                    cube[0].error(format!("expected a cube with {} inputs and an output", n))
+                )
+            }
+        };
+        if pattern.chars().count() != n || pattern.chars().any(|c| !"01-".contains(c)) {
+            return Err(cube[0].error(format!(
+                "{} is not a cube of {} inputs made of 0, 1 and -",
+                pattern, n
+            )));
+        }
+        let value = match output.text.as_str() {
+            "1" => true,
+            "0" => false,
+            text => return Err(output.error(format!("expected 0 or 1 but found {}", text))),
+        };
+        if *on_set.get_or_insert(value) != value {
+            return Err(output.error("a cover can not mix 0 and 1 outputs".to_string()));
+        }
+        patterns.push(pattern);
+    }
+    let on_set = on_set.unwrap_or(true);
+
+    let mut values = vec![false; n];
+    let mut table = vec![false; 2_usize.pow(n as u32)];
+    loop {
+        let matched = patterns.iter().any(|pattern| {
+            pattern
+                .chars()
+                .zip(values.iter())
+                .all(|(c, &value)| c == '-' || (c == '1') == value)
+        });
+        table[bool_to_u32(values.clone()) as usize] = matched == on_set;
+        if !update_values(&mut values) {
+            break;
+        }
+    }
+
+    let in_names: Vec<String> = (0..n).map(|i| format!("in{}", i)).collect();
+    LookupTable::new(
+        vec![table],
+        in_names.iter().map(|s| s.as_str()).collect(),
+        vec!["out"],
+        name,
+    )
+}
+
+/// `.latch input output [type control] [init]`
+fn latch(keyword: &Word, args: &[Word]) -> Result<Part, Error> {
+    let init = match args {
+        [_, _] | [_, _, _, _] => None,
+        [_, _, init] | [_, _, _, _, init] => Some(init),
+        _ => return Err(keyword.error(
+            ".latch needs an input, an output, an optional type and control and an optional init"
+                .to_string(),
+        )),
+    };
+    if let [_, _, kind, _, ..] = args {
+        if !["fe", "re", "ah", "al", "as"].contains(&kind.text.as_str()) {
+            return Err(kind.error(format!("{} is not a latch type", kind.text)));
+        }
+    }
+    // 2 and 3 are don't care and unknown, the latch starts with 0 for both
+    let init = match init.map(|init| init.text.as_str()) {
+        None | Some("0") | Some("2") | Some("3") => false,
+        Some("1") => true,
+        Some(text) => {
+            return Err(init
+                .unwrap()
+                .error(format!("{} is not an initial value", text)))
+        }
+    };
+
+    let control = match args {
+        [_, _, kind, control, ..] => {
+            signal(control, &control.text)?;
+            Some((kind.text.clone(), control.text.clone()))
+        }
+        _ => None,
+    };
+
+    signal(&args[0], &args[0].text)?;
+    signal(&args[1], &args[1].text)?;
+    Ok(Part::Latch {
+        input: args[0].text.clone(),
+        output: args[1].text.clone(),
+        control,
+        init,
+    })
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::elaborate::{bit_names, Cell, Netlist};
+use crate::{Circuit, Diagnostic, Error, LookupTable};
+
+// the id of the next `CircuitBuilder`, handles store the id of the builder that created them
+static NEXT_BUILDER: AtomicUsize = AtomicUsize::new(0);
+
+/// handle of an input bit added to a `CircuitBuilder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputId(usize, usize);
+
+/// handle of an output bit added to a `CircuitBuilder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutputId(usize, usize);
+
+/// handle of a lookup table instance added to a `CircuitBuilder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceId(usize, usize);
+
+/// the driving side of a connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Input(InputId),
+    /// an output pin of a lookup table instance
+    Pin(InstanceId, String),
+}
+
+/// the driven side of a connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sink {
+    Output(OutputId),
+    /// an input pin of a lookup table instance
+    Pin(InstanceId, String),
+}
+
+impl From<InputId> for Source {
+    fn from(id: InputId) -> Self {
+        Self::Input(id)
+    }
+}
+
+impl From<(InstanceId, &str)> for Source {
+    fn from((id, pin): (InstanceId, &str)) -> Self {
+        Self::Pin(id, pin.to_string())
+    }
+}
+
+impl From<OutputId> for Sink {
+    fn from(id: OutputId) -> Self {
+        Self::Output(id)
+    }
+}
+
+impl From<(InstanceId, &str)> for Sink {
+    fn from((id, pin): (InstanceId, &str)) -> Self {
+        Self::Pin(id, pin.to_string())
+    }
+}
+
+/// builds a `Circuit` directly from lookup table instances without a `ChipDef`
+///
+/// ```
+/// use hardware_sim::{CircuitBuilder, LookupTable};
+///
+/// let nand = LookupTable::new(
+///     vec![vec![true, true, true, false]],
+///     vec!["a", "b"],
+///     vec!["out"],
+///     "Nand",
+/// )
+/// .unwrap();
+///
+/// let mut builder = CircuitBuilder::new("Not");
+/// let input = builder.input("in");
+/// let output = builder.output("out");
+/// let not = builder.add_lut("not", nand);
+/// builder
+///     .connect(input, (not, "a"))
+///     .connect(input, (not, "b"))
+///     .connect((not, "out"), output);
+///
+/// let mut circuit = builder.build().unwrap();
+/// circuit.tick().unwrap();
+/// assert_eq!(circuit.get("out"), Ok(true));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitBuilder {
+    id: usize,
+    name: String,
+    // (name, width) of the ports
+    in_buses: Vec<(String, usize)>,
+    out_buses: Vec<(String, usize)>,
+    // names of the port bits, indexed by `InputId` and `OutputId`
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    // (instance name, lut), indexed by `InstanceId`
+    instances: Vec<(String, LookupTable)>,
+    connections: Vec<(Source, Sink)>,
+}
+
+impl CircuitBuilder {
+    /// creates an empty builder for a `Circuit` named `name`
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: NEXT_BUILDER.fetch_add(1, Ordering::Relaxed),
+            name: name.to_string(),
+            in_buses: Vec::new(),
+            outputs: Vec::new(),
+            inputs: Vec::new(),
+            out_buses: Vec::new(),
+            instances: Vec::new(),
+            connections: Vec::new(),
+        }
+    }
+
+    /// adds a 1 bit input named `name`
+    pub fn input(&mut self, name: &str) -> InputId {
+        self.input_bus(name, 1)[0]
+    }
+
+    /// adds an input bus named `name`, the handles are returned least significant bit first
+    pub fn input_bus(&mut self, name: &str, width: usize) -> Vec<InputId> {
+        self.in_buses.push((name.to_string(), width));
+        bit_names(name, width)
+            .into_iter()
+            .map(|bit| {
+                self.inputs.push(bit);
+                InputId(self.id, self.inputs.len() - 1)
+            })
+            .collect()
+    }
+
+    /// adds a 1 bit output named `name`
+    pub fn output(&mut self, name: &str) -> OutputId {
+        self.output_bus(name, 1)[0]
+    }
+
+    /// adds an output bus named `name`, the handles are returned least significant bit first
+    pub fn output_bus(&mut self, name: &str, width: usize) -> Vec<OutputId> {
+        self.out_buses.push((name.to_string(), width));
+        bit_names(name, width)
+            .into_iter()
+            .map(|bit| {
+                self.outputs.push(bit);
+                OutputId(self.id, self.outputs.len() - 1)
+            })
+            .collect()
+    }
+
+    /// adds an instance of `lut` that can be found in the `Circuit` as `instance`
+    pub fn add_lut(&mut self, instance: &str, lut: LookupTable) -> InstanceId {
+        self.instances.push((instance.to_string(), lut));
+        InstanceId(self.id, self.instances.len() - 1)
+    }
+
+    /// connects an input or an output pin of an instance to an output or an input pin of an instance
+    /// the connection is checked by `build`
+    pub fn connect(&mut self, from: impl Into<Source>, to: impl Into<Sink>) -> &mut Self {
+        self.connections.push((from.into(), to.into()));
+        self
+    }
+
+    /// checks the connections and creates the `Circuit`
+    /// returns `Error::Invalid` listing every problem, unused inputs are allowed
+    pub fn build(self) -> Result<Circuit, Error> {
+        let chip = self.name.clone();
+        let mut errors = Vec::new();
+
+        for (i, (instance, _)) in self.instances.iter().enumerate() {
+            if self.instances[..i]
+                .iter()
+                .any(|(other, _)| other == instance)
+            {
+                errors.push(Diagnostic::DuplicateInstance {
+                    chip: chip.clone(),
+                    instance: instance.clone(),
+                });
+            }
+        }
+
+        let mut ports: Vec<&String> = Vec::new();
+        for (port, _) in self.in_buses.iter().chain(self.out_buses.iter()) {
+            if ports.contains(&port) {
+                errors.push(Diagnostic::DuplicatePort {
+                    chip: chip.clone(),
+                    port: port.clone(),
+                });
+            }
+            ports.push(port);
+        }
+
+        // names of the net driven by a source, none for a handle of another builder
+        let net = |source: &Source| match source {
+            Source::Input(InputId(builder, id)) if *builder == self.id => {
+                self.inputs.get(*id).cloned()
+            }
+            Source::Pin(InstanceId(builder, id), pin) if *builder == self.id => self
+                .instances
+                .get(*id)
+                .map(|(instance, _)| format!("{}.{}", instance, pin)),
+            _ => None,
+        };
+        // names of the driven sinks, none for a handle of another builder
+        let sink_name = |sink: &Sink| match sink {
+            Sink::Output(OutputId(builder, id)) if *builder == self.id => {
+                self.outputs.get(*id).cloned()
+            }
+            Sink::Pin(InstanceId(builder, id), pin) if *builder == self.id => self
+                .instances
+                .get(*id)
+                .map(|(instance, _)| format!("{}.{}", instance, pin)),
+            _ => None,
+        };
+        let unknown_pin = |id: usize, pin: &String| {
+            let (instance, lut) = &self.instances[id];
+            Diagnostic::UnknownPin {
+                chip: chip.clone(),
+                instance: instance.clone(),
+                part: lut.name(),
+                pin: pin.clone(),
+            }
+        };
+
+        // maps every sink to the nets driving it
+        let mut drivers: HashMap<String, Vec<String>> = HashMap::new();
+        let mut cells: Vec<Cell> = self
+            .instances
+            .iter()
+            .map(|(instance, lut)| Cell {
+                name: instance.clone(),
+                lut: lut.clone(),
+                inputs: Vec::new(),
+                outputs: lut
+                    .out_names()
+                    .iter()
+                    .enumerate()
+                    .map(|(port, pin)| (port, format!("{}.{}", instance, pin)))
+                    .collect(),
+            })
+            .collect();
+        let mut aliases = HashMap::new();
+
+        for (source, sink) in self.connections.iter() {
+            let (source_net, sink_net) = match (net(source), sink_name(sink)) {
+                (Some(source_net), Some(sink_net)) => (source_net, sink_net),
+                _ => {
+                    errors.push(Diagnostic::InvalidConnection {
+                        chip: chip.clone(),
+                        connection: format!("{:?} -> {:?}", source, sink),
+                        reason: "the handle belongs to another builder".to_string(),
+                    });
+                    continue;
+                }
+            };
+            if let Source::Pin(InstanceId(_, id), pin) = source {
+                if self.instances[*id].1.out_map(pin).is_none() {
+                    errors.push(unknown_pin(*id, pin));
+                    continue;
+                }
+            }
+
+            match sink {
+                Sink::Pin(InstanceId(_, id), pin) => match self.instances[*id].1.in_map(pin) {
+                    Some(port) => cells[*id].inputs.push((port, source_net.clone())),
+                    None => {
+                        errors.push(unknown_pin(*id, pin));
+                        continue;
+                    }
+                },
+                Sink::Output(OutputId(_, id)) => {
+                    aliases.insert(self.outputs[*id].clone(), source_net.clone());
+                }
+            }
+
+            drivers.entry(sink_net).or_default().push(source_net);
+        }
+
+        for (wire, drivers) in drivers.iter() {
+            if drivers.len() > 1 {
+                errors.push(Diagnostic::MultipleDrivers {
+                    chip: chip.clone(),
+                    wire: wire.clone(),
+                    drivers: drivers.clone(),
+                });
+            }
+        }
+
+        for (instance, lut) in self.instances.iter() {
+            let connected: Vec<String> = lut
+                .in_names()
+                .into_iter()
+                .filter(|pin| drivers.contains_key(&format!("{}.{}", instance, pin)))
+                .collect();
+            if connected.len() != lut.in_names().len() {
+                errors.push(Diagnostic::PinCount {
+                    chip: chip.clone(),
+                    instance: instance.clone(),
+                    part: lut.name(),
+                    expected: lut.in_names(),
+                    connected,
+                });
+            }
+        }
+
+        for output in self.outputs.iter() {
+            if !drivers.contains_key(output) {
+                errors.push(Diagnostic::OutputNotDriven {
+                    chip: chip.clone(),
+                    output: output.clone(),
+                });
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(Error::Invalid(errors));
+        }
+
+        Circuit::from_netlist(Netlist {
+            name: self.name,
+            inputs: self.inputs,
+            outputs: self.outputs,
+            in_buses: self.in_buses,
+            out_buses: self.out_buses,
+            cells,
+            states: Vec::new(),
+            aliases,
+        })
+    }
+}
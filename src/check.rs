@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::chip_def::split_slice;
+use crate::elaborate::{bit_names, resolve_all, slice_bits, Callee, Resolved};
+use crate::{ChipDef, Library};
+
+/// a problem found while checking a `ChipDef`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diagnostic {
+    /// no chip or lookup table in the library has the name of the part
+    MissingPart {
+        chip: String,
+        instance: String,
+        part: String,
+    },
+
+    /// a chip instantiates itself, `path` lists the chips from the outermost one to the repeated one
+    Recursion { chip: String, path: Vec<String> },
+
+    /// two parts of a chip have the same instance name
+    DuplicateInstance { chip: String, instance: String },
+
+    /// two inputs or outputs of a chip have the same name
+    DuplicatePort { chip: String, port: String },
+
+    /// a part connects a pin that the chip or lookup table dose not have
+    UnknownPin {
+        chip: String,
+        instance: String,
+        part: String,
+        pin: String,
+    },
+
+    /// a part dose not connect all inputs of the chip or lookup table
+    PinCount {
+        chip: String,
+        instance: String,
+        part: String,
+        expected: Vec<String>,
+        connected: Vec<String>,
+    },
+
+    /// both sides of a connection have a different number of bits
+    WidthMismatch {
+        chip: String,
+        instance: String,
+        pin: String,
+        pin_width: usize,
+        wire: String,
+        wire_width: usize,
+    },
+
+    /// a connection can not be parsed or selects bits outside of a bus
+    InvalidConnection {
+        chip: String,
+        connection: String,
+        reason: String,
+    },
+
+    /// an internal wire is read but nothing drives it
+    Undriven { chip: String, wire: String },
+
+    /// a wire is driven by more than one source
+    MultipleDrivers {
+        chip: String,
+        wire: String,
+        drivers: Vec<String>,
+    },
+
+    /// an output of the chip is never driven
+    OutputNotDriven { chip: String, output: String },
+
+    /// an input of the chip is never read
+    UnusedInput { chip: String, input: String },
+
+    /// an internal wire is driven but never read
+    UnusedWire { chip: String, wire: String },
+}
+
+impl Diagnostic {
+    /// returns false for warnings that do not stop the chip from being simulated
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Self::UnusedInput { .. } | Self::UnusedWire { .. })
+    }
+
+    /// gets the name of the chip the problem was found in
+    pub fn chip(&self) -> &str {
+        match self {
+            Self::MissingPart { chip, .. }
+            | Self::Recursion { chip, .. }
+            | Self::DuplicateInstance { chip, .. }
+            | Self::DuplicatePort { chip, .. }
+            | Self::UnknownPin { chip, .. }
+            | Self::PinCount { chip, .. }
+            | Self::WidthMismatch { chip, .. }
+            | Self::InvalidConnection { chip, .. }
+            | Self::Undriven { chip, .. }
+            | Self::MultipleDrivers { chip, .. }
+            | Self::OutputNotDriven { chip, .. }
+            | Self::UnusedInput { chip, .. }
+            | Self::UnusedWire { chip, .. } => chip,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPart {
+                chip,
+                instance,
+                part,
+            } => write!(
+                f,
+                "{}: no chip or lookup table named {} (part {})",
+                chip, part, instance
+            ),
+            Self::Recursion { chip, path } => {
+                write!(
+                    f,
+                    "{}: chip instantiates itself ({})",
+                    chip,
+                    path.join(" -> ")
+                )
+            }
+            Self::DuplicateInstance { chip, instance } => {
+                write!(
+                    f,
+                    "{}: instance name {} is used more than once",
+                    chip, instance
+                )
+            }
+            Self::DuplicatePort { chip, port } => {
+                write!(f, "{}: port name {} is used more than once", chip, port)
+            }
+            Self::UnknownPin {
+                chip,
+                instance,
+                part,
+                pin,
+            } => write!(
+                f,
+                "{}: {} has no pin named {} (part {})",
+                chip, part, pin, instance
+            ),
+            Self::PinCount {
+                chip,
+                instance,
+                part,
+                expected,
+                connected,
+            } => write!(
+                f,
+                "{}: {} expects the pins [{}] but [{}] are connected (part {})",
+                chip,
+                part,
+                expected.join(", "),
+                connected.join(", "),
+                instance
+            ),
+            Self::WidthMismatch {
+                chip,
+                instance,
+                pin,
+                pin_width,
+                wire,
+                wire_width,
+            } => write!(
+                f,
+                "{}: {} is {} bits wide but {} is {} bits wide (part {})",
+                chip, pin, pin_width, wire, wire_width, instance
+            ),
+            Self::InvalidConnection {
+                chip,
+                connection,
+                reason,
+            } => write!(f, "{}: invalid connection {}: {}", chip, connection, reason),
+            Self::Undriven { chip, wire } => {
+                write!(f, "{}: wire {} is read but never driven", chip, wire)
+            }
+            Self::MultipleDrivers {
+                chip,
+                wire,
+                drivers,
+            } => write!(
+                f,
+                "{}: wire {} is driven by [{}]",
+                chip,
+                wire,
+                drivers.join(", ")
+            ),
+            Self::OutputNotDriven { chip, output } => {
+                write!(f, "{}: output {} is never driven", chip, output)
+            }
+            Self::UnusedInput { chip, input } => {
+                write!(f, "{}: input {} is never read", chip, input)
+            }
+            Self::UnusedWire { chip, wire } => {
+                write!(f, "{}: wire {} is driven but never read", chip, wire)
+            }
+        }
+    }
+}
+
+/// checks `chip_def` and every chip it uses
+pub(crate) fn check(chip_def: &ChipDef, library: &Library) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check_chip(
+        chip_def,
+        library,
+        &mut vec![chip_def.name()],
+        &mut HashSet::new(),
+        &mut diagnostics,
+    );
+    diagnostics
+}
+
+/// `stack` holds the chips currently being checked and `checked` all chips that were already checked
+fn check_chip(
+    chip_def: &ChipDef,
+    library: &Library,
+    stack: &mut Vec<String>,
+    checked: &mut HashSet<String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let chip = chip_def.name();
+    checked.insert(chip.clone());
+    let invalid = |connection: &str, reason: String| Diagnostic::InvalidConnection {
+        chip: chip.clone(),
+        connection: connection.to_string(),
+        reason,
+    };
+
+    let Resolved {
+        widths,
+        parts,
+        maps,
+        diagnostics: problems,
+    } = resolve_all(chip_def, library);
+    diagnostics.extend(problems);
+
+    // parts that instantiate a chip from the stack are left out of the bit level analysis
+    let mut checked_parts = Vec::new();
+    for (instance, name, callee, pins) in parts {
+        if let Callee::Chip(sub_def) = callee {
+            if stack.contains(&name) {
+                let mut path = stack.clone();
+                path.push(name.clone());
+                diagnostics.push(Diagnostic::Recursion {
+                    chip: chip.clone(),
+                    path,
+                });
+                continue;
+            }
+            if !checked.contains(&name) {
+                stack.push(name.clone());
+                check_chip(sub_def, library, stack, checked, diagnostics);
+                stack.pop();
+            }
+        }
+        checked_parts.push((instance, callee, pins));
+    }
+    let parts = checked_parts;
+
+    // bit level analysis, every bit is a net with drivers and a number of readers
+    let mut drivers: HashMap<String, Vec<String>> = HashMap::new();
+    let mut readers: HashMap<String, usize> = HashMap::new();
+    // all bits in the order they appear so that the diagnostics are deterministic
+    let mut bits = Vec::new();
+    let seen = |bit: &String, bits: &mut Vec<String>| {
+        if !bits.contains(bit) {
+            bits.push(bit.clone());
+        }
+    };
+
+    for input in chip_def.inputs() {
+        for bit in bit_names(&input, chip_def.width(&input)) {
+            seen(&bit, &mut bits);
+            drivers
+                .entry(bit)
+                .or_default()
+                .push(format!("input {}", input));
+        }
+    }
+    for output in chip_def.outputs() {
+        for bit in bit_names(&output, chip_def.width(&output)) {
+            seen(&bit, &mut bits);
+            *readers.entry(bit).or_default() += 1;
+        }
+    }
+
+    let signal = |expr: &String| slice_bits(expr, |name| widths.get(name).copied());
+    for (instance, callee, pins) in parts.iter() {
+        for (pin, wire) in pins.iter() {
+            let (pin_bits, wire_bits) =
+                match (slice_bits(pin, |pin| callee.width(pin)), signal(wire)) {
+                    (Ok(pin_bits), Ok(wire_bits)) => (pin_bits, wire_bits),
+                    (Err(err), _) => {
+                        diagnostics.push(invalid(pin, err));
+                        continue;
+                    }
+                    (_, Err(err)) => {
+                        diagnostics.push(invalid(wire, err));
+                        continue;
+                    }
+                };
+            if pin_bits.len() != wire_bits.len() {
+                diagnostics.push(Diagnostic::WidthMismatch {
+                    chip: chip.clone(),
+                    instance: instance.clone(),
+                    pin: pin.clone(),
+                    pin_width: pin_bits.len(),
+                    wire: wire.clone(),
+                    wire_width: wire_bits.len(),
+                });
+                continue;
+            }
+
+            let input =
+                callee.is_input(&split_slice(pin).map(|(name, _)| name).unwrap_or_default());
+            for (pin_bit, wire_bit) in pin_bits.iter().zip(wire_bits) {
+                seen(&wire_bit, &mut bits);
+                if input {
+                    *readers.entry(wire_bit).or_default() += 1;
+                } else {
+                    drivers
+                        .entry(wire_bit)
+                        .or_default()
+                        .push(format!("{}.{}", instance, pin_bit));
+                }
+            }
+        }
+    }
+
+    // maps merge bits in to one net
+    let mut parent: HashMap<String, String> = HashMap::new();
+    let find = |parent: &HashMap<String, String>, mut bit: String| {
+        while let Some(next) = parent.get(&bit) {
+            bit = next.clone();
+        }
+        bit
+    };
+    for (instance, (a, b)) in maps.iter() {
+        let (a_bits, b_bits) = match (signal(a), signal(b)) {
+            (Ok(a_bits), Ok(b_bits)) => (a_bits, b_bits),
+            (Err(err), _) => {
+                diagnostics.push(invalid(a, err));
+                continue;
+            }
+            (_, Err(err)) => {
+                diagnostics.push(invalid(b, err));
+                continue;
+            }
+        };
+        if a_bits.len() != b_bits.len() {
+            diagnostics.push(Diagnostic::WidthMismatch {
+                chip: chip.clone(),
+                instance: instance.clone(),
+                pin: a.clone(),
+                pin_width: a_bits.len(),
+                wire: b.clone(),
+                wire_width: b_bits.len(),
+            });
+            continue;
+        }
+        for (a, b) in a_bits.into_iter().zip(b_bits) {
+            seen(&a, &mut bits);
+            seen(&b, &mut bits);
+            let (a, b) = (find(&parent, a), find(&parent, b));
+            if a != b {
+                parent.insert(b, a);
+            }
+        }
+    }
+
+    // (bits, drivers, readers) of every net
+    let mut nets: Vec<(Vec<String>, Vec<String>, usize)> = Vec::new();
+    let mut net_ids: HashMap<String, usize> = HashMap::new();
+    for bit in bits {
+        let root = find(&parent, bit.clone());
+        let id = *net_ids.entry(root).or_insert_with(|| {
+            nets.push((Vec::new(), Vec::new(), 0));
+            nets.len() - 1
+        });
+        let net = &mut nets[id];
+        net.1.extend(drivers.remove(&bit).unwrap_or_default());
+        net.2 += readers.remove(&bit).unwrap_or_default();
+        net.0.push(bit);
+    }
+
+    let is_port = |bit: &String, ports: Vec<String>| {
+        ports
+            .iter()
+            .any(|port| bit_names(port, chip_def.width(port)).contains(bit))
+    };
+    for (bits, drivers, readers) in nets {
+        let inputs: Vec<&String> = bits
+            .iter()
+            .filter(|bit| is_port(bit, chip_def.inputs()))
+            .collect();
+        let outputs: Vec<&String> = bits
+            .iter()
+            .filter(|bit| is_port(bit, chip_def.outputs()))
+            .collect();
+
+        if drivers.len() > 1 {
+            diagnostics.push(Diagnostic::MultipleDrivers {
+                chip: chip.clone(),
+                wire: bits[0].clone(),
+                drivers: drivers.clone(),
+            });
+        }
+
+        if drivers.is_empty() {
+            for output in outputs.iter() {
+                diagnostics.push(Diagnostic::OutputNotDriven {
+                    chip: chip.clone(),
+                    output: output.to_string(),
+                });
+            }
+            if outputs.is_empty() && readers > 0 {
+                diagnostics.push(Diagnostic::Undriven {
+                    chip: chip.clone(),
+                    wire: bits[0].clone(),
+                });
+            }
+        }
+
+        if readers == 0 {
+            for input in inputs.iter() {
+                diagnostics.push(Diagnostic::UnusedInput {
+                    chip: chip.clone(),
+                    input: input.to_string(),
+                });
+            }
+            if inputs.is_empty() && !drivers.is_empty() {
+                diagnostics.push(Diagnostic::UnusedWire {
+                    chip: chip.clone(),
+                    wire: bits[0].clone(),
+                });
+            }
+        }
+    }
+}
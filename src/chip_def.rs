@@ -1,23 +1,40 @@
+use std::collections::HashMap;
+
+use crate::check::check;
+use crate::hdl;
+use crate::verilog;
+use crate::{Diagnostic, Error, Library};
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "crate::repr::ChipDefRepr", from = "crate::repr::ChipDefRepr")
+)]
 pub struct ChipDef {
     name: String,
     inputs: Vec<String>,
     outputs: Vec<String>,
+    // widths of the bus ports, ports that are not in here are 1 bit wide
+    widths: HashMap<String, usize>,
     parts: Vec<Component>,
+    // user given instance names by the index of the part
+    instances: HashMap<usize, String>,
 }
 
 impl ChipDef {
+    /// ports can be declared as buses by appending the width e.g. `a[16]`
     pub fn new(
         name: &str,
         inputs: Vec<&str>,
         outputs: Vec<&str>,
         parts: Vec<(Vec<(&str, &str)>, Vec<(&str, &str)>, &str)>,
     ) -> Self {
-        Self {
-            name: name.to_string(),
-            inputs: inputs.iter().map(|&s| s.to_string()).collect(),
-            outputs: outputs.iter().map(|&s| s.to_string()).collect(),
-            parts: parts
+        Self::new_string(
+            name.to_string(),
+            inputs.iter().map(|&s| s.to_string()).collect(),
+            outputs.iter().map(|&s| s.to_string()).collect(),
+            parts
                 .iter()
                 .map(|(inputs, outputs, name)| Component::Def {
                     inputs: inputs
@@ -31,23 +48,87 @@ impl ChipDef {
                     name: name.to_string(),
                 })
                 .collect(),
-        }
+        )
     }
 
+    /// ports can be declared as buses by appending the width e.g. `a[16]`
     pub fn new_string(
         name: String,
         inputs: Vec<String>,
         outputs: Vec<String>,
         parts: Vec<Component>,
     ) -> Self {
+        let mut widths = HashMap::new();
+        let mut port = |decl: String| match split_width(&decl) {
+            Some((name, width)) => {
+                widths.insert(name.clone(), width);
+                name
+            }
+            None => decl,
+        };
+        let inputs = inputs.into_iter().map(&mut port).collect();
+        let outputs = outputs.into_iter().map(&mut port).collect();
+
         Self {
             name,
             inputs,
             outputs,
+            widths,
             parts,
+            instances: HashMap::new(),
         }
     }
 
+    /// like `new_string` with the user given instance name of every part that has one
+    pub(crate) fn with_instances(
+        name: String,
+        inputs: Vec<String>,
+        outputs: Vec<String>,
+        parts: Vec<(Component, Option<String>)>,
+    ) -> Self {
+        let (parts, names): (Vec<Component>, Vec<Option<String>>) = parts.into_iter().unzip();
+        let mut chip_def = Self::new_string(name, inputs, outputs, parts);
+        chip_def.instances = names
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, name)| Some((i, name?)))
+            .collect();
+        chip_def
+    }
+
+    /// parses a chip written in nand2tetris hdl e.g. `CHIP Not { IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }`
+    /// the source has to contain exactly one chip with parts, use `Library::add_hdl` for `BUILTIN` chips
+    /// the constants `true` and `false` are driven by the lookup tables `$true` and `$false` that `Library::add_hdl` adds
+    pub fn from_hdl(src: &str) -> Result<Self, Error> {
+        let chips = hdl::parse(src)?;
+        let chip = match &chips[..] {
+            [chip] => chip,
+            [] => {
+                return Err(Error::Parse {
+                    line: 1,
+                    column: 1,
+                    msg: "expected a chip".to_string(),
+                })
+            }
+            [_, chip, ..] => {
+                return Err(Error::Parse {
+                    line: chip.pos.0,
+                    column: chip.pos.1,
+                    msg: "expected only one chip, use Library::add_hdl for more".to_string(),
+                })
+            }
+        };
+        chip.to_chip_def(|name| hdl::in_pins(&chips, &Library::new(), name))
+            .ok_or_else(|| Error::Parse {
+                line: chip.pos.0,
+                column: chip.pos.1,
+                msg: format!(
+                    "{} is a builtin chip, use Library::add_hdl to get its lookup table",
+                    chip.name
+                ),
+            })
+    }
+
     pub fn name(&self) -> String {
         self.name.clone()
     }
@@ -63,9 +144,111 @@ impl ChipDef {
     pub fn parts(&self) -> Vec<Component> {
         self.parts.clone()
     }
+
+    /// gets the number of bits of the port `name`, ports that are not declared as buses are 1 bit wide
+    pub fn width(&self, name: &str) -> usize {
+        self.widths.get(name).copied().unwrap_or(1)
+    }
+
+    /// writes the chip and every chip and lookup table it uses from the `library` as verilog modules
+    /// returns `Error::Invalid` if the check finds errors and `Error::Unsupported` for a `DFF` because its clock is the tick
+    pub fn to_verilog(&self, library: &Library) -> Result<String, Error> {
+        verilog::chip_modules(self, library)
+    }
+
+    /// checks the `ChipDef` and all chips it uses from the `library`
+    /// returns every problem that was found, use `Diagnostic::is_error` to tell errors from warnings
+    pub fn check(&self, library: &Library) -> Vec<Diagnostic> {
+        check(self, library)
+    }
+
+    /// names the part at `index` so it can be found in the elaborated `Circuit`
+    /// returns an error if there is no `Component::Def` at `index`
+    pub fn set_instance(&mut self, index: usize, instance: &str) -> Result<(), Error> {
+        match self.parts.get(index) {
+            Some(Component::Def { .. }) => {
+                self.instances.insert(index, instance.to_string());
+                Ok(())
+            }
+            _ => Err(Error::Index {
+                index,
+                len: self.parts.len(),
+            }),
+        }
+    }
+
+    /// gets the user given instance names with the index of their part, sorted by the index
+    #[cfg(feature = "serde")]
+    pub(crate) fn user_instances(&self) -> Vec<(usize, String)> {
+        let mut instances: Vec<(usize, String)> = self
+            .instances
+            .iter()
+            .map(|(i, name)| (*i, name.clone()))
+            .collect();
+        instances.sort();
+        instances
+    }
+
+    /// gets the instance name of every part in the same order as `parts`
+    /// parts without a user given name are called after the lower case part name and a counter e.g. `nand_0`, `nand_1`
+    pub fn instance_names(&self) -> Vec<String> {
+        let mut counter = HashMap::new();
+        self.parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                if let Some(instance) = self.instances.get(&i) {
+                    return instance.clone();
+                }
+
+                let name = part.name().to_lowercase();
+                let count = counter.entry(name.clone()).or_insert(0);
+                *count += 1;
+                format!("{}_{}", name, *count - 1)
+            })
+            .collect()
+    }
+}
+
+/// splits a bus declaration like `a[16]` in to its name and width
+pub(crate) fn split_width(decl: &str) -> Option<(String, usize)> {
+    let (name, width) = decl.strip_suffix(']')?.split_once('[')?;
+    match width.trim().parse() {
+        Ok(width) if width > 0 => Some((name.trim().to_string(), width)),
+        _ => None,
+    }
+}
+
+/// splits a connection like `a`, `a[3]` or `a[0..7]` in to its name and the inclusive bit range
+/// returns the reason if the range can not be parsed
+pub(crate) fn split_slice(expr: &str) -> Result<(String, Option<(usize, usize)>), String> {
+    let (name, range) = match expr.strip_suffix(']').and_then(|s| s.split_once('[')) {
+        Some(split) => split,
+        None => return Ok((expr.trim().to_string(), None)),
+    };
+
+    let bit = |s: &str| {
+        s.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid bit index {} in {}", s, expr))
+    };
+    let (start, end) = match range.split_once("..") {
+        Some((start, end)) => (bit(start)?, bit(end)?),
+        None => (bit(range)?, bit(range)?),
+    };
+    if start > end {
+        return Err(format!("invalid bit range in {}", expr));
+    }
+
+    Ok((name.trim().to_string(), Some((start, end))))
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind")
+)]
 pub enum Component {
     Map {
         var_map: Vec<(String, String)>,
@@ -96,11 +279,7 @@ impl Component {
                 name,
             } => name.clone(),
 
-            Self::Def {
-                inputs: _,
-                outputs: _,
-                name,
-            } => name.clone(),
+            Self::Def { name, .. } => name.clone(),
         }
     }
 }
@@ -1,7 +1,12 @@
-use crate::{ChipDef, Error, LookupTable};
+use crate::elaborate::{bit_names, flatten, Netlist};
+use crate::primitive::Primitive;
+use crate::{aiger, blif, verilog};
+use crate::{ChipDef, Diagnostic, Error, Library, LookupTable};
+use bool_algebra::bool_to_u32;
 use bool_algebra::update_values;
 use graph::Graph;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 
 #[derive(Debug, Clone, PartialEq)]
 struct InOut {
@@ -15,6 +20,54 @@ impl InOut {
     }
 }
 
+/// a register whose output is the value its input had in the previous tick
+#[derive(Debug, Clone, PartialEq)]
+struct Dff {
+    init: bool,
+    value: bool,
+    next: bool,
+}
+
+impl Dff {
+    fn new(init: bool) -> Self {
+        Self {
+            init,
+            value: init,
+            next: init,
+        }
+    }
+}
+
+/// a state element with explicit clock or enable pins, its value is updated when it is evaluated
+#[derive(Debug, Clone, PartialEq)]
+struct Storage {
+    primitive: Primitive,
+    inputs: Vec<bool>,
+    // the clock when the element was last evaluated, only a change from 0 to 1 is an edge
+    clk: bool,
+    value: bool,
+}
+
+impl Storage {
+    fn new(primitive: Primitive) -> Self {
+        Self {
+            primitive,
+            inputs: vec![false; primitive.in_names().len()],
+            clk: false,
+            value: false,
+        }
+    }
+
+    /// updates the value for the current inputs and returns it
+    fn update(&mut self) -> bool {
+        self.value = self.primitive.update(&self.inputs, self.clk, self.value);
+        if let Some(clock) = self.primitive.clock() {
+            self.clk = self.inputs[clock];
+        }
+        self.value
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 struct Connection {
     weight: usize,
@@ -32,9 +85,86 @@ impl Connection {
     }
 }
 
+/// the pending work of the event driven simulation
+#[derive(Debug, Clone)]
+struct Events {
+    // the position of every node in a topological order, only edges that close a loop go to a lower rank
+    rank: Vec<usize>,
+    // the outputs each node had when it was last evaluated, `None` if it never was
+    values: Vec<Option<Vec<bool>>>,
+    // nodes that have to be evaluated in the current tick as (rank, node id)
+    queue: BinaryHeap<Reverse<(usize, usize)>>,
+    // nodes driven by a loop, they are evaluated in the next delta cycle
+    deferred: Vec<usize>,
+    queued: Vec<bool>,
+    // the delta cycle in which each node was last evaluated
+    evaluated: Vec<usize>,
+    cycles: usize,
+    // the time of `run_until`
+    now: usize,
+    // the changes of inputs as (time, node id, input port, value) in the slot `time % wheel.len()`
+    // the wheel is longer than the largest delay so every slot holds the changes of a single time
+    wheel: Vec<Vec<(usize, usize, usize, bool)>>,
+    // the number of changes in the wheel
+    pending: usize,
+}
+
+impl Events {
+    /// schedules a node to be evaluated in the current or next delta cycle
+    fn schedule(&mut self, id: usize) {
+        if !self.queued[id] {
+            self.queued[id] = true;
+            self.queue.push(Reverse((self.rank[id], id)));
+        }
+    }
+
+    /// schedules a node driven by the node `from`, if the edge closes a loop it is evaluated in the next delta cycle
+    fn propagate(&mut self, from: usize, to: usize) {
+        if self.rank[to] > self.rank[from] {
+            self.schedule(to);
+        } else {
+            self.defer(to);
+        }
+    }
+
+    /// schedules a node to be evaluated in the next delta cycle
+    fn defer(&mut self, id: usize) {
+        if !self.queued[id] {
+            self.queued[id] = true;
+            self.deferred.push(id);
+        }
+    }
+
+    /// makes the time wheel long enough for changes `delay` time steps ahead
+    fn reserve(&mut self, delay: usize) {
+        if delay < self.wheel.len() {
+            return;
+        }
+        let changes: Vec<_> = self.wheel.iter_mut().flat_map(std::mem::take).collect();
+        self.wheel = vec![Vec::new(); delay + 1];
+        for change in changes {
+            let slot = change.0 % self.wheel.len();
+            self.wheel[slot].push(change);
+        }
+    }
+}
+
+/// how `tick` and `settle` order the evaluation of the lookup tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// changes propagate through the lookup tables in topological order, a loop advances one step per delta cycle
+    #[default]
+    Event,
+    /// every lookup table reads the values of the previous delta cycle and all outputs change together
+    /// the result dose not depend on the order of the nodes, a chain of n lookup tables takes n delta cycles
+    Synchronous,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum Component {
-    Lut(LookupTable),
+    Lut(Box<LookupTable>),
+    Dff(Dff),
+    Storage(Storage),
     In(InOut),
     Out(InOut),
 }
@@ -46,149 +176,206 @@ pub struct Circuit {
     outputs: Vec<usize>,
     in_map: HashMap<String, usize>,
     out_map: HashMap<String, usize>,
+    // maps the instance path of a lut to its node id
+    instances: HashMap<String, usize>,
+    // maps the instance path of a state element to its node id
+    states: HashMap<String, usize>,
+    // nodes that are evaluated every tick even if no input reaches them, state elements and luts without inputs
+    roots: Vec<usize>,
+    // maps the name of a port to the node ids of its bits, least significant bit first
+    in_buses: HashMap<String, Vec<usize>>,
+    out_buses: HashMap<String, Vec<usize>>,
+    // the node id and connection of every edge leaving a node, the weights are the delays of `run_until`
+    fanout: Vec<Vec<(usize, Connection)>>,
+    // `None` after the graph changed, every node is evaluated again in the next tick
+    events: Option<Events>,
+    settle_limit: usize,
+    mode: Mode,
 }
 
 impl Circuit {
     /// creates a new `Circuit` form the `ChipDef`
     pub fn new(chip_def: ChipDef, lut_map: HashMap<String, LookupTable>) -> Result<Self, Error> {
-        let mut circuit = Self::blank(chip_def.name());
-        // maps lut name with the corisponding node id in the circuit graph
-        let mut ids = HashMap::new();
-        // maps the internal connection name the the corisponding (lut id, port id)
-        let mut in_ports = HashMap::new();
-
-        for input in chip_def.inputs() {
-            let lut_id = circuit.add_node(Component::In(InOut::new(input.clone())))?;
-            ids.insert(input.clone(), lut_id);
-        }
+        Self::with_library(chip_def, &Library::from(lut_map))
+    }
 
-        for output in chip_def.outputs() {
-            let lut_id = circuit.add_node(Component::Out(InOut::new(output.clone())))?;
-            ids.insert(output.clone(), lut_id);
-            in_ports.insert(output.clone(), vec![(lut_id, 0)]);
+    /// creates a new `Circuit` form the `ChipDef`
+    /// parts are resolved against the `library` and can be lookup tables or other chips
+    /// returns an error listing every problem if the `ChipDef` is invalid
+    pub fn with_library(chip_def: ChipDef, library: &Library) -> Result<Self, Error> {
+        let errors: Vec<Diagnostic> = chip_def
+            .check(library)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .collect();
+        if !errors.is_empty() {
+            return Err(Error::Invalid(errors));
         }
 
-        // initilse in_ports and add lut as nodes
-        for part in chip_def.parts() {
-            if let crate::Component::Def {
-                inputs,
-                outputs: _,
-                name,
-            } = part
-            {
-                if let Some(lut) = lut_map.get(&name) {
-                    let lut_id = circuit.add_node(Component::Lut(lut.clone()))?;
-                    ids.insert(name, lut_id);
-                    if inputs.len() != lut.in_names().len() {
-                        return Err(Error::msg("wrong size".to_string()));
-                    }
+        Self::from_netlist(flatten(&chip_def, library)?)
+    }
 
-                    for (i, o) in inputs {
-                        if let Some(v) = in_ports.get_mut(&o) {
-                            v.push((lut_id, lut.in_map(&i).unwrap()));
-                        } else {
-                            in_ports.insert(o, vec![(lut_id, lut.in_map(&i).unwrap())]);
-                        }
-                    }
-                } else {
-                    return Err(Error::msg(format!(
-                        "no lookup table found with name {}",
-                        name
-                    )));
-                }
-            } else {
-            }
-        }
+    /// reads an aiger file in the ascii `aag` or the binary `aig` format
+    /// ands become 2 input lookup tables named after their variable e.g. `n5` and latches become state elements
+    pub fn from_aiger(src: &[u8], name: &str) -> Result<Self, Error> {
+        Self::from_netlist(aiger::to_netlist(&aiger::parse(src)?, name))
+    }
 
-        // create conections
-        for part in chip_def.parts() {
-            if let crate::Component::Def {
-                inputs: _,
-                outputs,
-                name,
-            } = part
-            {
-                for (i, o) in outputs {
-                    let from_lut_id = circuit.lut_name(&name)?;
-                    let from_port = circuit.lut_id(from_lut_id)?.out_map(&i).unwrap();
-                    for (to_lut_id, to_port) in in_ports.get(&o).unwrap().clone() {
-                        circuit.add_connection(
-                            from_lut_id,
-                            to_lut_id,
-                            Connection::new(from_port, to_port),
-                        )?;
-                    }
+    /// updates the `Circuit` with one delta cycle
+    /// only the nodes driven by a net that changed are evaluated, in topological order and at most once per tick
+    /// a change that goes around a loop is evaluated in the next tick, see `Mode` for the synchronous evaluation
+    /// `DFF` and `DFF1` output the value they stored in the previous tick and store their new input at the end
+    /// primitives with a clock or enable pin like `DFFC` and `DLatch` are updated when they are evaluated, not by the tick
+    pub fn tick(&mut self) -> Result<(), Error> {
+        let mut events = self.events.take().unwrap_or_else(|| self.events());
+        self.delta(&mut events)?;
+
+        for &id in self.states.values() {
+            if let Ok(Component::Dff(dff)) = self.graph.node_mut(id) {
+                if dff.value != dff.next {
+                    dff.value = dff.next;
+                    events.schedule(id);
                 }
-            } else {
             }
         }
+        self.events = Some(events);
+        Ok(())
+    }
 
-        // connecting io inputs to lut
-        for input in chip_def.inputs() {
-            if let Some(&in_id) = ids.get(&input).clone() {
-                for (to_lut_id, to_port) in in_ports.get(&input).unwrap().clone() {
-                    circuit.add_connection(in_id, to_lut_id, Connection::new(0, to_port))?;
-                }
+    /// evaluates the `Circuit` until no net changes and returns the number of delta cycles that took, 0 if nothing changed
+    /// a delta cycle evaluates the changed nodes like `tick` but state elements keep their value, `tick` stores their inputs
+    /// returns `Error::CombinationalLoop` with the instance paths of the lookup tables that changed in the last delta cycle if the settle limit is hit
+    pub fn settle(&mut self) -> Result<usize, Error> {
+        let mut events = self.events.take().unwrap_or_else(|| self.events());
+        let mut cycles = 0;
+        while !events.queue.is_empty() || !events.deferred.is_empty() {
+            if cycles == self.settle_limit {
+                let paths = self
+                    .lut_paths()
+                    .into_iter()
+                    .filter(|&(id, _)| events.queued[id] || events.evaluated[id] == events.cycles)
+                    .map(|(_, path)| path.clone())
+                    .collect();
+                self.events = Some(events);
+                return Err(Error::CombinationalLoop(paths));
             }
+            self.delta(&mut events)?;
+            cycles += 1;
         }
+        self.events = Some(events);
+        Ok(cycles)
+    }
 
-        Ok(circuit)
+    /// sets the number of delta cycles after which `settle` reports a loop, the default is 1000
+    pub fn set_settle_limit(&mut self, limit: usize) {
+        self.settle_limit = limit;
     }
 
-    /// updates the `Circuit`
-    pub fn tick(&mut self) -> Result<(), Error> {
-        let mut queue = VecDeque::with_capacity(self.inputs.len());
-        let mut viseted = HashSet::new();
+    /// gets the number of delta cycles after which `settle` reports a loop
+    pub fn settle_limit(&self) -> usize {
+        self.settle_limit
+    }
 
-        for input in self.inputs.clone() {
-            queue.push_back(input);
+    /// runs the timed simulation until the time `time` and returns every change of an output as (time, name, value)
+    /// a change of an input, lookup table or state element reaches the inputs it drives after the delay set with `set_delay`, 1 by default
+    /// unlike `tick` this shows the short pulses caused by paths with different delays, state elements keep their value
+    /// nodes changed by `set` or `tick` are evaluated at the current time
+    pub fn run_until(&mut self, time: usize) -> Result<Vec<(usize, String, bool)>, Error> {
+        let mut events = self.events.take().unwrap_or_else(|| self.events());
+        let mut changes = Vec::new();
+
+        let mut nodes: Vec<usize> = events
+            .queue
+            .drain()
+            .map(|Reverse((_, id))| id)
+            .chain(events.deferred.drain(..))
+            .collect();
+        for &id in nodes.iter() {
+            events.queued[id] = false;
         }
-
-        while let Some(node_id) = queue.pop_front() {
-            viseted.insert(node_id);
-
-            let value = match self.graph.node(node_id) {
-                Ok(Component::In(node)) => vec![node.value],
-                Ok(Component::Lut(lut)) => lut.outputs(),
-                Ok(Component::Out(_)) => continue,
-                Err(err) => return Err(Error::msg(format!("graph error {:?}", err))),
+        loop {
+            nodes.sort_unstable();
+            nodes.dedup();
+            self.timed(&mut events, &nodes)?;
+            nodes.clear();
+
+            let len = events.wheel.len();
+            let next = match events.pending {
+                0 => None,
+                _ => (events.now + 1..events.now + len).find(|t| !events.wheel[t % len].is_empty()),
             };
-
-            for (edge, id) in match self.graph.out_edges(node_id) {
-                Ok(vec) => vec,
-                Err(err) => return Err(Error::msg(format!("graph error {:?}", err))),
-            } {
-                if viseted.get(&id) == None {
-                    queue.push_back(id);
-                }
-
-                match self.graph.node_mut(id) {
-                    Ok(Component::Lut(lut)) => lut.set_id(edge.to, value[edge.from])?,
-                    Ok(Component::Out(out)) => {
-                        if edge.to == 0 {
-                            out.value = value[0]
-                        } else {
-                            return Err(Error::msg(format!(
-                                "unexpected id {} expected 0",
-                                edge.to
-                            )));
+            match next {
+                Some(next) if next <= time => {
+                    events.now = next;
+                    for (_, id, port, value) in std::mem::take(&mut events.wheel[next % len]) {
+                        events.pending -= 1;
+                        if let Ok(Component::Out(out)) = self.graph.node_mut(id) {
+                            if out.value != value {
+                                changes.push((next, out.name.clone(), value));
+                            }
+                        }
+                        if self.drive(id, port, value)? {
+                            nodes.push(id);
                         }
                     }
-                    Ok(node) => return Err(Error::msg(format!("unexpected type {:?}", node))),
-                    Err(err) => return Err(Error::msg(format!("graph error {:?}", err))),
+                }
+                _ => {
+                    events.now = events.now.max(time);
+                    break;
                 }
             }
         }
+        self.events = Some(events);
+        Ok(changes)
+    }
+
+    /// gets the time of the timed simulation, see `run_until`
+    pub fn time(&self) -> usize {
+        self.events.as_ref().map_or(0, |events| events.now)
+    }
+
+    /// sets the delay of every connection driven by the input, lookup table or state element named `name` for `run_until`
+    /// returns an error if the name dose not exist or the delay is 0
+    pub fn set_delay(&mut self, name: &str, delay: usize) -> Result<(), Error> {
+        let id = match self
+            .in_map
+            .get(name)
+            .or_else(|| self.instances.get(name))
+            .or_else(|| self.states.get(name))
+        {
+            Some(&id) => id,
+            None => return Err(Error::UnknownInstance(name.to_string())),
+        };
+        if delay == 0 {
+            return Err(Error::ZeroDelay(name.to_string()));
+        }
+
+        for (_, edge) in self.fanout[id].iter_mut() {
+            edge.weight = delay;
+        }
+        if let Some(events) = self.events.as_mut() {
+            events.reserve(delay);
+        }
         Ok(())
     }
 
+    /// sets how `tick` and `settle` evaluate the lookup tables, the default is `Mode::Event`
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// gets how `tick` and `settle` evaluate the lookup tables
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
     /// gets the output named `name` and returs the value
     /// returs an error if the name dose not exist
     pub fn get(&self, name: &str) -> Result<bool, Error> {
         if let Some(&id) = self.out_map.get(name).clone() {
             Ok(self.get_id(id)?)
         } else {
-            Err(Error::msg(format!("no output named {}", name)))
+            Err(Error::UnknownPort(name.to_string()))
         }
     }
 
@@ -198,8 +385,53 @@ impl Circuit {
         if let Some(&id) = self.in_map.get(name).clone() {
             self.set_id(id, value)
         } else {
-            Err(Error::msg(format!("no input named {}", name)))
+            Err(Error::UnknownPort(name.to_string()))
+        }
+    }
+
+    /// gets the value of the output bus named `name`, bit 0 is the least significant bit
+    /// returs an error if the name dose not exist or the bus is wider than 64 bits
+    pub fn get_bus(&self, name: &str) -> Result<u64, Error> {
+        let ids = match self.out_buses.get(name) {
+            Some(ids) => ids,
+            None => return Err(Error::UnknownPort(name.to_string())),
+        };
+        if ids.len() > 64 {
+            return Err(Error::Width {
+                name: "u64".to_string(),
+                expected: 64,
+                found: ids.len(),
+            });
+        }
+
+        let mut value = 0;
+        for (i, &id) in ids.iter().enumerate() {
+            if self.get_id(id)? {
+                value |= 1 << i;
+            }
+        }
+        Ok(value)
+    }
+
+    /// sets the input bus named `name` to `value`, bit 0 is the least significant bit
+    /// returs an error if the name dose not exist or the value dose not fit in to the bus
+    pub fn set_bus(&mut self, name: &str, value: u64) -> Result<(), Error> {
+        let ids = match self.in_buses.get(name) {
+            Some(ids) => ids.clone(),
+            None => return Err(Error::UnknownPort(name.to_string())),
+        };
+        if ids.len() < 64 && value >> ids.len() != 0 {
+            return Err(Error::Width {
+                name: name.to_string(),
+                expected: ids.len(),
+                found: 64 - value.leading_zeros() as usize,
+            });
+        }
+
+        for (i, id) in ids.into_iter().enumerate() {
+            self.set_id(id, i < 64 && value >> i & 1 == 1)?;
         }
+        Ok(())
     }
 
     /// retuns the name of the `Circuit`
@@ -207,9 +439,342 @@ impl Circuit {
         self.name.clone()
     }
 
+    /// gets the instance paths of all lookup tables e.g. `nand_0` or `xor_0.nand_1`
+    /// the order is the order in which the instances were elaborated
+    pub fn instance_names(&self) -> Vec<String> {
+        let mut names: Vec<(usize, String)> = self
+            .instances
+            .iter()
+            .map(|(name, &id)| (id, name.clone()))
+            .collect();
+        names.sort();
+        names.into_iter().map(|(_, name)| name).collect()
+    }
+
+    /// gets the current state of the lookup table instance at the path `name`
+    /// returs an error if the instance dose not exist
+    pub fn instance(&self, name: &str) -> Result<LookupTable, Error> {
+        if let Some(&id) = self.instances.get(name) {
+            match self.graph.node(id) {
+                Ok(Component::Lut(lut)) => Ok(*lut),
+                Ok(_) => Err(Error::UnknownInstance(name.to_string())),
+                Err(err) => Err(Error::graph(err)),
+            }
+        } else {
+            Err(Error::UnknownInstance(name.to_string()))
+        }
+    }
+
+    /// writes the flattened `Circuit` as a verilog module with one instance per lookup table
+    /// the modules of the lookup tables follow, unconnected lut inputs are tied to 0
+    /// a `DFF` is a register clocked by the added input `clk`, one rising edge of `clk` is one tick
+    /// the other primitives are registers updated by their clock or enable pins
+    /// returns `Error::Unsupported` if different lookup tables have the same name
+    pub fn to_verilog(&self) -> Result<String, Error> {
+        let node = |id: usize| self.graph.node(id).map_err(Error::graph);
+        let states = self.state_paths();
+        let mut ticked = false;
+        for &(id, _) in states.iter() {
+            ticked |= matches!(node(id)?, Component::Dff(_));
+        }
+        let paths = self.lut_paths();
+
+        // the verilog expression of a node output
+        let mut sources: HashMap<(usize, usize), String> = HashMap::new();
+        let mut ports = Vec::new();
+        for (ids, direction, buses) in [
+            (&self.inputs, "input", &self.in_buses),
+            (&self.outputs, "output", &self.out_buses),
+        ] {
+            for &id in ids.iter() {
+                let name = match node(id)? {
+                    Component::In(port) | Component::Out(port) => port.name,
+                    Component::Lut(_) | Component::Dff(_) | Component::Storage(_) => continue,
+                };
+                match buses.iter().find(|(_, bits)| bits.contains(&id)) {
+                    Some((bus, bits)) if bits.len() > 1 => {
+                        let i = bits.iter().position(|&bit| bit == id).unwrap();
+                        sources.insert((id, 0), format!("{}[{}]", verilog::ident(bus), i));
+                        if i == 0 {
+                            ports.push((direction, bus.clone(), bits.len()));
+                        }
+                    }
+                    _ => {
+                        sources.insert((id, 0), verilog::ident(&name));
+                        ports.push((direction, name, 1));
+                    }
+                }
+            }
+        }
+
+        // the tick becomes a clock input named like no other port
+        let mut clk = "clk".to_string();
+        while ports.iter().any(|(_, port, _)| *port == clk) {
+            clk = format!("{}_", clk);
+        }
+        if ticked {
+            ports.push(("input", clk.clone(), 1));
+        }
+        let clk = verilog::ident(&clk);
+
+        let mut luts: Vec<LookupTable> = Vec::new();
+        let same_table = |a: &LookupTable, b: &LookupTable| {
+            a.get_table() == b.get_table()
+                && a.in_names() == b.in_names()
+                && a.out_names() == b.out_names()
+        };
+        let mut wires = String::new();
+        for &(id, path) in states.iter() {
+            let reg = verilog::ident(&format!("{}.out", path));
+            wires += &format!("    reg {};\n", reg);
+            sources.insert((id, 0), reg);
+        }
+        for &(id, path) in paths.iter() {
+            if let Component::Lut(lut) = node(id)? {
+                for out in lut.out_names() {
+                    let wire = verilog::ident(&format!("{}.{}", path, out));
+                    wires += &format!("    wire {};\n", wire);
+                    sources.insert((id, lut.out_map(&out).unwrap()), wire);
+                }
+                // a module is written for every name, tables with the same name have to be the same
+                match luts.iter().find(|other| other.name() == lut.name()) {
+                    Some(other) if same_table(other, &lut) => (),
+                    Some(_) => {
+                        return Err(Error::Unsupported {
+                            part: lut.name(),
+                            msg: "different lookup tables with the same name can not be written as one module".to_string(),
+                        })
+                    }
+                    None => luts.push(*lut),
+                }
+            }
+        }
+
+        // the expression driving every (node, input port)
+        let mut drivers: HashMap<(usize, usize), String> = HashMap::new();
+        let ids = paths.iter().chain(states.iter()).map(|(id, _)| id);
+        for &id in self.inputs.iter().chain(ids) {
+            for (edge, to) in self.graph.out_edges(id).map_err(Error::graph)? {
+                drivers.insert((to, edge.to), sources[&(id, edge.from)].clone());
+            }
+        }
+
+        let mut body = String::new();
+        for &(id, _) in states.iter() {
+            let reg = &sources[&(id, 0)];
+            let driver = |port: usize| {
+                drivers
+                    .get(&(id, port))
+                    .cloned()
+                    .unwrap_or_else(|| "1'b0".to_string())
+            };
+            match node(id)? {
+                Component::Dff(dff) => {
+                    body += &format!("    initial {} = 1'b{};\n", reg, dff.init as u8);
+                    body += &format!("    always @(posedge {}) {} <= {};\n", clk, reg, driver(0));
+                }
+                Component::Storage(storage) => {
+                    let inputs: Vec<String> = (0..storage.inputs.len()).map(driver).collect();
+                    body += &verilog::storage(&storage.primitive, reg, &inputs).unwrap_or_default();
+                }
+                _ => (),
+            }
+        }
+        for &(id, path) in paths.iter() {
+            if let Component::Lut(lut) = node(id)? {
+                let connections: Vec<String> = lut
+                    .in_names()
+                    .iter()
+                    .enumerate()
+                    .map(|(port, pin)| (pin, drivers.get(&(id, port)).cloned()))
+                    .map(|(pin, driver)| (pin, driver.unwrap_or_else(|| "1'b0".to_string())))
+                    .chain(
+                        lut.out_names()
+                            .iter()
+                            .enumerate()
+                            .map(|(port, pin)| (pin, sources[&(id, port)].clone())),
+                    )
+                    .map(|(pin, wire)| format!("        .{}({})", verilog::ident(pin), wire))
+                    .collect();
+                body += &format!(
+                    "\n    {} {} (\n{}\n    );\n",
+                    verilog::ident(&lut.name()),
+                    verilog::ident(path),
+                    connections.join(",\n")
+                );
+            }
+        }
+        for &id in self.outputs.iter() {
+            let driver = drivers
+                .get(&(id, 0))
+                .cloned()
+                .unwrap_or_else(|| "1'b0".to_string());
+            body += &format!("    assign {} = {};\n", sources[&(id, 0)], driver);
+        }
+
+        let mut modules =
+            vec![verilog::header(&self.name, &ports) + &wires + &body + "endmodule\n"];
+        modules.extend(luts.iter().map(verilog::lut_module));
+        Ok(modules.join("\n"))
+    }
+
+    /// writes the flattened `Circuit` as a blif model with a `.names` for every lookup table output and a `.latch` for every state element
+    /// nets are named after the output they drive or after the instance path driving them
+    pub fn to_blif(&self) -> Result<String, Error> {
+        let node = |id: usize| self.graph.node(id).map_err(Error::graph);
+        let in_names = self.in_names();
+        let out_names = self.out_names();
+        let mut paths = self.lut_paths();
+        paths.extend(self.state_paths());
+
+        // the net of every (node, output port)
+        let mut nets: HashMap<(usize, usize), String> = HashMap::new();
+        let mut used: HashSet<String> = in_names.iter().chain(out_names.iter()).cloned().collect();
+        let mut claimed = HashSet::new();
+        for (&id, name) in self.inputs.iter().zip(in_names.iter()) {
+            nets.insert((id, 0), name.clone());
+        }
+        for &(id, path) in paths.iter() {
+            let pins = match node(id)? {
+                Component::Lut(lut) => lut.out_names(),
+                _ => vec!["out".to_string()],
+            };
+            let edges = self.graph.out_edges(id).map_err(Error::graph)?;
+            for (port, pin) in pins.iter().enumerate() {
+                // the first output driven by the pin gives the net its name
+                let output = self
+                    .outputs
+                    .iter()
+                    .position(|out| {
+                        edges
+                            .iter()
+                            .any(|(edge, to)| edge.from == port && to == out)
+                    })
+                    .map(|i| &out_names[i])
+                    .filter(|name| !claimed.contains(*name));
+                let net = match output {
+                    Some(name) => {
+                        claimed.insert(name.clone());
+                        name.clone()
+                    }
+                    None if pins.len() == 1 && !used.contains(path) => path.clone(),
+                    None => format!("{}.{}", path, pin),
+                };
+                used.insert(net.clone());
+                nets.insert((id, port), net);
+            }
+        }
+
+        // the net driving every (node, input port)
+        let mut drivers: HashMap<(usize, usize), String> = HashMap::new();
+        for &id in self.inputs.iter().chain(paths.iter().map(|(id, _)| id)) {
+            for (edge, to) in self.graph.out_edges(id).map_err(Error::graph)? {
+                drivers.insert((to, edge.to), nets[&(id, edge.from)].clone());
+            }
+        }
+        // unconnected inputs are driven by a constant 0
+        let mut zero = false;
+        let mut driver = |id: usize, port: usize| match drivers.get(&(id, port)) {
+            Some(net) => net.clone(),
+            None => {
+                zero = true;
+                "$false".to_string()
+            }
+        };
+
+        let mut blif = format!(".model {}\n", self.name);
+        if !in_names.is_empty() {
+            blif += &format!(".inputs {}\n", in_names.join(" "));
+        }
+        if !out_names.is_empty() {
+            blif += &format!(".outputs {}\n", out_names.join(" "));
+        }
+        for &(id, path) in paths.iter() {
+            match node(id)? {
+                Component::Lut(lut) => {
+                    let inputs: Vec<String> = (0..lut.in_names().len())
+                        .map(|port| driver(id, port))
+                        .collect();
+                    for port in 0..lut.out_names().len() {
+                        let mut signals = inputs.clone();
+                        signals.push(nets[&(id, port)].clone());
+                        blif += &format!(".names {}\n", signals.join(" "));
+                        for cube in blif::cover(&lut, port) {
+                            blif += &cube;
+                            blif += "\n";
+                        }
+                    }
+                }
+                Component::Dff(dff) => {
+                    blif += &format!(
+                        ".latch {} {} {}\n",
+                        driver(id, 0),
+                        nets[&(id, 0)],
+                        dff.init as u8
+                    );
+                }
+                Component::Storage(storage) => {
+                    let inputs: Vec<String> = (0..storage.inputs.len())
+                        .map(|port| driver(id, port))
+                        .collect();
+                    let out = &nets[&(id, 0)];
+                    // the latch stores its input while its control is high or rises
+                    let latch = match storage.primitive {
+                        Primitive::Dff { init } => format!("{} {} {}", inputs[0], out, init as u8),
+                        Primitive::ClockedDff => {
+                            format!("{} {} re {} 0", inputs[0], out, inputs[1])
+                        }
+                        Primitive::DLatch => format!("{} {} ah {} 0", inputs[0], out, inputs[1]),
+                        Primitive::EnableDff => {
+                            let next = format!("{}.next", path);
+                            blif += &format!(
+                                ".names {} {} {} {}\n11- 1\n0-1 1\n",
+                                inputs[1], inputs[0], out, next
+                            );
+                            format!("{} {} re {} 0", next, out, inputs[2])
+                        }
+                        Primitive::SrLatch => {
+                            let (d, en) = (format!("{}.d", path), format!("{}.en", path));
+                            blif += &format!(".names {} {}\n0 1\n", inputs[1], d);
+                            blif +=
+                                &format!(".names {} {} {}\n1- 1\n-1 1\n", inputs[0], inputs[1], en);
+                            format!("{} {} ah {} 0", d, out, en)
+                        }
+                    };
+                    blif += &format!(".latch {}\n", latch);
+                }
+                _ => return Err(Error::UnknownInstance(path.clone())),
+            }
+        }
+        for (&id, name) in self.outputs.iter().zip(out_names.iter()) {
+            if !claimed.contains(name) {
+                match drivers.get(&(id, 0)) {
+                    Some(net) => blif += &format!(".names {} {}\n1 1\n", net, name),
+                    None => blif += &format!(".names {}\n", name),
+                }
+            }
+        }
+        if zero {
+            blif += ".names $false\n";
+        }
+        Ok(blif + ".end\n")
+    }
+
+    /// writes the `Circuit` as an ascii aiger file with every lookup table split in to ands and inverters
+    /// returns `Error::CombinationalLoop` with the instance paths if lookup tables form a loop without a state element
+    pub fn to_aag(&self) -> Result<String, Error> {
+        Ok(self.aig()?.to_ascii())
+    }
+
+    /// writes the `Circuit` as a binary aiger file, like `to_aag`
+    pub fn to_aig(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.aig()?.to_binary())
+    }
+
     /// trys to transform the `Circuit` in to a `LookupTable`
+    /// returns `None` if the `Circuit` has feedback or state elements
     pub fn to_lut(mut self) -> Result<Option<LookupTable>, Error> {
-        if !self.graph.is_dag() {
+        if !self.states.is_empty() || !self.graph.is_dag() {
             return Ok(None);
         }
 
@@ -243,24 +808,16 @@ impl Circuit {
         )?))
     }
 
-    /// gest names of inputs
-    /// note the order can cange if you add new inputs
+    /// gest names of inputs in the order they are declared in the `ChipDef`
+    /// bits of buses are called `name[i]`
     pub fn in_names(&self) -> Vec<String> {
-        self.in_map
-            .clone()
-            .iter()
-            .map(|(name, _)| name.clone())
-            .collect()
+        self.io_names(&self.inputs)
     }
 
-    /// gest names of outputs
-    /// note the order can cange if you add new outputs
+    /// gest names of outputs in the order they are declared in the `ChipDef`
+    /// bits of buses are called `name[i]`
     pub fn out_names(&self) -> Vec<String> {
-        self.out_map
-            .clone()
-            .iter()
-            .map(|(name, _)| name.clone())
-            .collect()
+        self.io_names(&self.outputs)
     }
 }
 
@@ -273,12 +830,353 @@ impl Circuit {
             outputs: Vec::new(),
             in_map: HashMap::new(),
             out_map: HashMap::new(),
+            instances: HashMap::new(),
+            states: HashMap::new(),
+            roots: Vec::new(),
+            in_buses: HashMap::new(),
+            out_buses: HashMap::new(),
+            fanout: Vec::new(),
+            events: None,
+            settle_limit: 1000,
+            mode: Mode::Event,
+        }
+    }
+
+    /// creates the events that evaluate every node once, starting with the inputs and roots
+    /// evaluates the scheduled nodes once, changes that go around a loop are deferred to the next delta cycle
+    /// in `Mode::Synchronous` all nodes are evaluated before any output is propagated and every change is deferred
+    fn delta(&mut self, events: &mut Events) -> Result<(), Error> {
+        events.cycles += 1;
+        for id in std::mem::take(&mut events.deferred) {
+            events.queued[id] = false;
+            events.schedule(id);
+        }
+
+        let mut changes = Vec::new();
+        while let Some(Reverse((_, node_id))) = events.queue.pop() {
+            events.queued[node_id] = false;
+            events.evaluated[node_id] = events.cycles;
+
+            let value = match self.evaluate(node_id)? {
+                Some(value) => value,
+                None => continue,
+            };
+            match self.mode {
+                Mode::Event => self.apply(events, node_id, value)?,
+                Mode::Synchronous => changes.push((node_id, value)),
+            }
+        }
+        for (node_id, value) in changes {
+            self.apply(events, node_id, value)?;
+        }
+        Ok(())
+    }
+
+    /// propagates the outputs of a node along the edges whose value changed
+    fn apply(
+        &mut self,
+        events: &mut Events,
+        node_id: usize,
+        value: Vec<bool>,
+    ) -> Result<(), Error> {
+        let previous = events.values[node_id].replace(value.clone());
+
+        for i in 0..self.fanout[node_id].len() {
+            let (id, edge) = self.fanout[node_id][i].clone();
+            if previous
+                .as_ref()
+                .is_some_and(|old| old[edge.from] == value[edge.from])
+            {
+                continue;
+            }
+
+            if self.drive(id, edge.to, value[edge.from])? {
+                match self.mode {
+                    Mode::Event => events.propagate(node_id, id),
+                    Mode::Synchronous => events.defer(id),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// gets the outputs of a node, `None` for outputs of the `Circuit`
+    fn evaluate(&mut self, node_id: usize) -> Result<Option<Vec<bool>>, Error> {
+        match self.graph.node_mut(node_id) {
+            Ok(Component::In(node)) => Ok(Some(vec![node.value])),
+            Ok(Component::Lut(lut)) => Ok(Some(lut.try_outputs()?)),
+            Ok(Component::Dff(dff)) => Ok(Some(vec![dff.value])),
+            Ok(Component::Storage(storage)) => Ok(Some(vec![storage.update()])),
+            Ok(Component::Out(_)) => Ok(None),
+            Err(err) => Err(Error::graph(err)),
+        }
+    }
+
+    /// sets the input `port` of a node, returns true if the node is a lookup table that has to be evaluated
+    fn drive(&mut self, id: usize, port: usize, value: bool) -> Result<bool, Error> {
+        match self.graph.node_mut(id) {
+            Ok(Component::Lut(lut)) => {
+                lut.set_id(port, value)?;
+                Ok(true)
+            }
+            Ok(Component::Out(out)) if port == 0 => {
+                out.value = value;
+                Ok(false)
+            }
+            Ok(Component::Dff(dff)) if port == 0 => {
+                dff.next = value;
+                Ok(false)
+            }
+            Ok(Component::Storage(storage)) => match storage.inputs.get_mut(port) {
+                Some(input) => {
+                    *input = value;
+                    Ok(true)
+                }
+                None => Err(Error::Index {
+                    index: port,
+                    len: storage.inputs.len(),
+                }),
+            },
+            Ok(Component::Out(_) | Component::Dff(_)) => Err(Error::Index {
+                index: port,
+                len: 1,
+            }),
+            Ok(_) => Err(Error::UnknownPort(id.to_string())),
+            Err(err) => Err(Error::graph(err)),
+        }
+    }
+
+    /// evaluates the nodes at the current time of `run_until`
+    /// changed outputs reach the inputs they drive after the weight of the connection
+    fn timed(&mut self, events: &mut Events, nodes: &[usize]) -> Result<(), Error> {
+        for &node_id in nodes {
+            let value = match self.evaluate(node_id)? {
+                Some(value) => value,
+                None => continue,
+            };
+            let previous = events.values[node_id].replace(value.clone());
+
+            for (id, edge) in self.fanout[node_id].iter() {
+                if previous
+                    .as_ref()
+                    .is_some_and(|old| old[edge.from] == value[edge.from])
+                {
+                    continue;
+                }
+                let time = events.now + edge.weight;
+                let slot = time % events.wheel.len();
+                events.wheel[slot].push((time, *id, edge.to, value[edge.from]));
+                events.pending += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn events(&self) -> Events {
+        let len = self.fanout.len();
+        let states: HashSet<usize> = self.states.values().copied().collect();
+
+        // reverse post order of a depth first search, state elements are only reached as roots
+        let mut order = Vec::with_capacity(len);
+        let mut visited = vec![false; len];
+        for root in self
+            .inputs
+            .iter()
+            .chain(self.roots.iter())
+            .copied()
+            .chain(0..len)
+        {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            let mut stack = vec![(root, 0)];
+            while let Some(&(id, next)) = stack.last() {
+                match self.fanout[id].get(next) {
+                    Some(&(to, _)) => {
+                        stack.last_mut().unwrap().1 += 1;
+                        if !visited[to] && !states.contains(&to) {
+                            visited[to] = true;
+                            stack.push((to, 0));
+                        }
+                    }
+                    None => {
+                        order.push(id);
+                        stack.pop();
+                    }
+                }
+            }
         }
+        let mut rank = vec![0; len];
+        for (i, &id) in order.iter().rev().enumerate() {
+            rank[id] = i;
+        }
+
+        let mut events = Events {
+            rank,
+            values: vec![None; len],
+            queue: BinaryHeap::with_capacity(len),
+            deferred: Vec::new(),
+            queued: vec![false; len],
+            evaluated: vec![0; len],
+            cycles: 0,
+            now: 0,
+            wheel: Vec::new(),
+            pending: 0,
+        };
+        let delay = self.fanout.iter().flatten().map(|(_, edge)| edge.weight);
+        events.reserve(delay.max().unwrap_or(1));
+        for id in 0..len {
+            events.schedule(id);
+        }
+        events
+    }
+
+    /// builds the and-inverter graph of the `Circuit`, the lookup tables are added in topological order
+    fn aig(&self) -> Result<aiger::Aig, Error> {
+        let node = |id: usize| self.graph.node(id).map_err(Error::graph);
+        let luts = self.lut_paths();
+        let states = self.state_paths();
+
+        let mut latches = Vec::with_capacity(states.len());
+        for &(id, path) in states.iter() {
+            match node(id)? {
+                Component::Dff(dff) => latches.push((dff.init, path.clone())),
+                Component::Storage(storage) => {
+                    return Err(Error::Unsupported {
+                        part: storage.primitive.name(),
+                        msg: "only primitives clocked by the tick can be written as an aiger latch"
+                            .to_string(),
+                    })
+                }
+                _ => (),
+            }
+        }
+        let mut aig = aiger::Aig::new(self.in_names(), latches);
+
+        // the literal of every (node, output port)
+        let mut lits: HashMap<(usize, usize), u32> = HashMap::new();
+        for (&id, (lit, _)) in self.inputs.iter().zip(aig.inputs.iter()) {
+            lits.insert((id, 0), *lit);
+        }
+        for (&(id, _), (lit, _, _, _)) in states.iter().zip(aig.latches.iter()) {
+            lits.insert((id, 0), *lit);
+        }
+
+        // the (node, output port) driving every (node, input port)
+        let mut drivers: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        // number of lut inputs driven by luts that are not done yet
+        let mut waiting: HashMap<usize, usize> = luts.iter().map(|&(id, _)| (id, 0)).collect();
+        for &id in self
+            .inputs
+            .iter()
+            .chain(luts.iter().chain(states.iter()).map(|(id, _)| id))
+        {
+            for (edge, to) in self.graph.out_edges(id).map_err(Error::graph)? {
+                drivers.insert((to, edge.to), (id, edge.from));
+                if waiting.contains_key(&id) {
+                    if let Some(count) = waiting.get_mut(&to) {
+                        *count += 1;
+                    }
+                }
+            }
+        }
+
+        let mut queue: VecDeque<usize> = luts
+            .iter()
+            .map(|&(id, _)| id)
+            .filter(|id| waiting[id] == 0)
+            .collect();
+        let mut done = 0;
+        while let Some(id) = queue.pop_front() {
+            done += 1;
+            let lut = match node(id)? {
+                Component::Lut(lut) => lut,
+                _ => continue,
+            };
+
+            let n = lut.in_names().len();
+            let in_lits: Vec<u32> = (0..n)
+                .map(|port| drivers.get(&(id, port)).map_or(0, |driver| lits[driver]))
+                .collect();
+            // the table row of every input bit
+            let weights: Vec<usize> = (0..n)
+                .map(|j| bool_to_u32((0..n).map(|i| i == j).collect()) as usize)
+                .collect();
+            for (port, column) in lut.get_table().iter().enumerate() {
+                let truth: Vec<bool> = (0..column.len())
+                    .map(|row| {
+                        let index: usize = (0..n)
+                            .filter(|j| row >> j & 1 == 1)
+                            .map(|j| weights[j])
+                            .sum();
+                        column[index]
+                    })
+                    .collect();
+                lits.insert((id, port), aig.function(&truth, &in_lits));
+            }
+
+            for (_, to) in self.graph.out_edges(id).map_err(Error::graph)? {
+                if let Some(count) = waiting.get_mut(&to) {
+                    *count -= 1;
+                    if *count == 0 {
+                        queue.push_back(to);
+                    }
+                }
+            }
+        }
+        if done != luts.len() {
+            let paths = luts
+                .iter()
+                .filter(|&&(id, _)| waiting[&id] != 0)
+                .map(|(_, path)| path.to_string())
+                .collect();
+            return Err(Error::CombinationalLoop(paths));
+        }
+
+        let driver = |id: usize| drivers.get(&(id, 0)).map_or(0, |driver| lits[driver]);
+        for (latch, &(id, _)) in aig.latches.iter_mut().zip(states.iter()) {
+            latch.1 = driver(id);
+        }
+        for (&id, name) in self.outputs.iter().zip(self.out_names()) {
+            aig.outputs.push((driver(id), name));
+        }
+        Ok(aig)
+    }
+
+    /// (node id, instance path) of the luts ordered by id
+    fn lut_paths(&self) -> Vec<(usize, &String)> {
+        let mut paths: Vec<(usize, &String)> = self
+            .instances
+            .iter()
+            .map(|(path, &id)| (id, path))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// (node id, instance path) of the state elements ordered by id
+    fn state_paths(&self) -> Vec<(usize, &String)> {
+        let mut paths: Vec<(usize, &String)> =
+            self.states.iter().map(|(path, &id)| (id, path)).collect();
+        paths.sort();
+        paths
+    }
+
+    fn io_names(&self, ids: &[usize]) -> Vec<String> {
+        ids.iter()
+            .filter_map(|&id| match self.graph.node(id) {
+                Ok(Component::In(node)) | Ok(Component::Out(node)) => Some(node.name),
+                _ => None,
+            })
+            .collect()
     }
 
     fn add_node(&mut self, node: Component) -> Result<usize, Error> {
         match self.graph.add_node(node.clone()) {
             Ok(value) => {
+                self.fanout.resize(value + 1, Vec::new());
+                self.events = None;
                 match node {
                     Component::In(node) => {
                         self.inputs.push(value);
@@ -288,72 +1186,166 @@ impl Circuit {
                         self.outputs.push(value);
                         self.out_map.insert(node.name, value);
                     }
+                    Component::Dff(_) => self.roots.push(value),
+                    Component::Lut(lut) if lut.in_names().is_empty() => self.roots.push(value),
                     _ => (),
                 }
                 Ok(value)
             }
-            Err(error) => Err(Error::msg(format!("graph error {:?}", error))),
+            Err(error) => Err(Error::graph(error)),
         }
     }
 
-    fn lut_name(&self, name: &String) -> Result<usize, Error> {
-        for (id, node) in self.graph.nodes().into_iter().enumerate() {
-            if let Component::Lut(lut) = node {
-                if &lut.name() == name {
-                    return Ok(id);
+    pub(crate) fn from_netlist(netlist: Netlist) -> Result<Self, Error> {
+        let mut circuit = Self::blank(netlist.name.clone());
+        // (net name, node id) of the io inputs
+        let mut in_ids = Vec::with_capacity(netlist.inputs.len());
+        // maps the internal connection name the the corisponding (lut id, port id)
+        let mut in_ports: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+
+        for input in netlist.inputs.iter() {
+            let in_id = circuit.add_node(Component::In(InOut::new(input.clone())))?;
+            in_ids.push((netlist.net(input).to_string(), in_id));
+        }
+
+        for output in netlist.outputs.iter() {
+            let out_id = circuit.add_node(Component::Out(InOut::new(output.clone())))?;
+            in_ports
+                .entry(netlist.net(output).to_string())
+                .or_default()
+                .push((out_id, 0));
+        }
+
+        for (name, width) in netlist.in_buses.iter() {
+            let ids = bit_names(name, *width)
+                .iter()
+                .map(|bit| circuit.in_map[bit])
+                .collect();
+            circuit.in_buses.insert(name.clone(), ids);
+        }
+
+        for (name, width) in netlist.out_buses.iter() {
+            let ids = bit_names(name, *width)
+                .iter()
+                .map(|bit| circuit.out_map[bit])
+                .collect();
+            circuit.out_buses.insert(name.clone(), ids);
+        }
+
+        // initilse in_ports and add lut as nodes
+        let mut lut_ids = Vec::with_capacity(netlist.cells.len());
+        for cell in netlist.cells.iter() {
+            let lut_id = circuit.add_node(Component::Lut(Box::new(cell.lut.clone())))?;
+            circuit.instances.insert(cell.name.clone(), lut_id);
+            for (port, net) in cell.inputs.iter() {
+                in_ports
+                    .entry(net.clone())
+                    .or_default()
+                    .push((lut_id, *port));
+            }
+            lut_ids.push(lut_id);
+        }
+
+        let mut state_ids = Vec::with_capacity(netlist.states.len());
+        for state in netlist.states.iter() {
+            let component = match state.primitive {
+                Primitive::Dff { init } => Component::Dff(Dff::new(init)),
+                primitive => Component::Storage(Storage::new(primitive)),
+            };
+            let state_id = circuit.add_node(component)?;
+            circuit.states.insert(state.name.clone(), state_id);
+            for (port, net) in state.inputs.iter() {
+                in_ports
+                    .entry(net.clone())
+                    .or_default()
+                    .push((state_id, *port));
+            }
+            state_ids.push(state_id);
+        }
+
+        // create conections
+        let outputs = netlist
+            .cells
+            .iter()
+            .map(|cell| &cell.outputs)
+            .zip(lut_ids.iter())
+            .chain(
+                netlist
+                    .states
+                    .iter()
+                    .map(|state| &state.outputs)
+                    .zip(state_ids.iter()),
+            );
+        for (outputs, &from_lut_id) in outputs {
+            for (from_port, net) in outputs.iter() {
+                for &(to_lut_id, to_port) in in_ports.get(net).into_iter().flatten() {
+                    circuit.add_connection(
+                        from_lut_id,
+                        to_lut_id,
+                        Connection::new(*from_port, to_port),
+                    )?;
                 }
             }
         }
-        Err(Error::msg(format!("lut {} not found", name)))
-    }
 
-    fn lut_id(&self, node_id: usize) -> Result<LookupTable, Error> {
-        if let Ok(Component::Lut(lut)) = self.graph.node(node_id) {
-            Ok(lut)
-        } else {
-            Err(Error::msg(format!("lut {} not found", node_id)))
+        // connecting io inputs to lut
+        for (input, in_id) in in_ids {
+            for &(to_lut_id, to_port) in in_ports.get(&input).into_iter().flatten() {
+                circuit.add_connection(in_id, to_lut_id, Connection::new(0, to_port))?;
+            }
         }
+
+        Ok(circuit)
     }
 
     fn add_connection(&mut self, from: usize, to: usize, edge: Connection) -> Result<(), Error> {
-        match self.graph.add_edge(from, to, edge) {
-            Ok(()) => Ok(()),
-            Err(err) => Err(Error::msg(format!("graph error {:?}", err))),
+        match self.graph.add_edge(from, to, edge.clone()) {
+            Ok(()) => {
+                self.fanout[from].push((to, edge));
+                self.events = None;
+                Ok(())
+            }
+            Err(err) => Err(Error::graph(err)),
         }
     }
 
     fn get_id(&self, node_id: usize) -> Result<bool, Error> {
         if !self.outputs.contains(&node_id) {
-            return Err(Error::msg(format!("{} in not an input id", node_id)));
+            return Err(Error::UnknownPort(node_id.to_string()));
         }
 
         let node = match self.graph.node(node_id) {
-            Err(err) => return Err(Error::msg(format!("graph error {:?}", err))),
+            Err(err) => return Err(Error::graph(err)),
             Ok(node) => node.clone(),
         };
 
         if let Component::Out(node) = node {
             Ok(node.value)
         } else {
-            Err(Error::msg(format!("{:?} is not an output node", node)))
+            Err(Error::UnknownPort(node_id.to_string()))
         }
     }
 
     fn set_id(&mut self, node_id: usize, value: bool) -> Result<(), Error> {
         if !self.inputs.contains(&node_id) {
-            return Err(Error::msg(format!("{} in not an input id", node_id)));
+            return Err(Error::UnknownPort(node_id.to_string()));
         }
 
         let node = match self.graph.node_mut(node_id) {
-            Err(err) => return Err(Error::msg(format!("graph error {:?}", err))),
+            Err(err) => return Err(Error::graph(err)),
             Ok(node) => node,
         };
 
         if let Component::In(node) = node {
-            node.value = value;
+            if node.value != value {
+                node.value = value;
+                if let Some(events) = self.events.as_mut() {
+                    events.schedule(node_id);
+                }
+            }
             Ok(())
         } else {
-            Err(Error::msg(format!("{:?} is not an input node", node)))
+            Err(Error::UnknownPort(node_id.to_string()))
         }
     }
 }
@@ -384,9 +1376,9 @@ mod tests {
         .unwrap();
 
         let mut ram = Circuit::blank("ram".to_string());
-        let not_id = ram.add_node(Component::Lut(not)).unwrap();
-        let or_id = ram.add_node(Component::Lut(or)).unwrap();
-        let and_id = ram.add_node(Component::Lut(and)).unwrap();
+        let not_id = ram.add_node(Component::Lut(Box::new(not))).unwrap();
+        let or_id = ram.add_node(Component::Lut(Box::new(or))).unwrap();
+        let and_id = ram.add_node(Component::Lut(Box::new(and))).unwrap();
         let in_id = ram
             .add_node(Component::In(InOut::new("input".to_string())))
             .unwrap();
@@ -467,8 +1459,8 @@ mod tests {
         let circuit = Circuit::new(chip_def, lut_map).unwrap();
 
         let mut clock = Circuit::blank("clock".to_string());
-        let not_id = clock.add_node(Component::Lut(not)).unwrap();
-        let or_id = clock.add_node(Component::Lut(or)).unwrap();
+        let not_id = clock.add_node(Component::Lut(Box::new(not))).unwrap();
+        let or_id = clock.add_node(Component::Lut(Box::new(or))).unwrap();
         let in_id = clock
             .add_node(Component::In(InOut::new("input".to_string())))
             .unwrap();
@@ -548,7 +1540,7 @@ mod tests {
         let output = circuit
             .add_node(Component::Out(InOut::new("out".to_string())))
             .unwrap();
-        let lut = circuit.add_node(Component::Lut(lut)).unwrap();
+        let lut = circuit.add_node(Component::Lut(Box::new(lut))).unwrap();
 
         circuit
             .add_connection(in1, lut, Connection::new(0, 0))
@@ -599,7 +1591,7 @@ mod tests {
         let output = circuit
             .add_node(Component::Out(InOut::new("output".to_string())))
             .unwrap();
-        let lut = circuit.add_node(Component::Lut(lut)).unwrap();
+        let lut = circuit.add_node(Component::Lut(Box::new(lut))).unwrap();
 
         circuit
             .add_connection(input, lut, Connection::new(0, 0))
@@ -664,8 +1656,12 @@ mod tests {
         let out = circuit
             .add_node(Component::Out(InOut::new("out".to_string())))
             .unwrap();
-        let nand = circuit.add_node(Component::Lut(lut.clone())).unwrap();
-        let not = circuit.add_node(Component::Lut(lut.clone())).unwrap();
+        let nand = circuit
+            .add_node(Component::Lut(Box::new(lut.clone())))
+            .unwrap();
+        let not = circuit
+            .add_node(Component::Lut(Box::new(lut.clone())))
+            .unwrap();
 
         circuit
             .add_connection(a, nand, Connection::new(0, 0))
@@ -702,4 +1698,48 @@ mod tests {
             circuit_from_def.graph.edge_list()
         );
     }
+
+    #[test]
+    fn changed_fanout() {
+        let not =
+            LookupTable::new(vec![vec![true, false]], vec!["in"], vec!["out"], "Not").unwrap();
+        let mut lut_map = HashMap::new();
+        lut_map.insert("Not".to_string(), not);
+
+        let def = ChipDef::new(
+            "Nots",
+            vec!["a", "b"],
+            vec!["x", "y"],
+            vec![
+                (vec![("in", "a")], vec![("out", "x")], "Not"),
+                (vec![("in", "b")], vec![("out", "y")], "Not"),
+            ],
+        );
+        let mut circuit = Circuit::new(def, lut_map).unwrap();
+        let names = circuit.instance_names();
+        let (not_a, not_b) = (circuit.instances[&names[0]], circuit.instances[&names[1]]);
+
+        // the first tick evaluates every node
+        assert_eq!(circuit.tick(), Ok(()));
+        assert_eq!(circuit.get("x"), Ok(true));
+        assert_eq!(circuit.get("y"), Ok(true));
+        let events = circuit.events.as_ref().unwrap();
+        assert!(events.evaluated.iter().all(|&tick| tick == 1));
+
+        // nothing changed so nothing is evaluated
+        assert_eq!(circuit.tick(), Ok(()));
+        let events = circuit.events.as_ref().unwrap();
+        assert!(events.evaluated.iter().all(|&tick| tick == 1));
+
+        // setting an input to its current value is not a change
+        assert_eq!(circuit.set("b", false), Ok(()));
+        assert_eq!(circuit.set("a", true), Ok(()));
+        assert_eq!(circuit.tick(), Ok(()));
+        assert_eq!(circuit.get("x"), Ok(false));
+        assert_eq!(circuit.get("y"), Ok(true));
+        let events = circuit.events.as_ref().unwrap();
+        assert_eq!(events.evaluated[not_a], 3);
+        assert_eq!(events.evaluated[not_b], 1);
+        assert_eq!(events.evaluated[circuit.in_map["b"]], 1);
+    }
 }
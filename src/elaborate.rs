@@ -0,0 +1,685 @@
+use std::collections::HashMap;
+
+use crate::chip_def::split_slice;
+use crate::primitive::Primitive;
+use crate::{ChipDef, Component, Diagnostic, Error, Library, LookupTable};
+
+/// a `ChipDef` with every sub-chip expanded down to `LookupTable`s
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Netlist {
+    pub name: String,
+    /// nets of the input bits e.g. `a[0]`, `a[1]`, `sel`
+    pub inputs: Vec<String>,
+    /// nets of the output bits
+    pub outputs: Vec<String>,
+    /// (port name, width) of the inputs
+    pub in_buses: Vec<(String, usize)>,
+    /// (port name, width) of the outputs
+    pub out_buses: Vec<(String, usize)>,
+    pub cells: Vec<Cell>,
+    pub states: Vec<State>,
+    /// maps nets merged by a `Component::Map` to the net that represents them
+    pub aliases: HashMap<String, String>,
+}
+
+impl Netlist {
+    /// gets the net that represents `name` after aliasing
+    pub fn net<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.aliases.get(name) {
+            Some(net) => net,
+            None => name,
+        }
+    }
+}
+
+/// one `LookupTable` instance of a `Netlist`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Cell {
+    /// instance path e.g. `xor_0.nand_1`
+    pub name: String,
+    pub lut: LookupTable,
+    /// (port id, net name)
+    pub inputs: Vec<(usize, String)>,
+    /// (port id, net name)
+    pub outputs: Vec<(usize, String)>,
+}
+
+/// one primitive state element of a `Netlist`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct State {
+    /// instance path e.g. `bit_0.dff_0`
+    pub name: String,
+    pub primitive: Primitive,
+    /// (port id, net name)
+    pub inputs: Vec<(usize, String)>,
+    /// (port id, net name)
+    pub outputs: Vec<(usize, String)>,
+}
+
+/// gets the name of the net carrying bit `i` of a signal that is `width` bits wide
+/// signals that are 1 bit wide keep their name, bits of buses are called `name[i]`
+pub(crate) fn bit_name(name: &str, i: usize, width: usize) -> String {
+    if width == 1 {
+        name.to_string()
+    } else {
+        format!("{}[{}]", name, i)
+    }
+}
+
+/// gets the names of the nets carrying all bits of a signal that is `width` bits wide
+pub(crate) fn bit_names(name: &str, width: usize) -> Vec<String> {
+    (0..width).map(|i| bit_name(name, i, width)).collect()
+}
+
+/// the chip, lookup table or primitive a part refers to
+pub(crate) enum Callee<'a> {
+    Lut(&'a LookupTable),
+    Chip(&'a ChipDef),
+    Prim(Primitive),
+}
+
+impl<'a> Callee<'a> {
+    /// looks up the part `name` in the `library`, lookup tables are preferred over chips
+    /// primitives are only used if the library has no part with the same name
+    pub fn find(library: &'a Library, name: &str) -> Option<Self> {
+        if let Some(lut) = library.lut(name) {
+            Some(Self::Lut(lut))
+        } else if let Some(chip_def) = library.chip(name) {
+            Some(Self::Chip(chip_def))
+        } else {
+            Primitive::find(name).map(Self::Prim)
+        }
+    }
+
+    /// gets the width of the pin `name` or `None` if there is no such pin
+    pub fn width(&self, name: &str) -> Option<usize> {
+        match self {
+            Self::Lut(lut) => lut
+                .in_map(&name.to_string())
+                .or(lut.out_map(&name.to_string()))
+                .map(|_| 1),
+            Self::Chip(chip_def) => {
+                if chip_def.inputs().iter().any(|pin| pin == name)
+                    || chip_def.outputs().iter().any(|pin| pin == name)
+                {
+                    Some(chip_def.width(name))
+                } else {
+                    None
+                }
+            }
+            Self::Prim(primitive) => primitive
+                .in_names()
+                .iter()
+                .chain(primitive.out_names().iter())
+                .any(|pin| pin == name)
+                .then_some(1),
+        }
+    }
+
+    /// returns true if `name` is an input pin
+    pub fn is_input(&self, name: &str) -> bool {
+        match self {
+            Self::Lut(lut) => lut.in_map(&name.to_string()).is_some(),
+            Self::Chip(chip_def) => chip_def.inputs().iter().any(|pin| pin == name),
+            Self::Prim(primitive) => primitive.in_names().iter().any(|pin| pin == name),
+        }
+    }
+}
+
+/// gets the bit names selected by a connection like `a`, `a[3]` or `a[0..7]`
+/// `width` gets the width of a signal or `None` if the signal dose not exist
+/// returns the reason if the bits can not be selected
+pub(crate) fn slice_bits(
+    expr: &str,
+    width: impl Fn(&str) -> Option<usize>,
+) -> Result<Vec<String>, String> {
+    let (name, range) = split_slice(expr)?;
+    let width = match width(&name) {
+        Some(width) => width,
+        None => return Err(format!("unknown signal {}", name)),
+    };
+    let (start, end) = range.unwrap_or((0, width - 1));
+    if end >= width {
+        return Err(format!(
+            "{} is out of range, {} is {} bits wide",
+            expr, name, width
+        ));
+    }
+    Ok((start..=end).map(|i| bit_name(&name, i, width)).collect())
+}
+
+/// sets the width of the internal signal used by a connection like `a`, `a[3]` or `a[0..7]`
+/// unsliced signals take the width of the first pin they are connected to, sliced signals are as wide as the highest bit used
+/// the widths of the ports of `chip_def` are never changed
+pub(crate) fn infer_width(
+    chip_def: &ChipDef,
+    widths: &mut HashMap<String, usize>,
+    expr: &str,
+    len: usize,
+) -> Result<(), String> {
+    let (name, range) = split_slice(expr)?;
+    match range {
+        Some(_) if chip_def.inputs().contains(&name) || chip_def.outputs().contains(&name) => (),
+        Some((_, end)) => {
+            let width = widths.entry(name).or_insert(end + 1);
+            *width = (*width).max(end + 1);
+        }
+        None => {
+            widths.entry(name).or_insert(len);
+        }
+    }
+    Ok(())
+}
+
+/// recursively expands `chip_def` with the chips and lookup tables in `library`
+pub(crate) fn flatten(chip_def: &ChipDef, library: &Library) -> Result<Netlist, Error> {
+    let buses = |ports: Vec<String>| -> Vec<(String, usize)> {
+        ports
+            .into_iter()
+            .map(|port| {
+                let width = chip_def.width(&port);
+                (port, width)
+            })
+            .collect()
+    };
+    let bits = |buses: &Vec<(String, usize)>| -> Vec<String> {
+        buses
+            .iter()
+            .flat_map(|(port, width)| bit_names(port, *width))
+            .collect()
+    };
+
+    let in_buses = buses(chip_def.inputs());
+    let out_buses = buses(chip_def.outputs());
+    let mut netlist = Netlist {
+        name: chip_def.name(),
+        inputs: bits(&in_buses),
+        outputs: bits(&out_buses),
+        in_buses,
+        out_buses,
+        cells: Vec::new(),
+        states: Vec::new(),
+        aliases: HashMap::new(),
+    };
+    // pairs of nets connected by a `Component::Map`
+    let mut aliases = Vec::new();
+
+    // the ports of the top level chip are nets with the same name
+    let ports = netlist
+        .inputs
+        .iter()
+        .chain(netlist.outputs.iter())
+        .map(|port| (port.clone(), port.clone()))
+        .collect();
+
+    expand(
+        chip_def,
+        library,
+        "",
+        &ports,
+        &mut vec![chip_def.name()],
+        &mut netlist,
+        &mut aliases,
+    )?;
+
+    resolve_aliases(&mut netlist, aliases)?;
+    Ok(netlist)
+}
+
+/// merges the aliased nets and renames every net of the cells to the net representing it
+/// top level ports are preferred as representatives so that they keep their names
+fn resolve_aliases(netlist: &mut Netlist, aliases: Vec<(String, String)>) -> Result<(), Error> {
+    fn find(parent: &HashMap<String, String>, mut net: String) -> String {
+        while let Some(next) = parent.get(&net) {
+            net = next.clone();
+        }
+        net
+    }
+
+    let mut parent: HashMap<String, String> = HashMap::new();
+    for (a, b) in aliases {
+        let (a, b) = (find(&parent, a), find(&parent, b));
+        if a == b {
+            continue;
+        }
+
+        if netlist.inputs.contains(&a) && netlist.inputs.contains(&b) {
+            return Err(Error::Invalid(vec![Diagnostic::MultipleDrivers {
+                chip: netlist.name.clone(),
+                drivers: vec![format!("input {}", a), format!("input {}", b)],
+                wire: a,
+            }]));
+        }
+
+        let is_port = |net: &String| netlist.inputs.contains(net) || netlist.outputs.contains(net);
+        if is_port(&b) && !is_port(&a) {
+            parent.insert(a, b);
+        } else {
+            parent.insert(b, a);
+        }
+    }
+
+    netlist.aliases = parent
+        .keys()
+        .map(|net| (net.clone(), find(&parent, net.clone())))
+        .collect();
+
+    let nets = netlist
+        .cells
+        .iter_mut()
+        .flat_map(|cell| cell.inputs.iter_mut().chain(cell.outputs.iter_mut()))
+        .chain(
+            netlist
+                .states
+                .iter_mut()
+                .flat_map(|state| state.inputs.iter_mut().chain(state.outputs.iter_mut())),
+        );
+    for (_, net) in nets {
+        if let Some(alias) = netlist.aliases.get(net) {
+            *net = alias.clone();
+        }
+    }
+
+    Ok(())
+}
+
+/// gets the input and output pin names of the chip, lookup table or primitive `name` in declaration order
+pub(crate) fn pin_names(library: &Library, name: &str) -> Option<(Vec<String>, Vec<String>)> {
+    match Callee::find(library, name)? {
+        Callee::Lut(lut) => Some((lut.in_names(), lut.out_names())),
+        Callee::Chip(chip_def) => Some((chip_def.inputs(), chip_def.outputs())),
+        Callee::Prim(primitive) => Some((primitive.in_names(), primitive.out_names())),
+    }
+}
+
+/// wraps the reason why `connection` is invalid in to an `Error`
+fn invalid_connection(chip: &str, connection: &str) -> impl FnOnce(String) -> Error {
+    let (chip, connection) = (chip.to_string(), connection.to_string());
+    move |reason| {
+        Error::Invalid(vec![Diagnostic::InvalidConnection {
+            chip,
+            connection,
+            reason,
+        }])
+    }
+}
+
+/// the parts of a chip with the pins resolved against a library
+pub(crate) struct Resolved<'a> {
+    /// widths of the ports and internal signals
+    pub widths: HashMap<String, usize>,
+    /// (instance, part name, callee, (pin, wire) pairs) of the parts without errors
+    pub parts: Vec<(String, String, Callee<'a>, Vec<(String, String)>)>,
+    /// (instance, (a, b)) of the maps
+    pub maps: Vec<(String, (String, String))>,
+    /// every problem that was found while resolving
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// like `resolve_all` but stops at the first error, a missing part is returned as `Error::MissingPart`
+pub(crate) fn resolve<'a>(chip_def: &ChipDef, library: &'a Library) -> Result<Resolved<'a>, Error> {
+    let resolved = resolve_all(chip_def, library);
+    match resolved
+        .diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.is_error())
+    {
+        Some(Diagnostic::MissingPart { chip, part, .. }) => Err(Error::MissingPart {
+            chip: chip.clone(),
+            part: part.clone(),
+        }),
+        Some(diagnostic) => Err(Error::Invalid(vec![diagnostic.clone()])),
+        None => Ok(resolved),
+    }
+}
+
+/// finds the callees of the parts of `chip_def`, names the pins of `Component::IO` and works out the widths of the internal signals
+/// parts with problems are reported in `diagnostics` and left out of `parts`
+pub(crate) fn resolve_all<'a>(chip_def: &ChipDef, library: &'a Library) -> Resolved<'a> {
+    let chip = chip_def.name();
+    let mut diagnostics = Vec::new();
+    let invalid = |connection: &str, reason: String| Diagnostic::InvalidConnection {
+        chip: chip.clone(),
+        connection: connection.to_string(),
+        reason,
+    };
+
+    let instances = chip_def.instance_names();
+    for (i, instance) in instances.iter().enumerate() {
+        if instances[..i].contains(instance) && !instances[i + 1..].contains(instance) {
+            diagnostics.push(Diagnostic::DuplicateInstance {
+                chip: chip.clone(),
+                instance: instance.clone(),
+            });
+        }
+    }
+
+    // widths of the ports and internal signals of this chip
+    let mut widths: HashMap<String, usize> = chip_def
+        .inputs()
+        .into_iter()
+        .chain(chip_def.outputs())
+        .map(|port| {
+            let width = chip_def.width(&port);
+            (port, width)
+        })
+        .collect();
+    // (instance, part name, callee, (pin, net) pairs)
+    let mut parts = Vec::new();
+    let mut maps = Vec::new();
+
+    for (part, instance) in chip_def.parts().into_iter().zip(instances) {
+        let missing = |name: &String| Diagnostic::MissingPart {
+            chip: chip.clone(),
+            instance: instance.clone(),
+            part: name.clone(),
+        };
+
+        let (pins, name): (Vec<(String, String)>, String) = match part {
+            Component::Def {
+                inputs,
+                outputs,
+                name,
+            } => (inputs.into_iter().chain(outputs).collect(), name),
+
+            // the pins are connected by position
+            Component::IO {
+                inputs,
+                outputs,
+                name,
+            } => {
+                let (in_names, out_names) = match pin_names(library, &name) {
+                    Some(names) => names,
+                    None => {
+                        diagnostics.push(missing(&name));
+                        continue;
+                    }
+                };
+                if inputs.len() != in_names.len() || outputs.len() != out_names.len() {
+                    diagnostics.push(Diagnostic::PinCount {
+                        chip: chip.clone(),
+                        instance: instance.clone(),
+                        part: name,
+                        expected: in_names.iter().chain(out_names.iter()).cloned().collect(),
+                        connected: in_names
+                            .iter()
+                            .take(inputs.len())
+                            .chain(out_names.iter().take(outputs.len()))
+                            .cloned()
+                            .collect(),
+                    });
+                    continue;
+                }
+                (
+                    in_names
+                        .into_iter()
+                        .zip(inputs)
+                        .chain(out_names.into_iter().zip(outputs))
+                        .collect(),
+                    name,
+                )
+            }
+
+            // the nets are aliases of each other
+            Component::Map { var_map, .. } => {
+                for pair in var_map {
+                    maps.push((instance.clone(), pair));
+                }
+                continue;
+            }
+        };
+
+        let callee = match Callee::find(library, &name) {
+            Some(callee) => callee,
+            None => {
+                diagnostics.push(missing(&name));
+                continue;
+            }
+        };
+
+        let mut valid = true;
+        let mut connected = Vec::new();
+        for (pin, wire) in pins.iter() {
+            let (pin_name, pin_range) = match split_slice(pin) {
+                Ok(slice) => slice,
+                Err(err) => {
+                    diagnostics.push(invalid(pin, err));
+                    valid = false;
+                    continue;
+                }
+            };
+            let pin_width = match callee.width(&pin_name) {
+                Some(width) => width,
+                None => {
+                    diagnostics.push(Diagnostic::UnknownPin {
+                        chip: chip.clone(),
+                        instance: instance.clone(),
+                        part: name.clone(),
+                        pin: pin_name,
+                    });
+                    valid = false;
+                    continue;
+                }
+            };
+            let len = match pin_range {
+                Some((start, end)) => end - start + 1,
+                None => pin_width,
+            };
+            if let Err(err) = infer_width(chip_def, &mut widths, wire, len) {
+                diagnostics.push(invalid(wire, err));
+                valid = false;
+            }
+            if !connected.contains(&pin_name) {
+                connected.push(pin_name);
+            }
+        }
+
+        let in_names = pin_names(library, &name).unwrap_or_default().0;
+        if in_names.iter().any(|pin| !connected.contains(pin)) {
+            diagnostics.push(Diagnostic::PinCount {
+                chip: chip.clone(),
+                instance: instance.clone(),
+                part: name.clone(),
+                expected: in_names.clone(),
+                connected: connected
+                    .into_iter()
+                    .filter(|pin| in_names.contains(pin))
+                    .collect(),
+            });
+        }
+
+        if valid {
+            parts.push((instance, name, callee, pins));
+        }
+    }
+
+    // signals that are only connected by maps take the width of the other side
+    for (_, (a, b)) in maps.iter() {
+        let width = |expr: &String| match split_slice(expr) {
+            Ok((_, Some((start, end)))) => Some(end - start + 1),
+            Ok((name, None)) => widths.get(&name).copied(),
+            Err(_) => None,
+        };
+        let (a_width, b_width) = (width(a), width(b));
+        for (expr, width) in [(a, a_width.or(b_width)), (b, b_width.or(a_width))] {
+            if let Err(err) = infer_width(chip_def, &mut widths, expr, width.unwrap_or(1)) {
+                diagnostics.push(invalid(expr, err));
+            }
+        }
+    }
+
+    Resolved {
+        widths,
+        parts,
+        maps,
+        diagnostics,
+    }
+}
+
+/// adds the cells and state elements of `chip_def` to `netlist`
+/// `ports` maps the port bits of `chip_def` to the nets of the parent and `stack` holds the chips currently being expanded
+fn expand(
+    chip_def: &ChipDef,
+    library: &Library,
+    prefix: &str,
+    ports: &HashMap<String, String>,
+    stack: &mut Vec<String>,
+    netlist: &mut Netlist,
+    aliases: &mut Vec<(String, String)>,
+) -> Result<(), Error> {
+    let net = |name: &String| match ports.get(name) {
+        Some(net) => net.clone(),
+        None => format!("{}{}", prefix, name),
+    };
+    let chip = chip_def.name();
+    let invalid = |diagnostic| Error::Invalid(vec![diagnostic]);
+    let Resolved {
+        widths,
+        parts,
+        maps,
+        ..
+    } = resolve(chip_def, library)?;
+
+    let signal = |expr: &String| slice_bits(expr, |name| widths.get(name).copied());
+    for (instance, (a, b)) in maps.iter() {
+        let a_bits = signal(a).map_err(invalid_connection(&chip, a))?;
+        let b_bits = signal(b).map_err(invalid_connection(&chip, b))?;
+        if a_bits.len() != b_bits.len() {
+            return Err(invalid(Diagnostic::WidthMismatch {
+                chip: chip.clone(),
+                instance: instance.clone(),
+                pin: a.clone(),
+                pin_width: a_bits.len(),
+                wire: b.clone(),
+                wire_width: b_bits.len(),
+            }));
+        }
+        for (a, b) in a_bits.iter().zip(b_bits.iter()) {
+            aliases.push((net(a), net(b)));
+        }
+    }
+
+    for (instance, name, callee, pins) in parts {
+        // (pin bit, net) pairs
+        let mut bits = Vec::new();
+        for (pin, wire) in pins.iter() {
+            let pin_bits =
+                slice_bits(pin, |pin| callee.width(pin)).map_err(invalid_connection(&chip, pin))?;
+            let wire_bits = signal(wire).map_err(invalid_connection(&chip, wire))?;
+            if pin_bits.len() != wire_bits.len() {
+                return Err(invalid(Diagnostic::WidthMismatch {
+                    chip: chip.clone(),
+                    instance,
+                    pin: pin.clone(),
+                    pin_width: pin_bits.len(),
+                    wire: wire.clone(),
+                    wire_width: wire_bits.len(),
+                }));
+            }
+            bits.extend(pin_bits.into_iter().zip(wire_bits.iter().map(net)));
+        }
+
+        match callee {
+            Callee::Lut(lut) => {
+                let mut cell = Cell {
+                    name: format!("{}{}", prefix, instance),
+                    lut: lut.clone(),
+                    inputs: Vec::new(),
+                    outputs: Vec::new(),
+                };
+
+                // pins are sorted by the direction declared in the lut
+                for (pin, net) in bits {
+                    if let Some(port) = lut.in_map(&pin) {
+                        cell.inputs.push((port, net));
+                    } else if let Some(port) = lut.out_map(&pin) {
+                        cell.outputs.push((port, net));
+                    }
+                }
+
+                if cell.inputs.len() != lut.in_names().len() {
+                    let (expected, _) = pin_names(library, &name).unwrap_or_default();
+                    return Err(invalid(Diagnostic::PinCount {
+                        chip: chip.clone(),
+                        instance,
+                        part: name,
+                        connected: expected
+                            .iter()
+                            .filter(|pin| pins.iter().any(|(p, _)| &p == pin))
+                            .cloned()
+                            .collect(),
+                        expected,
+                    }));
+                }
+
+                netlist.cells.push(cell);
+            }
+
+            Callee::Chip(sub_def) => {
+                if stack.contains(&name) {
+                    let mut path = stack.clone();
+                    path.push(name);
+                    return Err(invalid(Diagnostic::Recursion { chip, path }));
+                }
+
+                // an output connected to more than one wire drives all of them
+                let mut sub_ports: HashMap<String, String> = HashMap::new();
+                for (pin, net) in bits {
+                    match sub_ports.get(&pin) {
+                        Some(first) => aliases.push((first.clone(), net)),
+                        None => {
+                            sub_ports.insert(pin, net);
+                        }
+                    }
+                }
+
+                stack.push(name.clone());
+                expand(
+                    sub_def,
+                    library,
+                    &format!("{}{}.", prefix, instance),
+                    &sub_ports,
+                    stack,
+                    netlist,
+                    aliases,
+                )?;
+                stack.pop();
+            }
+
+            Callee::Prim(primitive) => {
+                let (in_names, out_names) = (primitive.in_names(), primitive.out_names());
+                let mut state = State {
+                    name: format!("{}{}", prefix, instance),
+                    primitive,
+                    inputs: Vec::new(),
+                    outputs: Vec::new(),
+                };
+                for (pin, net) in bits {
+                    if let Some(port) = in_names.iter().position(|name| name == &pin) {
+                        state.inputs.push((port, net));
+                    } else if let Some(port) = out_names.iter().position(|name| name == &pin) {
+                        state.outputs.push((port, net));
+                    }
+                }
+
+                if state.inputs.len() != in_names.len() {
+                    return Err(invalid(Diagnostic::PinCount {
+                        chip: chip.clone(),
+                        instance,
+                        part: name,
+                        connected: in_names
+                            .iter()
+                            .filter(|pin| pins.iter().any(|(p, _)| &p == pin))
+                            .cloned()
+                            .collect(),
+                        expected: in_names,
+                    }));
+                }
+
+                netlist.states.push(state);
+            }
+        }
+    }
+
+    Ok(())
+}
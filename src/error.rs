@@ -0,0 +1,149 @@
+use std::fmt;
+use std::sync::Arc;
+
+use graph::GraphErr;
+
+use crate::Diagnostic;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// no input, output or pin has the name or id
+    UnknownPort(String),
+
+    /// no lookup table instance has the path
+    UnknownInstance(String),
+
+    /// an index is out of range, `len` is the number of valid indices
+    Index { index: usize, len: usize },
+
+    /// a table or a list of values has the wrong number of entries
+    Shape { expected: usize, found: usize },
+
+    /// a value dose not fit in to the bus `name`
+    Width {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// the part `part` used in `chip` is neither a chip nor a lookup table of the library
+    MissingPart { chip: String, part: String },
+
+    /// the `ChipDef` has errors, warnings are not included
+    Invalid(Vec<Diagnostic>),
+
+    /// the nets in a combinational loop did not settle
+    CombinationalLoop(Vec<String>),
+
+    /// the source could not be parsed, `line` and `column` start at 1
+    Parse {
+        line: usize,
+        column: usize,
+        msg: String,
+    },
+
+    /// the operation can not handle the part `part` e.g. a verilog module for a `DFF`
+    Unsupported { part: String, msg: String },
+
+    /// the output `output` of the lookup table `name` was read for a don't care row with `DontCarePolicy::X`
+    DontCare { name: String, output: String },
+
+    /// the delay of the input or instance was set to 0, a change takes at least one time step
+    ZeroDelay(String),
+
+    /// an error returned by the graph of a `Circuit`, the graph error is the `source` and not part of the message
+    Graph(GraphError),
+}
+
+impl Error {
+    /// wraps an error of the `graph` crate
+    pub(crate) fn graph(err: GraphErr) -> Self {
+        Self::Graph(GraphError(Arc::new(err)))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPort(name) => write!(f, "no port named {}", name),
+            Self::UnknownInstance(name) => write!(f, "no instance named {}", name),
+            Self::Index { index, len } => {
+                write!(f, "index {} is out of range for length {}", index, len)
+            }
+            Self::Shape { expected, found } => write!(
+                f,
+                "wrong shape expected {} entries but found {}",
+                expected, found
+            ),
+            Self::Width {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} is {} bits wide but {} bits are needed",
+                name, expected, found
+            ),
+            Self::MissingPart { chip, part } => {
+                write!(f, "{}: no chip or lookup table named {}", chip, part)
+            }
+            Self::Invalid(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i != 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            Self::CombinationalLoop(nets) => {
+                write!(f, "combinational loop did not settle [{}]", nets.join(", "))
+            }
+            Self::Parse { line, column, msg } => write!(f, "{}:{}: {}", line, column, msg),
+            Self::Unsupported { part, msg } => write!(f, "{}: {}", part, msg),
+            Self::DontCare { name, output } => {
+                write!(
+                    f,
+                    "{}: {} is a don't care for the current inputs",
+                    name, output
+                )
+            }
+            Self::ZeroDelay(name) => write!(f, "{}: a delay has to be at least 1", name),
+            Self::Graph(_) => write!(f, "graph error"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Graph(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// an error of the `graph` crate, errors are equal if their debug output is
+#[derive(Debug, Clone)]
+pub struct GraphError(Arc<GraphErr>);
+
+impl GraphError {
+    /// gets the error returned by the graph
+    pub fn graph_err(&self) -> &GraphErr {
+        &self.0
+    }
+}
+
+impl PartialEq for GraphError {
+    fn eq(&self, other: &Self) -> bool {
+        format!("{:?}", self.0) == format!("{:?}", other.0)
+    }
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl std::error::Error for GraphError {}
@@ -0,0 +1,136 @@
+use bool_algebra::{bool_to_u32, update_values};
+
+use crate::token::{error, Parser, Tok};
+use crate::{Error, LookupTable};
+
+const SYMBOLS: &[&str] = &["=", ";", "(", ")", "!", "~", "&", "|", "^"];
+
+/// a boolean expression over the inputs of a lookup table
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Const(bool),
+    /// index in to the inputs
+    Input(usize),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, values: &[bool]) -> bool {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Input(i) => values[*i],
+            Expr::Not(e) => !e.eval(values),
+            Expr::And(a, b) => a.eval(values) && b.eval(values),
+            Expr::Or(a, b) => a.eval(values) || b.eval(values),
+            Expr::Xor(a, b) => a.eval(values) != b.eval(values),
+        }
+    }
+}
+
+/// creates a lookup table from assignments like `out = a & !b | c; carry = a & b`
+/// the outputs are the assigned names in the order they are written
+pub(crate) fn lut(src: &str, in_names: Vec<&str>, name: &str) -> Result<LookupTable, Error> {
+    let mut p = Parser::new(src, SYMBOLS)?;
+    let mut outputs: Vec<(String, Expr)> = Vec::new();
+    while !p.at_end() {
+        let token = p.peek().clone();
+        let output = p.ident()?;
+        if in_names.contains(&output.as_str()) {
+            return Err(error(
+                token.line,
+                token.column,
+                format!("{} is an input and can not be assigned", output),
+            ));
+        }
+        if outputs.iter().any(|(name, _)| *name == output) {
+            return Err(error(
+                token.line,
+                token.column,
+                format!("{} is assigned twice", output),
+            ));
+        }
+        p.symbol("=")?;
+        let expr = or(&mut p, &in_names)?;
+        outputs.push((output, expr));
+        if !p.eat(";") && !p.at_end() {
+            return Err(p.unexpected("';'"));
+        }
+    }
+    if outputs.is_empty() {
+        return Err(p.unexpected("an assignment"));
+    }
+
+    let mut table = vec![vec![false; 2_usize.pow(in_names.len() as u32)]; outputs.len()];
+    let mut values = vec![false; in_names.len()];
+    loop {
+        let row = bool_to_u32(values.clone()) as usize;
+        for (i, (_, expr)) in outputs.iter().enumerate() {
+            table[i][row] = expr.eval(&values);
+        }
+        if !update_values(&mut values) {
+            break;
+        }
+    }
+
+    let out_names: Vec<&str> = outputs.iter().map(|(name, _)| name.as_str()).collect();
+    LookupTable::new(table, in_names, out_names, name)
+}
+
+// the operators bind from loosest to tightest `|`, `^`, `&`, then `!` and `~`
+
+fn or(p: &mut Parser, in_names: &[&str]) -> Result<Expr, Error> {
+    let mut expr = xor(p, in_names)?;
+    while p.eat("|") {
+        expr = Expr::Or(Box::new(expr), Box::new(xor(p, in_names)?));
+    }
+    Ok(expr)
+}
+
+fn xor(p: &mut Parser, in_names: &[&str]) -> Result<Expr, Error> {
+    let mut expr = and(p, in_names)?;
+    while p.eat("^") {
+        expr = Expr::Xor(Box::new(expr), Box::new(and(p, in_names)?));
+    }
+    Ok(expr)
+}
+
+fn and(p: &mut Parser, in_names: &[&str]) -> Result<Expr, Error> {
+    let mut expr = unary(p, in_names)?;
+    while p.eat("&") {
+        expr = Expr::And(Box::new(expr), Box::new(unary(p, in_names)?));
+    }
+    Ok(expr)
+}
+
+fn unary(p: &mut Parser, in_names: &[&str]) -> Result<Expr, Error> {
+    if p.eat("!") || p.eat("~") {
+        return Ok(Expr::Not(Box::new(unary(p, in_names)?)));
+    }
+    if p.eat("(") {
+        let expr = or(p, in_names)?;
+        p.symbol(")")?;
+        return Ok(expr);
+    }
+    match p.peek().tok.clone() {
+        Tok::Number(n @ (0 | 1)) => {
+            p.next();
+            Ok(Expr::Const(n == 1))
+        }
+        // an input named `true` or `false` hides the constant
+        Tok::Ident(name) => match in_names.iter().position(|input| *input == name) {
+            Some(i) => {
+                p.next();
+                Ok(Expr::Input(i))
+            }
+            None if name == "true" || name == "false" => {
+                p.next();
+                Ok(Expr::Const(name == "true"))
+            }
+            None => Err(p.error(format!("{} is not an input", name))),
+        },
+        _ => Err(p.unexpected("an input, 0, 1, true, false, '!' or '('")),
+    }
+}
@@ -0,0 +1,436 @@
+use bool_algebra::update_values;
+
+use crate::chip_def::{split_slice, split_width};
+use crate::elaborate::{pin_names, Callee};
+use crate::primitive::Primitive;
+use crate::token::{error, Parser, Tok};
+use crate::{ChipDef, Component, Error, Library, LookupTable};
+
+/// a chip parsed from nand2tetris hdl
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HdlChip {
+    pub name: String,
+    /// ports with the width appended for buses e.g. `a[16]`
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub body: Body,
+    /// position of the `CHIP` keyword
+    pub pos: (usize, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Body {
+    /// (part name, (pin, wire) pairs) in the order they are written
+    Parts(Vec<(String, Vec<(String, String)>)>),
+    /// the chip is implemented by a lookup table
+    Builtin(Box<LookupTable>),
+    /// the chip is the primitive `name` that is clocked by the tick e.g. `DFF`
+    Primitive(String),
+}
+
+impl HdlChip {
+    /// turns the parts in to a `ChipDef`, returns `None` for chips that are a lookup table or a primitive of the same name
+    /// pins are sorted in to inputs and outputs with `in_pins`, which gets the input pins of a part with their widths
+    /// all pins of unknown parts are treated as inputs, elaboration looks at the part itself anyway
+    /// inputs connected to `true` or `false` are driven by the lookup tables `$true` and `$false` from `constant`
+    pub fn to_chip_def(
+        &self,
+        in_pins: impl Fn(&str) -> Option<Vec<(String, usize)>>,
+    ) -> Option<ChipDef> {
+        let parts = match &self.body {
+            Body::Parts(parts) => parts.clone(),
+            Body::Builtin(_) => return None,
+            Body::Primitive(primitive) if primitive == &self.name => return None,
+            // the ports are named like the pins of the primitive
+            Body::Primitive(primitive) => {
+                let pins = self.inputs.iter().chain(self.outputs.iter());
+                vec![(
+                    primitive.clone(),
+                    pins.map(|p| (p.clone(), p.clone())).collect(),
+                )]
+            }
+        };
+
+        let mut constants = [false, false];
+        let mut buses = Vec::new();
+        let mut chip_parts = Vec::new();
+        for (name, pins) in parts {
+            let in_pins = in_pins(&name);
+            let (mut inputs, outputs): (Vec<_>, Vec<_>) = pins.into_iter().partition(|(pin, _)| {
+                let pin = pin.split('[').next().unwrap_or(pin);
+                match &in_pins {
+                    Some(in_pins) => in_pins.iter().any(|(name, _)| name == pin),
+                    None => true,
+                }
+            });
+
+            for (pin, wire) in inputs.iter_mut() {
+                let value = match wire.as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => continue,
+                };
+                constants[value as usize] = true;
+                let width = match split_slice(pin) {
+                    Ok((_, Some((start, end)))) => end - start + 1,
+                    Ok((pin, None)) => in_pins
+                        .iter()
+                        .flatten()
+                        .find(|(name, _)| *name == pin)
+                        .map_or(1, |(_, width)| *width),
+                    Err(_) => 1,
+                };
+                // every bit of a bus is an alias of the constant
+                *wire = if width == 1 {
+                    format!("${}", value)
+                } else {
+                    let bus = format!("${}_{}", value, buses.len());
+                    buses.push((
+                        Component::Map {
+                            var_map: (0..width)
+                                .map(|i| (format!("${}", value), format!("{}[{}]", bus, i)))
+                                .collect(),
+                            name: bus.clone(),
+                        },
+                        None,
+                    ));
+                    bus
+                };
+            }
+
+            chip_parts.push((
+                Component::Def {
+                    inputs,
+                    outputs,
+                    name,
+                },
+                None,
+            ));
+        }
+
+        for value in [false, true] {
+            if constants[value as usize] {
+                let name = format!("${}", value);
+                chip_parts.push((
+                    Component::Def {
+                        inputs: Vec::new(),
+                        outputs: vec![("Y".to_string(), name.clone())],
+                        name: name.clone(),
+                    },
+                    Some(name),
+                ));
+            }
+        }
+        chip_parts.extend(buses);
+
+        Some(ChipDef::with_instances(
+            self.name.clone(),
+            self.inputs.clone(),
+            self.outputs.clone(),
+            chip_parts,
+        ))
+    }
+}
+
+/// gets the lookup table `$true` or `$false` without inputs that drives the constant `value`
+/// the output is called `Y` like the one of the constants of a yosys netlist
+pub(crate) fn constant(value: bool) -> LookupTable {
+    LookupTable::from_fn(vec![], vec!["Y"], &format!("${}", value), |_| vec![value]).unwrap()
+}
+
+/// parses every chip in `src`
+pub(crate) fn parse(src: &str) -> Result<Vec<HdlChip>, Error> {
+    let mut parser = Parser::new(
+        src,
+        &["..", "{", "}", "(", ")", "[", "]", ";", ",", "=", ":"],
+    )?;
+    let mut chips = Vec::new();
+    while !parser.at_end() {
+        chips.push(chip(&mut parser)?);
+    }
+    Ok(chips)
+}
+
+/// gets the input pins of `name` with their widths from the chips in `chips`, the library or the builtin chips
+pub(crate) fn in_pins(
+    chips: &[HdlChip],
+    library: &Library,
+    name: &str,
+) -> Option<Vec<(String, usize)>> {
+    let port = |decl: &String| split_width(decl).unwrap_or((decl.clone(), 1));
+    if let Some(chip) = chips.iter().find(|chip| chip.name == name) {
+        return Some(chip.inputs.iter().map(port).collect());
+    }
+    if let Some((inputs, _)) = pin_names(library, name) {
+        let callee = Callee::find(library, name)?;
+        return Some(
+            inputs
+                .into_iter()
+                .map(|pin| {
+                    let width = callee.width(&pin).unwrap_or(1);
+                    (pin, width)
+                })
+                .collect(),
+        );
+    }
+    builtin(name).map(|(inputs, _, _)| inputs.iter().map(|s| (s.to_string(), 1)).collect())
+}
+
+/// (inputs, outputs, function) of the builtin chips
+type Builtin = (
+    &'static [&'static str],
+    &'static [&'static str],
+    fn(&[bool]) -> Vec<bool>,
+);
+
+/// gets the builtin chip named `name`, only chips with 1 bit pins can be builtin
+/// bus chips like `Mux16` would need a lookup table with too many rows and have to be written with parts
+fn builtin(name: &str) -> Option<Builtin> {
+    Some(match name {
+        "Nand" => (&["a", "b"], &["out"], |v| vec![!(v[0] && v[1])]),
+        "Not" => (&["in"], &["out"], |v| vec![!v[0]]),
+        "And" => (&["a", "b"], &["out"], |v| vec![v[0] && v[1]]),
+        "Or" => (&["a", "b"], &["out"], |v| vec![v[0] || v[1]]),
+        "Xor" => (&["a", "b"], &["out"], |v| vec![v[0] != v[1]]),
+        "Mux" => (&["a", "b", "sel"], &["out"], |v| {
+            vec![if v[2] { v[1] } else { v[0] }]
+        }),
+        "DMux" => (&["in", "sel"], &["a", "b"], |v| {
+            vec![v[0] && !v[1], v[0] && v[1]]
+        }),
+        "HalfAdder" => (&["a", "b"], &["sum", "carry"], |v| {
+            vec![v[0] != v[1], v[0] && v[1]]
+        }),
+        "FullAdder" => (&["a", "b", "c"], &["sum", "carry"], |v| {
+            let count = v.iter().filter(|&&v| v).count();
+            vec![count % 2 == 1, count >= 2]
+        }),
+        _ => return None,
+    })
+}
+
+/// creates the lookup table of the builtin chip `builtin` for the ports in the order they are declared
+fn builtin_lut(
+    name: &str,
+    builtin: &Builtin,
+    inputs: &[String],
+    outputs: &[String],
+) -> Result<LookupTable, String> {
+    let (builtin_in, builtin_out, function) = builtin;
+    let same = |declared: &[String], expected: &[&str]| {
+        declared.len() == expected.len()
+            && expected.iter().all(|pin| declared.iter().any(|d| d == pin))
+    };
+    if !same(inputs, builtin_in) || !same(outputs, builtin_out) {
+        return Err(format!(
+            "builtin chip {} needs the ports IN {}; OUT {};",
+            name,
+            builtin_in.join(", "),
+            builtin_out.join(", ")
+        ));
+    }
+
+    let mut values = vec![false; inputs.len()];
+    let mut table = vec![Vec::with_capacity(2_usize.pow(inputs.len() as u32)); outputs.len()];
+    loop {
+        let args: Vec<bool> = builtin_in
+            .iter()
+            .map(|pin| values[inputs.iter().position(|d| d == pin).unwrap()])
+            .collect();
+        let result = function(&args);
+        for (i, output) in outputs.iter().enumerate() {
+            table[i].push(result[builtin_out.iter().position(|pin| pin == output).unwrap()]);
+        }
+        if !update_values(&mut values) {
+            break;
+        }
+    }
+
+    LookupTable::new(
+        table,
+        inputs.iter().map(|s| s.as_str()).collect(),
+        outputs.iter().map(|s| s.as_str()).collect(),
+        name,
+    )
+    .map_err(|err| err.to_string())
+}
+
+/// `CHIP name { IN ports; OUT ports; PARTS: parts }` or `BUILTIN name;` instead of the parts
+fn chip(p: &mut Parser) -> Result<HdlChip, Error> {
+    let pos = (p.peek().line, p.peek().column);
+    p.keyword("CHIP")?;
+    let name = p.ident()?;
+    p.symbol("{")?;
+
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    if p.is_keyword("IN") {
+        p.next();
+        inputs = ports(p)?;
+    }
+    if p.is_keyword("OUT") {
+        p.next();
+        outputs = ports(p)?;
+    }
+
+    let body = if p.is_keyword("BUILTIN") {
+        p.next();
+        let token = p.peek().clone();
+        let builtin_name = p.ident()?;
+        p.symbol(";")?;
+        let clocked = p.is_keyword("CLOCKED");
+        if clocked {
+            p.next();
+            ports(p)?;
+        }
+
+        // a clocked builtin is a primitive whose state is updated by the tick
+        match Primitive::find(&builtin_name) {
+            Some(primitive @ Primitive::Dff { .. }) => {
+                let same = |declared: &[String], expected: Vec<String>| {
+                    declared.len() == expected.len()
+                        && expected.iter().all(|pin| declared.contains(pin))
+                };
+                if !same(&inputs, primitive.in_names()) || !same(&outputs, primitive.out_names())
+                {
+                    return Err(error(
+                        token.line,
+                        token.column,
+                        format!(
+                            "builtin chip {} needs the ports IN {}; OUT {};",
+                            builtin_name,
+                            primitive.in_names().join(", "),
+                            primitive.out_names().join(", ")
+                        ),
+                    ));
+                }
+                p.symbol("}")?;
+                return Ok(HdlChip {
+                    name,
+                    inputs,
+                    outputs,
+                    body: Body::Primitive(builtin_name),
+                    pos,
+                });
+            }
+            _ if clocked => {
+                return Err(error(
+                    token.line,
+                    token.column,
+                    format!(
+                        "only the builtin chip DFF can be clocked, {} is not a flip-flop clocked by the tick",
+                        builtin_name
+                    ),
+                ))
+            }
+            _ => {}
+        }
+
+        let lut = builtin(&builtin_name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown builtin chip {}, bus chips like Mux16 are not builtin and need parts",
+                    builtin_name
+                )
+            })
+            .and_then(|builtin| {
+                if inputs
+                    .iter()
+                    .chain(outputs.iter())
+                    .any(|port| port.contains('['))
+                {
+                    return Err(format!("builtin chip {} can not have buses", name));
+                }
+                builtin_lut(&name, &builtin, &inputs, &outputs)
+            })
+            .map_err(|msg| error(token.line, token.column, msg))?;
+        Body::Builtin(Box::new(lut))
+    } else {
+        p.keyword("PARTS")?;
+        p.symbol(":")?;
+        let mut parts = Vec::new();
+        while let Tok::Ident(_) = p.peek().tok {
+            parts.push(part(p)?);
+        }
+        Body::Parts(parts)
+    };
+
+    p.symbol("}")?;
+    Ok(HdlChip {
+        name,
+        inputs,
+        outputs,
+        body,
+        pos,
+    })
+}
+
+/// `a, b[16];`
+fn ports(p: &mut Parser) -> Result<Vec<String>, Error> {
+    let mut ports = Vec::new();
+    loop {
+        let mut port = p.ident()?;
+        if p.eat("[") {
+            let token = p.peek().clone();
+            let width = p.number()?;
+            if width == 0 {
+                return Err(error(
+                    token.line,
+                    token.column,
+                    format!("bus {} needs at least 1 bit", port),
+                ));
+            }
+            p.symbol("]")?;
+            port = format!("{}[{}]", port, width);
+        }
+        ports.push(port);
+        if !p.eat(",") {
+            break;
+        }
+    }
+    p.symbol(";")?;
+    Ok(ports)
+}
+
+/// `Name(pin=wire, pin[0..7]=wire[3..10]);`
+fn part(p: &mut Parser) -> Result<(String, Vec<(String, String)>), Error> {
+    let name = p.ident()?;
+    p.symbol("(")?;
+    let mut pins = Vec::new();
+    loop {
+        let pin = signal(p)?;
+        p.symbol("=")?;
+        let wire = signal(p)?;
+        pins.push((pin, wire));
+        if !p.eat(",") {
+            break;
+        }
+    }
+    p.symbol(")")?;
+    p.symbol(";")?;
+    Ok((name, pins))
+}
+
+/// `a`, `a[3]` or `a[0..7]`
+fn signal(p: &mut Parser) -> Result<String, Error> {
+    let name = p.ident()?;
+    if !p.eat("[") {
+        return Ok(name);
+    }
+    let token = p.peek().clone();
+    let start = p.number()?;
+    let end = if p.eat("..") { p.number()? } else { start };
+    p.symbol("]")?;
+    if end < start {
+        return Err(error(
+            token.line,
+            token.column,
+            format!("the range {}..{} is empty", start, end),
+        ));
+    }
+    if start == end {
+        Ok(format!("{}[{}]", name, start))
+    } else {
+        Ok(format!("{}[{}..{}]", name, start, end))
+    }
+}
@@ -0,0 +1,261 @@
+use crate::token::error;
+use crate::Error;
+
+/// a json value with the position of its first char
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Value {
+    pub json: Json,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// the members in the order they are written
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn error(&self, msg: String) -> Error {
+        error(self.line, self.column, msg)
+    }
+
+    /// gets the member `key` of an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match &self.json {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// gets the members of an object or an error if the value is not an object
+    pub fn members(&self) -> Result<&[(String, Value)], Error> {
+        match &self.json {
+            Json::Object(members) => Ok(members),
+            _ => Err(self.error("expected an object".to_string())),
+        }
+    }
+
+    pub fn elements(&self) -> Result<&[Value], Error> {
+        match &self.json {
+            Json::Array(elements) => Ok(elements),
+            _ => Err(self.error("expected an array".to_string())),
+        }
+    }
+
+    pub fn str(&self) -> Result<&str, Error> {
+        match &self.json {
+            Json::String(s) => Ok(s),
+            _ => Err(self.error("expected a string".to_string())),
+        }
+    }
+
+    /// gets a number that is a whole non negative number
+    pub fn usize(&self) -> Result<usize, Error> {
+        match self.json {
+            Json::Number(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            _ => Err(self.error("expected a whole number".to_string())),
+        }
+    }
+}
+
+/// parses the json document `src`
+pub(crate) fn parse(src: &str) -> Result<Value, Error> {
+    let mut parser = Parser {
+        chars: src.chars().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("expected the end of the document".to_string()));
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Parser {
+    fn error(&self, msg: String) -> Error {
+        error(self.line, self.column, msg)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.advance();
+        }
+    }
+
+    /// consumes `c` after any whitespace
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", c)))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        match self.peek() {
+            Some(c) => self.error(format!("expected {} but found {:?}", expected, c)),
+            None => self.error(format!("expected {} but found end of input", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+        let json = match self.peek() {
+            Some('{') => {
+                self.advance();
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.advance();
+                } else {
+                    loop {
+                        self.skip_whitespace();
+                        if self.peek() != Some('"') {
+                            return Err(self.unexpected("a string"));
+                        }
+                        let key = self.string()?;
+                        self.expect(':')?;
+                        members.push((key, self.value()?));
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(',') => self.advance(),
+                            Some('}') => {
+                                self.advance();
+                                break;
+                            }
+                            _ => return Err(self.unexpected("',' or '}'")),
+                        };
+                    }
+                }
+                Json::Object(members)
+            }
+            Some('[') => {
+                self.advance();
+                let mut elements = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.advance();
+                } else {
+                    loop {
+                        elements.push(self.value()?);
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(',') => self.advance(),
+                            Some(']') => {
+                                self.advance();
+                                break;
+                            }
+                            _ => return Err(self.unexpected("',' or ']'")),
+                        };
+                    }
+                }
+                Json::Array(elements)
+            }
+            Some('"') => Json::String(self.string()?),
+            Some('-' | '0'..='9') => {
+                let mut number = String::new();
+                while let Some(c @ ('-' | '+' | '.' | 'e' | 'E' | '0'..='9')) = self.peek() {
+                    number.push(c);
+                    self.advance();
+                }
+                match number.parse() {
+                    Ok(n) => Json::Number(n),
+                    Err(_) => {
+                        return Err(error(line, column, format!("{} is not a number", number)))
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut word = String::new();
+                while let Some(c) = self.peek().filter(|c| c.is_ascii_alphabetic()) {
+                    word.push(c);
+                    self.advance();
+                }
+                match word.as_str() {
+                    "null" => Json::Null,
+                    "true" => Json::Bool(true),
+                    "false" => Json::Bool(false),
+                    _ => return Err(error(line, column, format!("unexpected {}", word))),
+                }
+            }
+            _ => return Err(self.unexpected("a value")),
+        };
+        Ok(Value { json, line, column })
+    }
+
+    /// reads a string starting at its opening quote
+    fn string(&mut self) -> Result<String, Error> {
+        self.advance();
+        let mut s = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let c = match self.advance() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => {
+                            let hex: String = (0..4).filter_map(|_| self.advance()).collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        Some(c @ ('"' | '\\' | '/')) => c,
+                        _ => {
+                            return Err(error(
+                                self.line,
+                                self.column - 1,
+                                "invalid escape".to_string(),
+                            ))
+                        }
+                    };
+                    s.push(c);
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string".to_string())),
+            }
+        }
+    }
+}
@@ -1,18 +1,30 @@
+mod aiger;
+mod blif;
+mod builder;
+mod check;
 mod chip_def;
 mod circuit;
+mod elaborate;
+mod error;
+mod expr;
+mod hdl;
+mod json;
+mod library;
 mod lookup_tabel;
+mod minimize;
+mod pla;
+mod primitive;
+#[cfg(feature = "serde")]
+mod repr;
+mod token;
+mod verilog;
+mod yosys;
 
+pub use builder::{CircuitBuilder, InputId, InstanceId, OutputId, Sink, Source};
+pub use check::Diagnostic;
 pub use chip_def::{ChipDef, Component};
-pub use circuit::Circuit;
-pub use lookup_tabel::LookupTable;
-
-#[derive(PartialEq, Debug, Clone)]
-pub struct Error {
-    msg: String,
-}
-
-impl Error {
-    pub fn msg(msg: String) -> Self {
-        Self { msg: msg }
-    }
-}
+pub use circuit::{Circuit, Mode};
+pub use error::{Error, GraphError};
+pub use library::Library;
+pub use lookup_tabel::{DontCarePolicy, LookupTable};
+pub use minimize::{Expression, Form};
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use crate::blif;
+use crate::hdl::{self, Body};
+use crate::verilog;
+use crate::yosys;
+use crate::{ChipDef, Error, LookupTable};
+
+/// a collection of `ChipDef`s and `LookupTable`s that the parts of a `ChipDef` are resolved against
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Library {
+    chips: HashMap<String, ChipDef>,
+    luts: HashMap<String, LookupTable>,
+}
+
+impl Library {
+    /// creates an empty `Library`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a `ChipDef` under its own name, replacing any chip with the same name
+    pub fn add_chip(&mut self, chip_def: ChipDef) {
+        self.chips.insert(chip_def.name(), chip_def);
+    }
+
+    /// adds a `LookupTable` under its own name, replacing any table with the same name
+    pub fn add_lut(&mut self, lut: LookupTable) {
+        self.luts.insert(lut.name(), lut);
+    }
+
+    /// gets the `ChipDef` named `name`
+    pub fn chip(&self, name: &str) -> Option<&ChipDef> {
+        self.chips.get(name)
+    }
+
+    /// gets the `LookupTable` named `name`
+    pub fn lut(&self, name: &str) -> Option<&LookupTable> {
+        self.luts.get(name)
+    }
+
+    /// adds every chip written in the nand2tetris hdl `src`
+    /// `BUILTIN` chips are added as lookup tables, the parts of other chips can use chips of the library and of `src`
+    /// `BUILTIN DFF; CLOCKED in;` is the `DFF` primitive and the constants `true` and `false` add the lookup tables `$true` and `$false`
+    pub fn add_hdl(&mut self, src: &str) -> Result<(), Error> {
+        let chips = hdl::parse(src)?;
+        let mut chip_defs = Vec::new();
+        for chip in chips.iter() {
+            match &chip.body {
+                Body::Builtin(lut) => self.add_lut(*lut.clone()),
+                Body::Parts(_) | Body::Primitive(_) => {
+                    if let Some(chip_def) =
+                        chip.to_chip_def(|name| hdl::in_pins(&chips, self, name))
+                    {
+                        chip_defs.push(chip_def);
+                    }
+                }
+            }
+        }
+        for value in [false, true] {
+            let name = format!("${}", value);
+            let used = chip_defs
+                .iter()
+                .any(|chip_def| chip_def.parts().iter().any(|part| part.name() == name));
+            if used {
+                self.add_lut(hdl::constant(value));
+            }
+        }
+        for chip_def in chip_defs {
+            self.add_chip(chip_def);
+        }
+        Ok(())
+    }
+
+    /// adds every module of the structural verilog `src` as a chip
+    /// the gate primitives `and`, `or`, `nand`, `nor`, `xor`, `xnor`, `not` and `buf` are added as lookup tables named like `$and2` or `$not`
+    /// modules connected by position have to be in `src` or the library
+    pub fn add_verilog(&mut self, src: &str) -> Result<(), Error> {
+        verilog::add_verilog(self, src)
+    }
+
+    /// adds every model of the blif `src` as a chip and every `.names` cover as a lookup table named `model.signal`
+    /// `.latch` becomes a `DFF` or `DFF1` part that is clocked by the tick, signals like `a[3]` are bits of the bus `a`
+    /// `re` and `ah` latches that start with 0 on a control of the model become `DFFC` and `DLatch` parts
+    /// returns `Error::Unsupported` for other latch types and for buses with bits that are not declared
+    pub fn add_blif(&mut self, src: &str) -> Result<(), Error> {
+        blif::add_blif(self, src)
+    }
+
+    /// adds every module of a yosys `write_json` netlist as a chip
+    /// gate cells like `$_AND_` and `$lut` cells become lookup tables named after the cell type
+    /// `$_DFF_P_` and `$_DFF_N_` become `DFFC` parts clocked by the `C` pin, `$_FF_` becomes a `DFF` part that is clocked by the tick
+    /// undefined `x` and high impedance `z` bits are not supported
+    pub fn add_yosys_json(&mut self, src: &str) -> Result<(), Error> {
+        yosys::add_yosys_json(self, src)
+    }
+
+    /// returns true if a chip or a lookup table is named `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.chips.contains_key(name) || self.luts.contains_key(name)
+    }
+}
+
+impl From<HashMap<String, LookupTable>> for Library {
+    /// the keys of the map are used as part names, like in `Circuit::new`
+    fn from(luts: HashMap<String, LookupTable>) -> Self {
+        Self {
+            chips: HashMap::new(),
+            luts,
+        }
+    }
+}
@@ -1,18 +1,51 @@
 use std::collections::HashMap;
 
-use crate::Error;
-use bool_algebra::bool_to_u32;
+use crate::expr;
+use crate::minimize::{self, Expression, Form};
+use crate::pla;
+use crate::verilog;
+use crate::{ChipDef, Error, Library};
+use bool_algebra::{bool_to_u32, update_values};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        into = "crate::repr::LookupTableRepr",
+        try_from = "crate::repr::LookupTableRepr"
+    )
+)]
 pub struct LookupTable {
     table: Vec<Vec<bool>>,
+    // rows of each output that are not specified, same shape as `table`
+    dont_care: Vec<Vec<bool>>,
+    policy: DontCarePolicy,
     in_values: Vec<bool>,
 
     in_map: HashMap<String, usize>,
     out_map: HashMap<String, usize>,
+    // names in the order they were declared
+    in_names: Vec<String>,
+    out_names: Vec<String>,
     name: String,
 }
 
+/// what a `LookupTable` outputs for a don't care row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum DontCarePolicy {
+    #[default]
+    Zero,
+    One,
+    /// reading the output is an error, this finds designs that depend on unspecified values
+    X,
+}
+
 impl LookupTable {
     pub fn new(
         table: Vec<Vec<bool>>,
@@ -21,19 +54,17 @@ impl LookupTable {
         name: &str,
     ) -> Result<Self, Error> {
         if table.len() != out_names.len() {
-            return Err(Error::msg(format!(
-                "wrong shape table.len() <{}> has to equal out_names.len() <{}>",
-                table.len(),
-                out_names.len()
-            )));
+            return Err(Error::Shape {
+                expected: out_names.len(),
+                found: table.len(),
+            });
         }
         for t in table.iter() {
             if 2_usize.pow(in_names.len() as u32) != t.len() {
-                return Err(Error::msg(format!(
-                    "wrong shape table[i].len() <{}> has to equal 2^in_names.len() <{}>",
-                    2_usize.pow(in_names.len() as u32),
-                    t.len()
-                )));
+                return Err(Error::Shape {
+                    expected: 2_usize.pow(in_names.len() as u32),
+                    found: t.len(),
+                });
             }
         }
 
@@ -51,15 +82,96 @@ impl LookupTable {
         }
 
         Ok(Self {
+            dont_care: table.iter().map(|t| vec![false; t.len()]).collect(),
+            policy: DontCarePolicy::Zero,
             table,
             in_values: vec![false; in_names.len()],
 
             name,
             in_map,
             out_map,
+            in_names: in_names.iter().map(|s| s.to_string()).collect(),
+            out_names: out_names.iter().map(|s| s.to_string()).collect(),
         })
     }
 
+    /// creates a table from boolean assignments like `out = a & !b | c; carry = (a ^ b) & 1`
+    /// the operators are `!` or `~`, `&`, `^` and `|` from tightest to loosest, the outputs are the assigned names in the order they are written
+    /// the constants are `0` and `1` or `true` and `false`
+    pub fn from_expr(src: &str, in_names: Vec<&str>, name: &str) -> Result<Self, Error> {
+        expr::lut(src, in_names, name)
+    }
+
+    /// creates a table by calling `function` with every combination of the inputs in the order of `in_names`
+    /// returns `Error::Shape` if `function` dose not return one value per output
+    pub fn from_fn<F>(
+        in_names: Vec<&str>,
+        out_names: Vec<&str>,
+        name: &str,
+        mut function: F,
+    ) -> Result<Self, Error>
+    where
+        F: FnMut(&[bool]) -> Vec<bool>,
+    {
+        let mut table = vec![vec![false; 2_usize.pow(in_names.len() as u32)]; out_names.len()];
+        let mut values = vec![false; in_names.len()];
+        loop {
+            let outputs = function(&values);
+            if outputs.len() != out_names.len() {
+                return Err(Error::Shape {
+                    expected: out_names.len(),
+                    found: outputs.len(),
+                });
+            }
+            let row = bool_to_u32(values.clone()) as usize;
+            for (column, value) in table.iter_mut().zip(outputs) {
+                column[row] = value;
+            }
+            if !update_values(&mut values) {
+                break;
+            }
+        }
+        Self::new(table, in_names, out_names, name)
+    }
+
+    /// like `from_fn` but the inputs are passed as a word with `in_names[i]` in bit `i`
+    /// and bit `i` of the returned word is `out_names[i]`
+    /// returns `Error::Width` if the returned word has bits set above the last output
+    pub fn from_word_fn<F>(
+        in_names: Vec<&str>,
+        out_names: Vec<&str>,
+        name: &str,
+        mut function: F,
+    ) -> Result<Self, Error>
+    where
+        F: FnMut(u64) -> u64,
+    {
+        let out_count = out_names.len();
+        let mut too_wide = None;
+        let lut = Self::from_fn(in_names, out_names, name, |values| {
+            let word = values
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, &value)| acc | (value as u64) << i);
+            let result = function(word);
+            let width = (u64::BITS - result.leading_zeros()) as usize;
+            if width > out_count {
+                too_wide.get_or_insert(width);
+            }
+            (0..out_count)
+                .map(|i| i < 64 && result >> i & 1 == 1)
+                .collect()
+        });
+        match too_wide {
+            Some(found) => Err(Error::Width {
+                name: name.to_string(),
+                expected: out_count,
+                found,
+            }),
+            None => lut,
+        }
+    }
+
     pub fn get_table(&self) -> Vec<Vec<bool>> {
         self.table.clone()
     }
@@ -80,16 +192,40 @@ impl LookupTable {
         }
     }
 
+    /// with `DontCarePolicy::X` don't care rows output the value stored in the table, `try_outputs` returns an error instead
     pub fn outputs(&self) -> Vec<bool> {
         let index = bool_to_u32(self.in_values.clone()) as usize;
         let mut result = Vec::with_capacity(self.out_map.len());
         for i in 0..self.out_map.len() {
-            result.push(self.table[i][index]);
+            result.push(self.value(i, index).unwrap_or(self.table[i][index]));
         }
 
         return result;
     }
 
+    /// returns `Error::DontCare` if an output is a don't care with `DontCarePolicy::X`
+    pub fn try_outputs(&self) -> Result<Vec<bool>, Error> {
+        let index = bool_to_u32(self.in_values.clone()) as usize;
+        (0..self.out_map.len())
+            .map(|i| self.value(i, index))
+            .collect()
+    }
+
+    /// gets the output `out_id` for the row `index` with the don't care policy applied
+    fn value(&self, out_id: usize, index: usize) -> Result<bool, Error> {
+        if !self.dont_care[out_id][index] {
+            return Ok(self.table[out_id][index]);
+        }
+        match self.policy {
+            DontCarePolicy::Zero => Ok(false),
+            DontCarePolicy::One => Ok(true),
+            DontCarePolicy::X => Err(Error::DontCare {
+                name: self.name.clone(),
+                output: self.out_names[out_id].clone(),
+            }),
+        }
+    }
+
     pub fn name(&self) -> String {
         self.name.clone()
     }
@@ -99,26 +235,25 @@ impl LookupTable {
             self.in_values[index.clone()] = value;
             Ok(())
         } else {
-            Err(Error::msg(format!("name {} not found", in_name)))
+            Err(Error::UnknownPort(in_name.to_string()))
         }
     }
 
     pub fn get(&mut self, out_name: &str) -> Result<bool, Error> {
         let index = bool_to_u32(self.in_values.clone()) as usize;
         if let Some(out_id) = self.out_map.get(out_name) {
-            Ok(self.table[out_id.clone()][index])
+            self.value(out_id.clone(), index)
         } else {
-            Err(Error::msg(format!("name {} not found", out_name)))
+            Err(Error::UnknownPort(out_name.to_string()))
         }
     }
 
     pub fn set_id(&mut self, in_id: usize, value: bool) -> Result<(), Error> {
-        if in_id > self.in_values.len() - 1 {
-            Err(Error::msg(format!(
-                "in_id dose not exist max {} input {}",
-                self.in_values.len() - 1,
-                in_id
-            )))
+        if in_id >= self.in_values.len() {
+            Err(Error::Index {
+                index: in_id,
+                len: self.in_values.len(),
+            })
         } else {
             self.in_values[in_id] = value;
             Ok(())
@@ -127,30 +262,137 @@ impl LookupTable {
 
     pub fn get_id(&mut self, out_id: usize) -> Result<bool, Error> {
         let index = bool_to_u32(self.in_values.clone()) as usize;
-        if out_id > self.out_map.len() - 1 {
-            Err(Error::msg(format!(
-                "out_id dose not exist max {} input {}",
-                self.out_map.len() - 1,
-                out_id
-            )))
+        if out_id >= self.out_map.len() {
+            Err(Error::Index {
+                index: out_id,
+                len: self.out_map.len(),
+            })
         } else {
-            Ok(self.table[out_id][index])
+            self.value(out_id, index)
         }
     }
 
-    pub fn in_names(&self) -> Vec<String> {
-        let mut names = Vec::new();
-        for name in self.in_map.keys() {
-            names.push(name.clone());
+    /// marks the rows of the output `out_name` that are not specified, `mask` has an entry per row of the table
+    /// don't cares are free to be 0 or 1 when the table is minimized or compared
+    pub fn set_dont_care(&mut self, out_name: &str, mask: Vec<bool>) -> Result<(), Error> {
+        let out_id = match self.out_map.get(out_name) {
+            Some(&out_id) => out_id,
+            None => return Err(Error::UnknownPort(out_name.to_string())),
+        };
+        if mask.len() != self.table[out_id].len() {
+            return Err(Error::Shape {
+                expected: self.table[out_id].len(),
+                found: mask.len(),
+            });
         }
-        names
+        self.dont_care[out_id] = mask;
+        Ok(())
     }
 
-    pub fn out_names(&self) -> Vec<String> {
-        let mut names = Vec::new();
-        for name in self.out_map.keys() {
-            names.push(name.clone());
+    /// gets the don't care rows of the output `out_name`
+    pub fn dont_care(&self, out_name: &str) -> Result<Vec<bool>, Error> {
+        match self.out_map.get(out_name) {
+            Some(&out_id) => Ok(self.dont_care[out_id].clone()),
+            None => Err(Error::UnknownPort(out_name.to_string())),
         }
-        names
+    }
+
+    /// sets what don't care rows output when the table is simulated, the default is `DontCarePolicy::Zero`
+    pub fn set_policy(&mut self, policy: DontCarePolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> DontCarePolicy {
+        self.policy
+    }
+
+    /// returns true if both tables have the same ports by name and every row that is not a don't care in either table has the same outputs
+    /// the ports can be declared in a different order
+    pub fn equivalent(&self, other: &LookupTable) -> bool {
+        let same = |names: &[String], map: &HashMap<String, usize>| {
+            names.len() == map.len() && names.iter().all(|name| map.contains_key(name))
+        };
+        if !same(&self.in_names, &other.in_map) || !same(&self.out_names, &other.out_map) {
+            return false;
+        }
+
+        let mut values = vec![false; self.in_names.len()];
+        loop {
+            let row = bool_to_u32(values.clone()) as usize;
+            let mut other_values = vec![false; values.len()];
+            for (name, &value) in self.in_names.iter().zip(values.iter()) {
+                other_values[other.in_map[name]] = value;
+            }
+            let other_row = bool_to_u32(other_values) as usize;
+
+            for (i, name) in self.out_names.iter().enumerate() {
+                let j = other.out_map[name];
+                if !self.dont_care[i][row]
+                    && !other.dont_care[j][other_row]
+                    && self.table[i][row] != other.table[j][other_row]
+                {
+                    return false;
+                }
+            }
+            if !update_values(&mut values) {
+                break;
+            }
+        }
+        true
+    }
+
+    /// reads a berkeley pla like the ones espresso reads and writes, cubes can set several outputs and `-` outputs are don't cares
+    /// the ports are named after `.ilb` and `.ob` or `in0`, `out0` ... if they are missing
+    pub fn from_pla(src: &str, name: &str) -> Result<Self, Error> {
+        pla::parse(src, name)
+    }
+
+    /// writes the table as a pla of `.type fd` with a cube for each row that is 1 or a don't care
+    pub fn to_pla(&self) -> String {
+        pla::write(self)
+    }
+
+    /// writes the table as a verilog module with a constant per output that is indexed by the inputs
+    pub fn to_verilog(&self) -> String {
+        verilog::lut_module(self)
+    }
+
+    /// gets a minimal sum of products of the output `out_name`
+    /// tables with up to 8 inputs are minimized exactly with Quine-McCluskey, larger ones with an espresso style heuristic
+    pub fn sop(&self, out_name: &str) -> Result<Expression, Error> {
+        self.minimize(out_name, Form::Sop)
+    }
+
+    /// gets a minimal product of sums of the output `out_name`
+    pub fn pos(&self, out_name: &str) -> Result<Expression, Error> {
+        self.minimize(out_name, Form::Pos)
+    }
+
+    fn minimize(&self, out_name: &str, form: Form) -> Result<Expression, Error> {
+        match self.out_map.get(out_name) {
+            Some(&out_id) => Ok(minimize::expression(
+                &self.in_names,
+                &self.table[out_id],
+                &self.dont_care[out_id],
+                form,
+            )),
+            None => Err(Error::UnknownPort(out_name.to_string())),
+        }
+    }
+
+    /// builds a chip of 2 input `And` and `Or` parts and `Not` parts with the minimized outputs in `form`
+    /// the returned library has the lookup tables of the gates so the chip can be simulated with `Circuit::with_library`
+    pub fn to_gates(&self, form: Form) -> (ChipDef, Library) {
+        minimize::to_gates(self, form)
+    }
+
+    /// gets the names of the inputs in the order they were passed to `new`
+    pub fn in_names(&self) -> Vec<String> {
+        self.in_names.clone()
+    }
+
+    /// gets the names of the outputs in the order they were passed to `new`
+    pub fn out_names(&self) -> Vec<String> {
+        self.out_names.clone()
     }
 }
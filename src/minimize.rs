@@ -0,0 +1,592 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use bool_algebra::bool_to_u32;
+
+use crate::{ChipDef, Component, Library, LookupTable};
+
+/// tables with up to this many inputs are minimized exactly with Quine-McCluskey, larger ones with an espresso style heuristic
+const QM_INPUTS: usize = 8;
+/// the number of branches the exact cover of Quine-McCluskey may try before it keeps the best cover found so far
+const COVER_BUDGET: usize = 100_000;
+/// the number of reduce and expand rounds of the heuristic
+const ESPRESSO_ROUNDS: usize = 8;
+
+/// the shape of a two level `Expression`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    /// sum of products like `a & !b | c`
+    Sop,
+    /// product of sums like `(a | b) & !c`
+    Pos,
+}
+
+/// a minimized output of a `LookupTable`
+/// it prints like `a & !b | c`, which `LookupTable::from_expr` can read back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    form: Form,
+    in_names: Vec<String>,
+    // one literal per input, `Some(false)` is the negated input and `None` an input that is not used
+    terms: Vec<Vec<Option<bool>>>,
+}
+
+impl Expression {
+    pub fn form(&self) -> Form {
+        self.form
+    }
+
+    /// gets the products of a `Form::Sop` or the sums of a `Form::Pos` with one entry per input
+    /// `Some(true)` is the input, `Some(false)` the negated input and `None` means the input is not used
+    pub fn terms(&self) -> Vec<Vec<Option<bool>>> {
+        self.terms.clone()
+    }
+
+    pub fn in_names(&self) -> Vec<String> {
+        self.in_names.clone()
+    }
+
+    /// the number of literals of all terms
+    pub fn literal_count(&self) -> usize {
+        self.terms
+            .iter()
+            .map(|term| term.iter().filter(|l| l.is_some()).count())
+            .sum()
+    }
+
+    /// evaluates the expression with the inputs in the order of `in_names`
+    pub fn eval(&self, inputs: &[bool]) -> bool {
+        let literal = |term: &[Option<bool>], i: usize| term[i].map(|l| l == inputs[i]);
+        match self.form {
+            Form::Sop => self
+                .terms
+                .iter()
+                .any(|term| (0..term.len()).all(|i| literal(term, i).unwrap_or(true))),
+            Form::Pos => self
+                .terms
+                .iter()
+                .all(|term| (0..term.len()).any(|i| literal(term, i).unwrap_or(false))),
+        }
+    }
+
+    /// gets the literals of a term as (input, positive) pairs
+    fn literals(term: &[Option<bool>]) -> impl Iterator<Item = (usize, bool)> + '_ {
+        term.iter()
+            .enumerate()
+            .filter_map(|(i, l)| l.map(|positive| (i, positive)))
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (inner, outer, empty_term, empty) = match self.form {
+            Form::Sop => (" & ", " | ", "1", "0"),
+            Form::Pos => (" | ", " & ", "0", "1"),
+        };
+        if self.terms.is_empty() {
+            return write!(f, "{}", empty);
+        }
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|term| {
+                let literals: Vec<String> = Self::literals(term)
+                    .map(|(i, positive)| match positive {
+                        true => self.in_names[i].clone(),
+                        false => format!("!{}", self.in_names[i]),
+                    })
+                    .collect();
+                match literals.len() {
+                    0 => empty_term.to_string(),
+                    1 => literals[0].clone(),
+                    _ if self.form == Form::Pos && self.terms.len() > 1 => {
+                        format!("({})", literals.join(inner))
+                    }
+                    _ => literals.join(inner),
+                }
+            })
+            .collect();
+        write!(f, "{}", terms.join(outer))
+    }
+}
+
+/// a product of literals over the bits of a row index
+/// the bits in `care` are literals, the others are not used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Cube {
+    pub care: u32,
+    /// the values of the literals, bits outside of `care` are 0
+    pub value: u32,
+}
+
+impl Cube {
+    fn contains_row(&self, row: u32) -> bool {
+        row & self.care == self.value
+    }
+
+    fn contains(&self, other: &Cube) -> bool {
+        other.care & self.care == self.care && other.value & self.care == self.value
+    }
+
+    fn literals(&self) -> u32 {
+        self.care.count_ones()
+    }
+}
+
+/// (terms, literals) of a cover, smaller is better
+fn cost(cover: &[Cube]) -> (usize, u32) {
+    (cover.len(), cover.iter().map(|c| c.literals()).sum())
+}
+
+/// finds a small set of cubes that covers every row in `on` and no row that is neither in `on` nor in `dont_care`
+/// rows are indexed like the table of a `LookupTable` with `n` inputs
+pub(crate) fn minimize(n: usize, on: &[bool], dont_care: &[bool]) -> Vec<Cube> {
+    let mut cover = if n <= QM_INPUTS {
+        quine_mccluskey(n, on, dont_care)
+    } else {
+        espresso(n, on, dont_care)
+    };
+    cover.sort();
+    cover
+}
+
+fn quine_mccluskey(n: usize, on: &[bool], dont_care: &[bool]) -> Vec<Cube> {
+    let full = (1_u64 << n) as u32 - 1;
+    let minterms: Vec<u32> = (0..on.len() as u32).filter(|&r| on[r as usize]).collect();
+
+    // merges cubes that differ in a single literal until no more can be merged, the ones left over are prime
+    let mut current: HashSet<Cube> = (0..on.len() as u32)
+        .filter(|&r| on[r as usize] || dont_care[r as usize])
+        .map(|r| Cube {
+            care: full,
+            value: r,
+        })
+        .collect();
+    let mut primes = Vec::new();
+    while !current.is_empty() {
+        let mut next = HashSet::new();
+        for cube in current.iter() {
+            let mut merged = false;
+            for bit in (0..n).map(|b| 1 << b).filter(|bit| cube.care & bit != 0) {
+                let partner = Cube {
+                    care: cube.care,
+                    value: cube.value ^ bit,
+                };
+                if current.contains(&partner) {
+                    merged = true;
+                    next.insert(Cube {
+                        care: cube.care & !bit,
+                        value: cube.value & !bit,
+                    });
+                }
+            }
+            if !merged && minterms.iter().any(|&r| cube.contains_row(r)) {
+                primes.push(*cube);
+            }
+        }
+        current = next;
+    }
+    primes.sort();
+
+    let covers: Vec<Vec<usize>> = minterms
+        .iter()
+        .map(|&r| {
+            (0..primes.len())
+                .filter(|&p| primes[p].contains_row(r))
+                .collect()
+        })
+        .collect();
+    let mut search = CoverSearch {
+        primes: &primes,
+        minterms: &minterms,
+        covers: &covers,
+        best: greedy_cover(&primes, &minterms),
+        budget: COVER_BUDGET,
+    };
+    search.branch(&mut Vec::new());
+    search.best.iter().map(|&p| primes[p]).collect()
+}
+
+/// picks the prime that covers the most uncovered minterms until all are covered
+fn greedy_cover(primes: &[Cube], minterms: &[u32]) -> Vec<usize> {
+    let mut uncovered: Vec<u32> = minterms.to_vec();
+    let mut chosen = Vec::new();
+    while !uncovered.is_empty() {
+        let best = (0..primes.len())
+            .max_by_key(|&p| {
+                let count = uncovered
+                    .iter()
+                    .filter(|&&r| primes[p].contains_row(r))
+                    .count();
+                (count, u32::MAX - primes[p].literals())
+            })
+            .unwrap();
+        uncovered.retain(|&r| !primes[best].contains_row(r));
+        chosen.push(best);
+    }
+    chosen
+}
+
+/// branch and bound over the primes that cover the minterm with the fewest choices
+struct CoverSearch<'a> {
+    primes: &'a [Cube],
+    minterms: &'a [u32],
+    /// the primes covering each minterm
+    covers: &'a [Vec<usize>],
+    best: Vec<usize>,
+    budget: usize,
+}
+
+impl CoverSearch<'_> {
+    fn cost(&self, chosen: &[usize]) -> (usize, u32) {
+        let cubes: Vec<Cube> = chosen.iter().map(|&p| self.primes[p]).collect();
+        cost(&cubes)
+    }
+
+    fn branch(&mut self, chosen: &mut Vec<usize>) {
+        if self.budget == 0 {
+            return;
+        }
+        self.budget -= 1;
+
+        let uncovered = (0..self.minterms.len())
+            .filter(|&m| !self.covers[m].iter().any(|p| chosen.contains(p)))
+            .min_by_key(|&m| self.covers[m].len());
+        let minterm = match uncovered {
+            Some(minterm) => minterm,
+            None => {
+                if self.cost(chosen) < self.cost(&self.best) {
+                    self.best = chosen.clone();
+                }
+                return;
+            }
+        };
+        if chosen.len() + 1 > self.best.len() {
+            return;
+        }
+        for &p in self.covers[minterm].iter() {
+            chosen.push(p);
+            self.branch(chosen);
+            chosen.pop();
+        }
+    }
+}
+
+fn espresso(n: usize, on: &[bool], dont_care: &[bool]) -> Vec<Cube> {
+    let full = (1_u64 << n) as u32 - 1;
+    let minterms: Vec<u32> = (0..on.len() as u32).filter(|&r| on[r as usize]).collect();
+    let off: Vec<u32> = (0..on.len() as u32)
+        .filter(|&r| !on[r as usize] && !dont_care[r as usize])
+        .collect();
+
+    let mut cover: Vec<Cube> = minterms
+        .iter()
+        .map(|&r| Cube {
+            care: full,
+            value: r,
+        })
+        .collect();
+    expand(&mut cover, &off);
+    irredundant(&mut cover, &minterms);
+
+    for _ in 0..ESPRESSO_ROUNDS {
+        let mut next = cover.clone();
+        reduce(&mut next, &minterms, full);
+        expand(&mut next, &off);
+        irredundant(&mut next, &minterms);
+        if cost(&next) >= cost(&cover) {
+            break;
+        }
+        cover = next;
+    }
+    cover
+}
+
+/// drops literals of each cube as long as it does not cover a row of the off set, then removes the covered cubes
+fn expand(cover: &mut Vec<Cube>, off: &[u32]) {
+    cover.sort_by_key(|c| c.literals());
+    for cube in cover.iter_mut() {
+        for bit in (0..u32::BITS).map(|b| 1 << b) {
+            if cube.care & bit == 0 {
+                continue;
+            }
+            let expanded = Cube {
+                care: cube.care & !bit,
+                value: cube.value & !bit,
+            };
+            if !off.iter().any(|&r| expanded.contains_row(r)) {
+                *cube = expanded;
+            }
+        }
+    }
+
+    let mut kept: Vec<Cube> = Vec::new();
+    for cube in cover.iter() {
+        if !kept.iter().any(|k| k.contains(cube)) {
+            kept.retain(|k| !cube.contains(k));
+            kept.push(*cube);
+        }
+    }
+    *cover = kept;
+}
+
+/// removes cubes whose minterms are all covered by other cubes, the ones with the most literals go first
+fn irredundant(cover: &mut Vec<Cube>, minterms: &[u32]) {
+    let mut counts: Vec<usize> = minterms
+        .iter()
+        .map(|&r| cover.iter().filter(|c| c.contains_row(r)).count())
+        .collect();
+    cover.sort_by_key(|c| u32::MAX - c.literals());
+    let mut kept = Vec::new();
+    for cube in cover.iter() {
+        let covered: Vec<usize> = (0..minterms.len())
+            .filter(|&m| cube.contains_row(minterms[m]))
+            .collect();
+        if covered.iter().all(|&m| counts[m] > 1) {
+            for m in covered {
+                counts[m] -= 1;
+            }
+        } else {
+            kept.push(*cube);
+        }
+    }
+    *cover = kept;
+}
+
+/// shrinks each cube to the smallest cube of the minterms that no other cube covers
+fn reduce(cover: &mut Vec<Cube>, minterms: &[u32], full: u32) {
+    let mut i = 0;
+    while i < cover.len() {
+        let cube = cover[i];
+        let only: Vec<u32> = minterms
+            .iter()
+            .copied()
+            .filter(|&r| cube.contains_row(r))
+            .filter(|&r| (0..cover.len()).all(|j| j == i || !cover[j].contains_row(r)))
+            .collect();
+        if only.is_empty() {
+            cover.remove(i);
+            continue;
+        }
+        let all = only.iter().fold(u32::MAX, |acc, &r| acc & r);
+        let any = only.iter().fold(0, |acc, &r| acc | r);
+        let care = !(all ^ any) & full;
+        cover[i] = Cube {
+            care,
+            value: all & care,
+        };
+        i += 1;
+    }
+}
+
+/// the minimized `Form::Sop` or `Form::Pos` of the output column `table`
+pub(crate) fn expression(
+    in_names: &[String],
+    table: &[bool],
+    dont_care: &[bool],
+    form: Form,
+) -> Expression {
+    let n = in_names.len();
+    // the bit of the row index that each input sets
+    let bits: Vec<u32> = (0..n)
+        .map(|i| bool_to_u32((0..n).map(|j| j == i).collect()))
+        .collect();
+    let literals = |cube: &Cube, negate: bool| -> Vec<Option<bool>> {
+        bits.iter()
+            .map(|&bit| (cube.care & bit != 0).then_some((cube.value & bit != 0) != negate))
+            .collect()
+    };
+
+    let mut terms: Vec<Vec<Option<bool>>> = match form {
+        Form::Sop => minimize(n, table, dont_care)
+            .iter()
+            .map(|cube| literals(cube, false))
+            .collect(),
+        // the sums are the negated products of the negated function
+        Form::Pos => {
+            let off: Vec<bool> = (0..table.len())
+                .map(|r| !table[r] && !dont_care[r])
+                .collect();
+            minimize(n, &off, dont_care)
+                .iter()
+                .map(|cube| literals(cube, true))
+                .collect()
+        }
+    };
+    // terms using the first inputs come first
+    terms.sort_by(|a, b| b.cmp(a));
+    Expression {
+        form,
+        in_names: in_names.to_vec(),
+        terms,
+    }
+}
+
+/// builds a chip of `And`, `Or` and `Not` parts that computes every output of `lut` in `form`, don't cares are whatever the minimized form gives
+/// returns the chip and a library with the gates it uses
+pub(crate) fn to_gates(lut: &LookupTable, form: Form) -> (ChipDef, Library) {
+    let in_names = lut.in_names();
+    let out_names = lut.out_names();
+    let table = lut.get_table();
+    let mut gates = Gates {
+        parts: Vec::new(),
+        library: Library::new(),
+        wires: 0,
+    };
+    let (inner, outer) = match form {
+        Form::Sop => ("And", "Or"),
+        Form::Pos => ("Or", "And"),
+    };
+    for (out_name, column) in out_names.iter().zip(table.iter()) {
+        let dont_care = lut.dont_care(out_name).unwrap();
+        let expr = expression(&in_names, column, &dont_care, form);
+        let terms: Vec<String> = expr
+            .terms
+            .iter()
+            .map(|term| {
+                let literals = Expression::literals(term)
+                    .map(|(i, positive)| match positive {
+                        true => in_names[i].clone(),
+                        false => gates.not(&in_names[i]),
+                    })
+                    .collect();
+                gates.chain(inner, literals, None)
+            })
+            .collect();
+        gates.chain(outer, terms, Some(out_name));
+    }
+
+    let chip_def = ChipDef::new_string(lut.name(), in_names, out_names, gates.parts);
+    (chip_def, gates.library)
+}
+
+/// the parts of a chip made from `And`, `Or` and `Not`
+struct Gates {
+    parts: Vec<Component>,
+    library: Library,
+    /// the number of internal wires so far
+    wires: usize,
+}
+
+impl Gates {
+    fn gate(&mut self, name: &str, inputs: Vec<String>, output: String) {
+        if !self.library.contains(name) {
+            let lut = match name {
+                "Not" => LookupTable::from_fn(vec!["in"], vec!["out"], name, |v| vec![!v[0]]),
+                "And" => {
+                    LookupTable::from_fn(vec!["a", "b"], vec!["out"], name, |v| vec![v[0] && v[1]])
+                }
+                "Or" => {
+                    LookupTable::from_fn(vec!["a", "b"], vec!["out"], name, |v| vec![v[0] || v[1]])
+                }
+                "True" => LookupTable::from_fn(vec![], vec!["out"], name, |_| vec![true]),
+                _ => LookupTable::from_fn(vec![], vec!["out"], name, |_| vec![false]),
+            };
+            self.library.add_lut(lut.unwrap());
+        }
+        let pins = match name {
+            "Not" => vec!["in"],
+            "And" | "Or" => vec!["a", "b"],
+            _ => vec![],
+        };
+        self.parts.push(Component::Def {
+            inputs: pins.iter().map(|p| p.to_string()).zip(inputs).collect(),
+            outputs: vec![("out".to_string(), output)],
+            name: name.to_string(),
+        });
+    }
+
+    fn wire(&mut self) -> String {
+        self.wires += 1;
+        format!("$w{}", self.wires - 1)
+    }
+
+    /// gets the wire of the negated input, every input is negated only once
+    fn not(&mut self, input: &str) -> String {
+        let wire = format!("$not_{}", input);
+        let negated = self
+            .parts
+            .iter()
+            .any(|part| matches!(part, Component::Def { outputs, .. } if outputs[0].1 == wire));
+        if !negated {
+            self.gate("Not", vec![input.to_string()], wire.clone());
+        }
+        wire
+    }
+
+    /// combines `signals` with a chain of 2 input `gate`s and returns the last signal
+    /// an empty chain is the constant that `gate` ignores, `True` for `And` and `False` for `Or`
+    fn chain(&mut self, gate: &str, signals: Vec<String>, output: Option<&String>) -> String {
+        let mut signals = signals.into_iter();
+        let mut acc = match signals.next() {
+            Some(first) => first,
+            None => {
+                let constant = if gate == "And" { "True" } else { "False" };
+                let wire = output.cloned().unwrap_or_else(|| self.wire());
+                self.gate(constant, vec![], wire.clone());
+                return wire;
+            }
+        };
+        let mut signals = signals.peekable();
+        while let Some(signal) = signals.next() {
+            let wire = match (signals.peek(), output) {
+                (None, Some(output)) => output.clone(),
+                _ => self.wire(),
+            };
+            self.gate(gate, vec![acc, signal], wire.clone());
+            acc = wire;
+        }
+        if let Some(output) = output {
+            if acc != *output {
+                self.parts.push(Component::Map {
+                    var_map: vec![(acc.clone(), output.clone())],
+                    name: "assign".to_string(),
+                });
+            }
+        }
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("i{}", i)).collect()
+    }
+
+    fn rows(n: usize, f: impl Fn(u32) -> bool) -> Vec<bool> {
+        (0..1_u32 << n).map(f).collect()
+    }
+
+    #[test]
+    fn dont_care() {
+        // a BCD digit is at least 5, the codes 10 to 15 are not used
+        let weight = |i: usize| bool_to_u32((0..4).map(|j| j == i).collect());
+        let digit = |r: u32| {
+            (0..4)
+                .filter(|&i| r & weight(i) != 0)
+                .map(|i| 1 << i)
+                .sum::<u32>()
+        };
+        let on = rows(4, |r| (5..10).contains(&digit(r)));
+        let dont_care = rows(4, |r| digit(r) >= 10);
+
+        let expr = expression(&names(4), &on, &dont_care, Form::Sop);
+        assert_eq!(expr.to_string(), "i0 & i2 | i1 & i2 | i3");
+        let expr = expression(&names(4), &on, &[false; 16], Form::Sop);
+        assert_eq!(expr.literal_count(), 9);
+    }
+
+    #[test]
+    fn heuristic() {
+        // the parity of 3 of 10 inputs
+        let n = 10;
+        let on = rows(n, |r| (r & 7).count_ones() % 2 == 1);
+        let exact = quine_mccluskey(n, &on, &vec![false; 1 << n]);
+        let heuristic = espresso(n, &on, &vec![false; 1 << n]);
+        assert_eq!(cost(&heuristic), cost(&exact));
+        assert_eq!(cost(&heuristic), (4, 12));
+    }
+}
@@ -0,0 +1,244 @@
+use bool_algebra::{bool_to_u32, update_values};
+
+use crate::blif::{commands, Word};
+use crate::token::error;
+use crate::{Error, LookupTable};
+
+/// the most inputs a table can have, it has 2^n rows
+const MAX_INPUTS: usize = 20;
+
+/// commands of multi valued and symbolic plas
+const UNSUPPORTED: [&str; 7] = [
+    ".mv",
+    ".phase",
+    ".pair",
+    ".symbolic",
+    ".symbolic-output",
+    ".kiss",
+    ".label",
+];
+
+/// which sets the output plane of the cubes describes, set with `.type`
+/// the on set is always given, the off set is given by `r` and the don't care set by `d`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Type {
+    off: bool,
+    dont_care: bool,
+}
+
+/// reads the number after a command like `.i 3`
+fn number(words: &[Word]) -> Result<usize, Error> {
+    match words {
+        [_, n] => n
+            .text
+            .parse()
+            .map_err(|_| n.error(format!("{} is not a number", n.text))),
+        _ => Err(words[0].error(format!("{} takes a number", words[0].text))),
+    }
+}
+
+/// parses a berkeley pla with 1 bit inputs and outputs
+/// inputs and outputs are named after `.ilb` and `.ob` or `in0`, `out0` ...
+/// rows in neither the on set nor the don't care set are 0, for `.type fr` and `fdr` rows that are in no set are don't cares
+pub(crate) fn parse(src: &str, name: &str) -> Result<LookupTable, Error> {
+    let (mut inputs, mut outputs) = (None, None);
+    let (mut in_names, mut out_names) = (None, None);
+    let mut kind = Type {
+        off: false,
+        dont_care: true,
+    };
+    let mut cubes = Vec::new();
+    let mut ended = false;
+
+    for words in commands(src) {
+        let first = &words[0];
+        if ended {
+            return Err(first.error(format!("unexpected {} after .e", first.text)));
+        }
+        match first.text.as_str() {
+            ".i" => {
+                let n = number(&words)?;
+                if n > MAX_INPUTS {
+                    return Err(
+                        words[1].error(format!("a table can have at most {} inputs", MAX_INPUTS))
+                    );
+                }
+                inputs = Some(n);
+            }
+            ".o" => outputs = Some(number(&words)?),
+            ".ilb" => in_names = Some(words),
+            ".ob" => out_names = Some(words),
+            ".type" => {
+                kind = match words.get(1).map(|w| w.text.as_str()) {
+                    Some("f") => Type {
+                        off: false,
+                        dont_care: false,
+                    },
+                    Some("fd") => Type {
+                        off: false,
+                        dont_care: true,
+                    },
+                    Some("fr") => Type {
+                        off: true,
+                        dont_care: false,
+                    },
+                    Some("fdr") => Type {
+                        off: true,
+                        dont_care: true,
+                    },
+                    _ => return Err(first.error("expected f, fd, fr or fdr".to_string())),
+                }
+            }
+            // the number of cubes is only a hint
+            ".p" => {
+                number(&words)?;
+            }
+            ".e" | ".end" => ended = true,
+            command if UNSUPPORTED.contains(&command) => {
+                return Err(first.error(format!("{} is not supported", command)))
+            }
+            command if command.starts_with('.') => {
+                return Err(first.error(format!("unknown command {}", command)))
+            }
+            _ => cubes.push(words),
+        }
+    }
+
+    let count = |count: Option<usize>| -> Result<usize, Error> {
+        match (count, cubes.first()) {
+            (Some(count), _) => Ok(count),
+            (None, Some(cube)) => {
+                Err(cube[0].error("expected .i and .o before the cubes".to_string()))
+            }
+            (None, None) => Err(error(1, 1, "expected .i and .o".to_string())),
+        }
+    };
+    let (n, m) = (count(inputs)?, count(outputs)?);
+    let names = |words: Option<Vec<Word>>, count: usize, prefix: &str| match words {
+        Some(words) if words.len() - 1 != count => Err(words[0].error(format!(
+            "expected {} names but found {}",
+            count,
+            words.len() - 1
+        ))),
+        Some(words) => Ok(words[1..].iter().map(|w| w.text.clone()).collect()),
+        None => Ok((0..count).map(|i| format!("{}{}", prefix, i)).collect()),
+    };
+    let in_names: Vec<String> = names(in_names, n, "in")?;
+    let out_names: Vec<String> = names(out_names, m, "out")?;
+
+    let rows = 2_usize.pow(n as u32);
+    let mut on = vec![vec![false; rows]; m];
+    let mut off = vec![vec![false; rows]; m];
+    let mut dont_care = vec![vec![false; rows]; m];
+    for words in cubes {
+        // the planes can be split by spaces or `|` anywhere
+        let chars: Vec<char> = words
+            .iter()
+            .flat_map(|w| w.text.chars())
+            .filter(|&c| c != '|')
+            .collect();
+        if chars.len() != n + m {
+            return Err(words[0].error(format!(
+                "expected a cube with {} inputs and {} outputs",
+                n, m
+            )));
+        }
+
+        let mut fixed = vec![false; n];
+        let mut free = Vec::new();
+        for (i, &c) in chars[..n].iter().enumerate() {
+            match c {
+                '0' => (),
+                '1' => fixed[i] = true,
+                '-' | '2' => free.push(i),
+                _ => return Err(words[0].error(format!("{:?} is not an input value", c))),
+            }
+        }
+        let mut cube_rows = Vec::with_capacity(1 << free.len());
+        let mut choice = vec![false; free.len()];
+        loop {
+            let mut values = fixed.clone();
+            for (&i, &value) in free.iter().zip(choice.iter()) {
+                values[i] = value;
+            }
+            cube_rows.push(bool_to_u32(values) as usize);
+            if !update_values(&mut choice) {
+                break;
+            }
+        }
+
+        for (j, &c) in chars[n..].iter().enumerate() {
+            let set = match c {
+                '1' | '4' => &mut on[j],
+                '0' | '3' if kind.off => &mut off[j],
+                '-' | '2' if kind.dont_care => &mut dont_care[j],
+                '0' | '3' | '-' | '2' | '~' => continue,
+                _ => return Err(words[0].error(format!("{:?} is not an output value", c))),
+            };
+            for &row in cube_rows.iter() {
+                set[row] = true;
+            }
+        }
+        if (0..m).any(|j| cube_rows.iter().any(|&row| on[j][row] && off[j][row])) {
+            return Err(words[0].error("the cube is in the on set and the off set".to_string()));
+        }
+    }
+
+    let mut lut = LookupTable::new(
+        on.clone(),
+        in_names.iter().map(|s| s.as_str()).collect(),
+        out_names.iter().map(|s| s.as_str()).collect(),
+        name,
+    )?;
+    for j in 0..m {
+        // with an off set the rows that are in no set are not specified
+        let mask = (0..rows)
+            .map(|row| dont_care[j][row] || kind.off && !on[j][row] && !off[j][row])
+            .collect();
+        lut.set_dont_care(&out_names[j], mask)?;
+    }
+    Ok(lut)
+}
+
+/// writes a pla of type `fd` with a cube for every row that is 1 or a don't care in an output
+pub(crate) fn write(lut: &LookupTable) -> String {
+    let in_names = lut.in_names();
+    let out_names = lut.out_names();
+    let table = lut.get_table();
+    let dont_care: Vec<Vec<bool>> = out_names
+        .iter()
+        .map(|out| lut.dont_care(out).unwrap())
+        .collect();
+
+    let mut cubes = Vec::new();
+    let mut values = vec![false; in_names.len()];
+    loop {
+        let row = bool_to_u32(values.clone()) as usize;
+        let output: String = (0..out_names.len())
+            .map(|j| match (dont_care[j][row], table[j][row]) {
+                (true, _) => '-',
+                (false, true) => '1',
+                (false, false) => '0',
+            })
+            .collect();
+        if output.chars().any(|c| c != '0') {
+            let input: String = values.iter().map(|&v| if v { '1' } else { '0' }).collect();
+            cubes.push(format!("{} {}\n", input, output));
+        }
+        if !update_values(&mut values) {
+            break;
+        }
+    }
+
+    let mut pla = format!(".i {}\n.o {}\n", in_names.len(), out_names.len());
+    if !in_names.is_empty() {
+        pla += &format!(".ilb {}\n", in_names.join(" "));
+    }
+    if !out_names.is_empty() {
+        pla += &format!(".ob {}\n", out_names.join(" "));
+    }
+    pla += &format!(".p {}\n", cubes.len());
+    pla += &cubes.concat();
+    pla += ".e\n";
+    pla
+}
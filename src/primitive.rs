@@ -0,0 +1,82 @@
+/// a state element that parts can use by name without adding it to the `Library`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Primitive {
+    /// `out` is the value `in` had in the previous tick, it is `init` before the first tick
+    Dff { init: bool },
+    /// `out` takes the value of `in` when `clk` changes from 0 to 1
+    ClockedDff,
+    /// `out` takes the value of `in` when `clk` changes from 0 to 1 while `load` is 1
+    EnableDff,
+    /// `out` becomes 1 while `s` is 1 and 0 while `r` is 1, `r` wins if both are 1
+    SrLatch,
+    /// `out` follows `in` while `en` is 1 and keeps its value while `en` is 0
+    DLatch,
+}
+
+impl Primitive {
+    /// gets the primitive called `name`
+    /// `DFF` starts with 0 like the nand2tetris DFF and `DFF1` starts with 1
+    /// `DFFC`, `DFFE`, `SRLatch` and `DLatch` have explicit clock or enable pins and start with 0
+    pub fn find(name: &str) -> Option<Self> {
+        match name {
+            "DFF" => Some(Self::Dff { init: false }),
+            "DFF1" => Some(Self::Dff { init: true }),
+            "DFFC" => Some(Self::ClockedDff),
+            "DFFE" => Some(Self::EnableDff),
+            "SRLatch" => Some(Self::SrLatch),
+            "DLatch" => Some(Self::DLatch),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Dff { init: false } => "DFF".to_string(),
+            Self::Dff { init: true } => "DFF1".to_string(),
+            Self::ClockedDff => "DFFC".to_string(),
+            Self::EnableDff => "DFFE".to_string(),
+            Self::SrLatch => "SRLatch".to_string(),
+            Self::DLatch => "DLatch".to_string(),
+        }
+    }
+
+    /// gets the input pins in port order
+    pub fn in_names(&self) -> Vec<String> {
+        let pins: &[&str] = match self {
+            Self::Dff { .. } => &["in"],
+            Self::ClockedDff => &["in", "clk"],
+            Self::EnableDff => &["in", "load", "clk"],
+            Self::SrLatch => &["s", "r"],
+            Self::DLatch => &["in", "en"],
+        };
+        pins.iter().map(|pin| pin.to_string()).collect()
+    }
+
+    /// gets the output pins in port order
+    pub fn out_names(&self) -> Vec<String> {
+        vec!["out".to_string()]
+    }
+
+    /// gets the next value of a primitive with explicit clock or enable pins
+    /// `clk` is the clock when the primitive was last updated, a `Dff` keeps its value because the tick is its clock
+    pub fn update(&self, inputs: &[bool], clk: bool, value: bool) -> bool {
+        match self {
+            Self::Dff { .. } => value,
+            Self::ClockedDff if !clk && inputs[1] => inputs[0],
+            Self::EnableDff if !clk && inputs[2] && inputs[1] => inputs[0],
+            Self::ClockedDff | Self::EnableDff => value,
+            Self::SrLatch => !inputs[1] && (inputs[0] || value),
+            Self::DLatch if inputs[1] => inputs[0],
+            Self::DLatch => value,
+        }
+    }
+
+    /// gets the clock pin of a primitive that is triggered by an edge
+    pub fn clock(&self) -> Option<usize> {
+        match self {
+            Self::ClockedDff => Some(1),
+            Self::EnableDff => Some(2),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ChipDef, Component, DontCarePolicy, Error, LookupTable};
+
+/// the stored form of a `ChipDef`, buses are declared like in `ChipDef::new` e.g. `a[16]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ChipDefRepr {
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    parts: Vec<Component>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    instances: Vec<InstanceRepr>,
+}
+
+/// the user given name of the part at index `part`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct InstanceRepr {
+    part: usize,
+    name: String,
+}
+
+impl From<ChipDef> for ChipDefRepr {
+    fn from(chip_def: ChipDef) -> Self {
+        let port = |name: String| match chip_def.width(&name) {
+            1 => name,
+            width => format!("{}[{}]", name, width),
+        };
+        Self {
+            name: chip_def.name(),
+            inputs: chip_def.inputs().into_iter().map(port).collect(),
+            outputs: chip_def.outputs().into_iter().map(port).collect(),
+            parts: chip_def.parts(),
+            instances: chip_def
+                .user_instances()
+                .into_iter()
+                .map(|(part, name)| InstanceRepr { part, name })
+                .collect(),
+        }
+    }
+}
+
+impl From<ChipDefRepr> for ChipDef {
+    fn from(repr: ChipDefRepr) -> Self {
+        let mut names = vec![None; repr.parts.len()];
+        for instance in repr.instances {
+            if let Some(name) = names.get_mut(instance.part) {
+                *name = Some(instance.name);
+            }
+        }
+        ChipDef::with_instances(
+            repr.name,
+            repr.inputs,
+            repr.outputs,
+            repr.parts.into_iter().zip(names).collect(),
+        )
+    }
+}
+
+/// the stored form of a `LookupTable`
+/// every output is a hex number whose bit `i` is the output for row `i` of the table e.g. `"7"` for a nand
+/// the don't care masks are written the same way and left out if no row is a don't care
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LookupTableRepr {
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    table: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dont_care: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    policy: DontCarePolicy,
+}
+
+fn is_default(policy: &DontCarePolicy) -> bool {
+    *policy == DontCarePolicy::default()
+}
+
+impl From<LookupTable> for LookupTableRepr {
+    fn from(lut: LookupTable) -> Self {
+        let masks: Vec<Vec<bool>> = lut
+            .out_names()
+            .iter()
+            .map(|out| lut.dont_care(out).unwrap())
+            .collect();
+        let dont_care = match masks.iter().flatten().any(|&bit| bit) {
+            true => masks.iter().map(|mask| to_hex(mask)).collect(),
+            false => Vec::new(),
+        };
+        Self {
+            name: lut.name(),
+            inputs: lut.in_names(),
+            outputs: lut.out_names(),
+            table: lut
+                .get_table()
+                .iter()
+                .map(|column| to_hex(column))
+                .collect(),
+            dont_care,
+            policy: lut.policy(),
+        }
+    }
+}
+
+impl TryFrom<LookupTableRepr> for LookupTable {
+    type Error = Error;
+
+    fn try_from(repr: LookupTableRepr) -> Result<Self, Error> {
+        let rows = 2_usize.pow(repr.inputs.len() as u32);
+        let table = repr
+            .table
+            .iter()
+            .map(|hex| from_hex(hex, rows))
+            .collect::<Result<_, _>>()?;
+        let mut lut = LookupTable::new(
+            table,
+            repr.inputs.iter().map(|s| s.as_str()).collect(),
+            repr.outputs.iter().map(|s| s.as_str()).collect(),
+            &repr.name,
+        )?;
+        if !repr.dont_care.is_empty() {
+            if repr.dont_care.len() != repr.outputs.len() {
+                return Err(Error::Shape {
+                    expected: repr.outputs.len(),
+                    found: repr.dont_care.len(),
+                });
+            }
+            for (out, hex) in repr.outputs.iter().zip(repr.dont_care.iter()) {
+                lut.set_dont_care(out, from_hex(hex, rows)?)?;
+            }
+        }
+        lut.set_policy(repr.policy);
+        Ok(lut)
+    }
+}
+
+/// writes the bits with the last bit as the most significant one
+fn to_hex(bits: &[bool]) -> String {
+    bits.chunks(4)
+        .rev()
+        .map(|nibble| {
+            let value = nibble
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, &bit)| acc | ((bit as u32) << i));
+            std::char::from_digit(value, 16).unwrap()
+        })
+        .collect()
+}
+
+/// reads `rows` bits written by `to_hex`
+fn from_hex(hex: &str, rows: usize) -> Result<Vec<bool>, Error> {
+    let digits = rows.div_ceil(4);
+    let chars: Vec<char> = hex.chars().collect();
+    if chars.len() != digits {
+        return Err(Error::Shape {
+            expected: digits,
+            found: chars.len(),
+        });
+    }
+
+    let mut bits = Vec::with_capacity(digits * 4);
+    for (i, c) in chars.into_iter().enumerate().rev() {
+        let value = c.to_digit(16).ok_or_else(|| Error::Parse {
+            line: 1,
+            column: i + 1,
+            msg: format!("{:?} is not a hex digit", c),
+        })?;
+        bits.extend((0..4).map(|i| value >> i & 1 == 1));
+    }
+    if bits[rows..].iter().any(|&bit| bit) {
+        return Err(Error::Shape {
+            expected: rows,
+            found: bits.len(),
+        });
+    }
+    bits.truncate(rows);
+    Ok(bits)
+}
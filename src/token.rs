@@ -0,0 +1,233 @@
+use std::fmt;
+
+use crate::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Tok {
+    Ident(String),
+    Number(usize),
+    /// one of the symbols passed to `tokenize`
+    Symbol(&'static str),
+    Eof,
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::Ident(name) => write!(f, "{}", name),
+            Tok::Number(n) => write!(f, "{}", n),
+            Tok::Symbol(s) => write!(f, "{}", s),
+            Tok::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Token {
+    pub tok: Tok,
+    /// position of the first char, starting at 1
+    pub line: usize,
+    pub column: usize,
+}
+
+pub(crate) fn error(line: usize, column: usize, msg: String) -> Error {
+    Error::Parse { line, column, msg }
+}
+
+/// splits `src` in to tokens and drops whitespace and `//`, `/* */` comments
+/// `symbols` are tried in order, so longer symbols have to come first
+/// names start with a letter or `_` and can contain digits, `_` and `$`
+pub(crate) fn tokenize(src: &str, symbols: &[&'static str]) -> Result<Vec<Token>, Error> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let (mut i, mut line, mut column) = (0, 1, 1);
+
+    // moves forward by one char and keeps track of the position
+    let advance = |i: &mut usize, line: &mut usize, column: &mut usize| {
+        if chars[*i] == '\n' {
+            *line += 1;
+            *column = 1;
+        } else {
+            *column += 1;
+        }
+        *i += 1;
+    };
+    let starts_with = |i: usize, s: &str| {
+        s.chars()
+            .enumerate()
+            .all(|(j, c)| chars.get(i + j) == Some(&c))
+    };
+
+    while i < chars.len() {
+        let (start_line, start_column) = (line, column);
+        let c = chars[i];
+        let mut push = |tok| {
+            tokens.push(Token {
+                tok,
+                line: start_line,
+                column: start_column,
+            })
+        };
+
+        if c.is_whitespace() {
+            advance(&mut i, &mut line, &mut column);
+        } else if starts_with(i, "//") {
+            while i < chars.len() && chars[i] != '\n' {
+                advance(&mut i, &mut line, &mut column);
+            }
+        } else if starts_with(i, "/*") {
+            advance(&mut i, &mut line, &mut column);
+            advance(&mut i, &mut line, &mut column);
+            loop {
+                if i + 1 >= chars.len() {
+                    return Err(error(
+                        start_line,
+                        start_column,
+                        "unterminated comment".to_string(),
+                    ));
+                }
+                if starts_with(i, "*/") {
+                    advance(&mut i, &mut line, &mut column);
+                    advance(&mut i, &mut line, &mut column);
+                    break;
+                }
+                advance(&mut i, &mut line, &mut column);
+            }
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                name.push(chars[i]);
+                advance(&mut i, &mut line, &mut column);
+            }
+            push(Tok::Ident(name));
+        } else if c.is_ascii_digit() {
+            let mut number = String::new();
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                number.push(chars[i]);
+                advance(&mut i, &mut line, &mut column);
+            }
+            let number = number.parse().map_err(|_| {
+                error(
+                    start_line,
+                    start_column,
+                    format!("number {} is too large", number),
+                )
+            })?;
+            push(Tok::Number(number));
+        } else if let Some(symbol) = symbols.iter().find(|s| starts_with(i, s)) {
+            for _ in 0..symbol.chars().count() {
+                advance(&mut i, &mut line, &mut column);
+            }
+            push(Tok::Symbol(symbol));
+        } else {
+            return Err(error(line, column, format!("unexpected character {:?}", c)));
+        }
+    }
+
+    tokens.push(Token {
+        tok: Tok::Eof,
+        line,
+        column,
+    });
+    Ok(tokens)
+}
+
+/// steps through the tokens of a source, the last token is always `Tok::Eof`
+pub(crate) struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    pub fn new(src: &str, symbols: &[&'static str]) -> Result<Self, Error> {
+        Ok(Self {
+            tokens: tokenize(src, symbols)?,
+            pos: 0,
+        })
+    }
+
+    pub fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    pub fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.tok != Tok::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.peek().tok == Tok::Eof
+    }
+
+    /// an error at the next token
+    pub fn error(&self, msg: String) -> Error {
+        error(self.peek().line, self.peek().column, msg)
+    }
+
+    pub fn unexpected(&self, expected: &str) -> Error {
+        self.error(format!(
+            "expected {} but found {}",
+            expected,
+            self.peek().tok
+        ))
+    }
+
+    pub fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek().tok, Tok::Symbol(s) if s == symbol)
+    }
+
+    pub fn symbol(&mut self, symbol: &str) -> Result<(), Error> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", symbol)))
+        }
+    }
+
+    /// consumes `symbol` if it is next
+    pub fn eat(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    pub fn ident(&mut self) -> Result<String, Error> {
+        match self.peek().tok.clone() {
+            Tok::Ident(name) => {
+                self.next();
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().tok, Tok::Ident(name) if name == keyword)
+    }
+
+    pub fn keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        if self.is_keyword(keyword) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    pub fn number(&mut self) -> Result<usize, Error> {
+        match self.peek().tok {
+            Tok::Number(n) => {
+                self.next();
+                Ok(n)
+            }
+            _ => Err(self.unexpected("a number")),
+        }
+    }
+}
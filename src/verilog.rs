@@ -0,0 +1,867 @@
+use std::collections::{HashMap, HashSet};
+
+use bool_algebra::{bool_to_u32, update_values};
+
+use crate::chip_def::split_slice;
+use crate::elaborate::{pin_names, resolve, Callee, Resolved};
+use crate::primitive::Primitive;
+use crate::token::{error, Parser, Tok};
+use crate::{ChipDef, Component, Diagnostic, Error, Library, LookupTable};
+
+/// a module parsed from structural verilog
+#[derive(Debug, Clone, PartialEq)]
+struct Module {
+    name: String,
+    /// port names in the order of the module header
+    ports: Vec<String>,
+    /// ports with the width appended for buses e.g. `a[16]`
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    /// a gate primitive, the first terminal is the output
+    Gate {
+        lut: LookupTable,
+        instance: Option<String>,
+        terminals: Vec<String>,
+    },
+    Instance {
+        module: String,
+        instance: String,
+        pins: Pins,
+        pos: (usize, usize),
+    },
+    /// `assign a = b;`
+    Assign(String, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pins {
+    /// `.pin(wire)`, unconnected pins are left out
+    Named(Vec<(String, String)>),
+    Positional(Vec<String>),
+}
+
+/// parses every module of the structural verilog `src` and adds them to `library`
+/// the lookup tables of the gate primitives are added as well
+pub(crate) fn add_verilog(library: &mut Library, src: &str) -> Result<(), Error> {
+    let mut parser = Parser::new(
+        src,
+        &[
+            "(", ")", "[", "]", "{", "}", ":", ";", ",", ".", "=", "#", "'",
+        ],
+    )?;
+    let mut modules = Vec::new();
+    while !parser.at_end() {
+        modules.push(module(&mut parser)?);
+    }
+
+    // (port order, inputs, outputs) of a module or a part of the library
+    let ports =
+        |library: &Library, name: &str| -> Option<(Vec<String>, Vec<String>, Vec<String>)> {
+            let plain = |decl: &String| decl.split('[').next().unwrap_or(decl).to_string();
+            if let Some(module) = modules.iter().find(|module| module.name == name) {
+                return Some((
+                    module.ports.clone(),
+                    module.inputs.iter().map(plain).collect(),
+                    module.outputs.iter().map(plain).collect(),
+                ));
+            }
+            pin_names(library, name).map(|(inputs, outputs)| {
+                let order = inputs.iter().chain(outputs.iter()).cloned().collect();
+                (order, inputs, outputs)
+            })
+        };
+
+    let mut chip_defs = Vec::new();
+    let mut luts = Vec::new();
+    for module in modules.iter() {
+        let mut parts = Vec::new();
+        for item in module.items.iter() {
+            parts.push(match item {
+                Item::Gate {
+                    lut,
+                    instance,
+                    terminals,
+                } => {
+                    luts.push(lut.clone());
+                    (
+                        Component::Def {
+                            inputs: lut
+                                .in_names()
+                                .into_iter()
+                                .zip(terminals[1..].to_vec())
+                                .collect(),
+                            outputs: vec![(lut.out_names()[0].clone(), terminals[0].clone())],
+                            name: lut.name(),
+                        },
+                        instance.clone(),
+                    )
+                }
+                Item::Instance {
+                    module: name,
+                    instance,
+                    pins,
+                    pos,
+                } => {
+                    let ports = ports(library, name);
+                    let pins = match pins {
+                        Pins::Named(pins) => pins.clone(),
+                        Pins::Positional(wires) => match &ports {
+                            Some((order, _, _)) if order.len() == wires.len() => {
+                                order.iter().cloned().zip(wires.iter().cloned()).collect()
+                            }
+                            Some((order, _, _)) => {
+                                return Err(error(
+                                    pos.0,
+                                    pos.1,
+                                    format!(
+                                        "{} has {} ports but {} are connected",
+                                        name,
+                                        order.len(),
+                                        wires.len()
+                                    ),
+                                ))
+                            }
+                            None => {
+                                return Err(error(
+                                    pos.0,
+                                    pos.1,
+                                    format!(
+                                        "unknown module {} can only be connected by name",
+                                        name
+                                    ),
+                                ))
+                            }
+                        },
+                    };
+                    let (inputs, outputs) = pins.into_iter().partition(|(pin, _)| match &ports {
+                        Some((_, inputs, _)) => inputs.contains(pin),
+                        None => true,
+                    });
+                    (
+                        Component::Def {
+                            inputs,
+                            outputs,
+                            name: name.clone(),
+                        },
+                        Some(instance.clone()),
+                    )
+                }
+                Item::Assign(lhs, rhs) => (
+                    Component::Map {
+                        var_map: vec![(rhs.clone(), lhs.clone())],
+                        name: "assign".to_string(),
+                    },
+                    None,
+                ),
+            });
+        }
+
+        chip_defs.push(ChipDef::with_instances(
+            module.name.clone(),
+            module.inputs.clone(),
+            module.outputs.clone(),
+            parts,
+        ));
+    }
+
+    for lut in luts {
+        library.add_lut(lut);
+    }
+    for chip_def in chip_defs {
+        library.add_chip(chip_def);
+    }
+    Ok(())
+}
+
+/// gets the lookup table of a gate primitive like `and` with `inputs` inputs
+/// gates are called after the primitive and the number of inputs e.g. `$and2`, `$and3`, `not` and `buf` are `$not` and `$buf`
+fn gate(kind: &str, inputs: usize) -> Option<LookupTable> {
+    let function: fn(&[bool]) -> bool = match kind {
+        "and" => |v| v.iter().all(|&v| v),
+        "or" => |v| v.iter().any(|&v| v),
+        "nand" => |v| !v.iter().all(|&v| v),
+        "nor" => |v| !v.iter().any(|&v| v),
+        "xor" => |v| v.iter().filter(|&&v| v).count() % 2 == 1,
+        "xnor" => |v| v.iter().filter(|&&v| v).count() % 2 == 0,
+        "not" => |v| !v[0],
+        "buf" => |v| v[0],
+        _ => return None,
+    };
+
+    // the `$` keeps the gates from replacing lookup tables of the library
+    let mut name = format!("${}", kind);
+    let in_names: Vec<String> = if kind == "not" || kind == "buf" {
+        vec!["in".to_string()]
+    } else {
+        name = format!("{}{}", name, inputs);
+        (0..inputs)
+            .map(|i| ((b'a' + i as u8) as char).to_string())
+            .collect()
+    };
+
+    let mut values = vec![false; in_names.len()];
+    let mut table = Vec::with_capacity(2_usize.pow(values.len() as u32));
+    loop {
+        table.push(function(&values));
+        if !update_values(&mut values) {
+            break;
+        }
+    }
+
+    LookupTable::new(
+        vec![table],
+        in_names.iter().map(|s| s.as_str()).collect(),
+        vec!["out"],
+        &name,
+    )
+    .ok()
+}
+
+/// `module name (ports); items endmodule`
+fn module(p: &mut Parser) -> Result<Module, Error> {
+    p.keyword("module")?;
+    let name = p.ident()?;
+    let mut module = Module {
+        name,
+        ports: Vec::new(),
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        items: Vec::new(),
+    };
+    // (msb, lsb) of the buses
+    let mut ranges: HashMap<String, (usize, usize)> = HashMap::new();
+
+    if p.eat("(") && !p.eat(")") {
+        if p.is_keyword("input") || p.is_keyword("output") {
+            // ansi style `input [3:0] a, b, output y`
+            let (mut direction, mut range) = (String::new(), None);
+            loop {
+                if p.is_keyword("input") || p.is_keyword("output") || p.is_keyword("inout") {
+                    direction = p.ident()?;
+                    range = declaration(p, &direction, &mut module, &mut ranges)?;
+                } else {
+                    let port = p.ident()?;
+                    declare(&direction, &port, range, &mut module, &mut ranges);
+                }
+                if !p.eat(",") {
+                    break;
+                }
+            }
+        } else {
+            loop {
+                module.ports.push(p.ident()?);
+                if !p.eat(",") {
+                    break;
+                }
+            }
+        }
+        p.symbol(")")?;
+    }
+    p.symbol(";")?;
+
+    loop {
+        let token = p.peek().clone();
+        let keyword = match &token.tok {
+            Tok::Ident(keyword) => keyword.clone(),
+            _ => return Err(p.unexpected("endmodule")),
+        };
+        match keyword.as_str() {
+            "endmodule" => {
+                p.next();
+                break;
+            }
+            "input" | "output" | "inout" | "wire" => {
+                p.next();
+                let range = declaration(p, &keyword, &mut module, &mut ranges)?;
+                while p.eat(",") {
+                    let port = p.ident()?;
+                    declare(&keyword, &port, range, &mut module, &mut ranges);
+                }
+                p.symbol(";")?;
+            }
+            "assign" => {
+                p.next();
+                loop {
+                    let lhs = signal(p, &ranges)?;
+                    p.symbol("=")?;
+                    let rhs = signal(p, &ranges)?;
+                    module.items.push(Item::Assign(lhs, rhs));
+                    if !p.eat(",") {
+                        break;
+                    }
+                }
+                p.symbol(";")?;
+            }
+            "and" | "or" | "nand" | "nor" | "xor" | "xnor" | "not" | "buf" => {
+                p.next();
+                loop {
+                    let instance = match p.peek().tok {
+                        Tok::Ident(_) => Some(p.ident()?),
+                        _ => None,
+                    };
+                    let pos = p.peek().clone();
+                    p.symbol("(")?;
+                    let mut terminals = vec![signal(p, &ranges)?];
+                    while p.eat(",") {
+                        terminals.push(signal(p, &ranges)?);
+                    }
+                    p.symbol(")")?;
+
+                    let inputs = terminals.len() - 1;
+                    let single = keyword == "not" || keyword == "buf";
+                    if inputs == 0 || (single && inputs != 1) {
+                        return Err(error(
+                            pos.line,
+                            pos.column,
+                            format!(
+                                "{} needs one output and {} inputs",
+                                keyword,
+                                if single { "one" } else { "some" }
+                            ),
+                        ));
+                    }
+                    if inputs > 16 {
+                        return Err(error(
+                            pos.line,
+                            pos.column,
+                            "gates with more than 16 inputs are not supported".to_string(),
+                        ));
+                    }
+                    module.items.push(Item::Gate {
+                        lut: gate(&keyword, inputs).unwrap(),
+                        instance,
+                        terminals,
+                    });
+                    if !p.eat(",") {
+                        break;
+                    }
+                }
+                p.symbol(";")?;
+            }
+            "reg" | "always" | "initial" | "parameter" | "localparam" | "function" | "task"
+            | "generate" | "supply0" | "supply1" => {
+                return Err(p.error(format!(
+                    "{} is not supported, only structural verilog can be imported",
+                    keyword
+                )))
+            }
+            _ => {
+                p.next();
+                if p.is_symbol("#") {
+                    return Err(p.error("parameters are not supported".to_string()));
+                }
+                loop {
+                    let pos = (p.peek().line, p.peek().column);
+                    let instance = p.ident()?;
+                    p.symbol("(")?;
+                    let pins = connections(p, &ranges)?;
+                    p.symbol(")")?;
+                    module.items.push(Item::Instance {
+                        module: keyword.clone(),
+                        instance,
+                        pins,
+                        pos,
+                    });
+                    if !p.eat(",") {
+                        break;
+                    }
+                }
+                p.symbol(";")?;
+            }
+        }
+    }
+
+    for port in module.ports.iter() {
+        let declared = |decls: &Vec<String>| {
+            decls
+                .iter()
+                .any(|decl| decl.split('[').next() == Some(port.as_str()))
+        };
+        if !declared(&module.inputs) && !declared(&module.outputs) {
+            return Err(p.error(format!(
+                "port {} of module {} is not declared as input or output",
+                port, module.name
+            )));
+        }
+    }
+    Ok(module)
+}
+
+/// `[wire] [msb:lsb] name` after `input`, `output` or `wire`, returns the range for the following names
+fn declaration(
+    p: &mut Parser,
+    kind: &str,
+    module: &mut Module,
+    ranges: &mut HashMap<String, (usize, usize)>,
+) -> Result<Option<(usize, usize)>, Error> {
+    if kind == "inout" {
+        return Err(p.error("inout ports are not supported".to_string()));
+    }
+    if p.is_keyword("wire") {
+        p.next();
+    }
+    if p.is_keyword("reg") {
+        return Err(
+            p.error("reg is not supported, only structural verilog can be imported".to_string())
+        );
+    }
+
+    let range = if p.eat("[") {
+        let token = p.peek().clone();
+        let msb = p.number()?;
+        p.symbol(":")?;
+        let lsb = p.number()?;
+        p.symbol("]")?;
+        if msb < lsb {
+            return Err(error(
+                token.line,
+                token.column,
+                "only ranges like [msb:lsb] with msb >= lsb are supported".to_string(),
+            ));
+        }
+        Some((msb, lsb))
+    } else {
+        None
+    };
+
+    let name = p.ident()?;
+    declare(kind, &name, range, module, ranges);
+    Ok(range)
+}
+
+/// adds a port to the module and remembers the range of buses
+fn declare(
+    kind: &str,
+    name: &str,
+    range: Option<(usize, usize)>,
+    module: &mut Module,
+    ranges: &mut HashMap<String, (usize, usize)>,
+) {
+    if let Some(range) = range {
+        ranges.insert(name.to_string(), range);
+    }
+    let decl = match range {
+        Some((msb, lsb)) if msb != lsb => format!("{}[{}]", name, msb - lsb + 1),
+        _ => name.to_string(),
+    };
+
+    let ports = match kind {
+        "input" => &mut module.inputs,
+        "output" => &mut module.outputs,
+        _ => return,
+    };
+    ports.push(decl);
+    if !module.ports.iter().any(|port| port == name) {
+        module.ports.push(name.to_string());
+    }
+}
+
+/// `.pin(wire), .other()` or `wire, wire`
+fn connections(p: &mut Parser, ranges: &HashMap<String, (usize, usize)>) -> Result<Pins, Error> {
+    if p.is_symbol(")") {
+        return Ok(Pins::Positional(Vec::new()));
+    }
+
+    if !p.is_symbol(".") {
+        let mut wires = vec![signal(p, ranges)?];
+        while p.eat(",") {
+            wires.push(signal(p, ranges)?);
+        }
+        return Ok(Pins::Positional(wires));
+    }
+
+    let mut pins = Vec::new();
+    loop {
+        p.symbol(".")?;
+        let pin = p.ident()?;
+        p.symbol("(")?;
+        if !p.is_symbol(")") {
+            pins.push((pin, signal(p, ranges)?));
+        }
+        p.symbol(")")?;
+        if !p.eat(",") {
+            break;
+        }
+    }
+    Ok(Pins::Named(pins))
+}
+
+/// `a`, `a[3]` or `a[7:4]` turned in to the syntax of `ChipDef` with bit 0 as the lsb of the declared range
+fn signal(p: &mut Parser, ranges: &HashMap<String, (usize, usize)>) -> Result<String, Error> {
+    match p.peek().tok {
+        Tok::Number(_) | Tok::Symbol("'") => {
+            return Err(p.error("constants are not supported".to_string()))
+        }
+        Tok::Symbol("{") => return Err(p.error("concatenations are not supported".to_string())),
+        _ => (),
+    }
+
+    let name = p.ident()?;
+    if !p.eat("[") {
+        return Ok(name);
+    }
+    let token = p.peek().clone();
+    let msb = p.number()?;
+    let lsb = if p.eat(":") { p.number()? } else { msb };
+    p.symbol("]")?;
+
+    let offset = ranges.get(&name).map(|&(_, lsb)| lsb).unwrap_or(0);
+    if msb < lsb || lsb < offset {
+        return Err(error(
+            token.line,
+            token.column,
+            format!("invalid bit select of {}", name),
+        ));
+    }
+    if msb == lsb {
+        Ok(format!("{}[{}]", name, msb - offset))
+    } else {
+        Ok(format!("{}[{}..{}]", name, lsb - offset, msb - offset))
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "always",
+    "and",
+    "assign",
+    "begin",
+    "buf",
+    "case",
+    "default",
+    "else",
+    "end",
+    "endcase",
+    "endmodule",
+    "for",
+    "if",
+    "initial",
+    "inout",
+    "input",
+    "module",
+    "nand",
+    "nor",
+    "not",
+    "or",
+    "output",
+    "reg",
+    "wire",
+    "xnor",
+    "xor",
+];
+
+/// writes `name` as a verilog identifier, names like `adder.carry` are escaped
+pub(crate) fn ident(name: &str) -> String {
+    let simple = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if simple && !KEYWORDS.contains(&name) {
+        name.to_string()
+    } else {
+        format!("\\{} ", name)
+    }
+}
+
+/// writes the header of a module, `ports` are (direction, name, width)
+pub(crate) fn header(name: &str, ports: &[(&str, String, usize)]) -> String {
+    let ports: Vec<String> = ports
+        .iter()
+        .map(|(direction, port, width)| match width {
+            1 => format!("    {} {}", direction, ident(port)),
+            width => format!("    {} [{}:0] {}", direction, width - 1, ident(port)),
+        })
+        .collect();
+    format!("module {} (\n{}\n);\n", ident(name), ports.join(",\n"))
+}
+
+/// writes a module that looks up every output in a constant with one bit per row of the table
+pub(crate) fn lut_module(lut: &LookupTable) -> String {
+    let in_names = lut.in_names();
+    let ports: Vec<(&str, String, usize)> = in_names
+        .iter()
+        .map(|name| ("input", name.clone(), 1))
+        .chain(lut.out_names().into_iter().map(|name| ("output", name, 1)))
+        .collect();
+    let mut module = header(&lut.name(), &ports);
+
+    // the row of the table is the value of the inputs, most significant input first
+    let mut order: Vec<usize> = (0..in_names.len()).collect();
+    order.sort_by_key(|&i| {
+        let mut values = vec![false; in_names.len()];
+        values[i] = true;
+        std::cmp::Reverse(bool_to_u32(values))
+    });
+    let index: Vec<String> = order.iter().map(|&i| ident(&in_names[i])).collect();
+    let index = match index.len() {
+        1 => index[0].clone(),
+        _ => format!("{{{}}}", index.join(", ")),
+    };
+
+    for (column, output) in lut.get_table().iter().zip(lut.out_names()) {
+        let bits: String = column
+            .iter()
+            .rev()
+            .map(|&bit| if bit { '1' } else { '0' })
+            .collect();
+        if in_names.is_empty() {
+            module += &format!("    assign {} = 1'b{};\n", ident(&output), bits);
+        } else {
+            let table = ident(&format!("{}_table", output));
+            module += &format!(
+                "    wire [{}:0] {} = {}'b{};\n    assign {} = {}[{}];\n",
+                column.len() - 1,
+                table,
+                column.len(),
+                bits,
+                ident(&output),
+                table,
+                index
+            );
+        }
+    }
+
+    module + "endmodule\n"
+}
+
+/// writes the statements that update the register `reg` of a primitive with explicit clock or enable pins
+/// `inputs` are the expressions connected to the input pins, returns `None` for a `DFF` whose clock is the tick
+pub(crate) fn storage(primitive: &Primitive, reg: &str, inputs: &[String]) -> Option<String> {
+    let update = match primitive {
+        Primitive::Dff { .. } => return None,
+        Primitive::ClockedDff => {
+            format!("always @(posedge {}) {} <= {};", inputs[1], reg, inputs[0])
+        }
+        Primitive::EnableDff => format!(
+            "always @(posedge {}) if ({}) {} <= {};",
+            inputs[2], inputs[1], reg, inputs[0]
+        ),
+        // the latches are written as incomplete assignments
+        Primitive::SrLatch => format!(
+            "always @(*) if ({}) {} = 1'b0; else if ({}) {} = 1'b1;",
+            inputs[1], reg, inputs[0], reg
+        ),
+        Primitive::DLatch => format!("always @(*) if ({}) {} = {};", inputs[1], reg, inputs[0]),
+    };
+    Some(format!("    initial {} = 1'b0;\n    {}\n", reg, update))
+}
+
+/// writes a module for a primitive with explicit clock or enable pins, its state is the register `state`
+fn primitive_module(primitive: &Primitive) -> Result<String, Error> {
+    let in_names = primitive.in_names();
+    let inputs: Vec<String> = in_names.iter().map(|name| ident(name)).collect();
+    let update = storage(primitive, "state", &inputs).ok_or_else(|| Error::Unsupported {
+        part: primitive.name(),
+        msg: "the clock of a DFF is the tick and can not be written as verilog".to_string(),
+    })?;
+    let ports: Vec<(&str, String, usize)> = in_names
+        .into_iter()
+        .map(|name| ("input", name, 1))
+        .chain(
+            primitive
+                .out_names()
+                .into_iter()
+                .map(|name| ("output", name, 1)),
+        )
+        .collect();
+    Ok(header(&primitive.name(), &ports)
+        + "    reg state;\n"
+        + &update
+        + &format!("    assign {} = state;\n", ident(&primitive.out_names()[0]))
+        + "endmodule\n")
+}
+
+/// writes the modules of `chip_def` and of every chip and lookup table used by it, `chip_def` comes first
+pub(crate) fn chip_modules(chip_def: &ChipDef, library: &Library) -> Result<String, Error> {
+    let errors: Vec<Diagnostic> = chip_def
+        .check(library)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.is_error())
+        .collect();
+    if !errors.is_empty() {
+        return Err(Error::Invalid(errors));
+    }
+
+    let mut modules = Vec::new();
+    let mut written = HashSet::new();
+    let mut queue = vec![Callee::Chip(chip_def)];
+    while let Some(callee) = queue.pop() {
+        match callee {
+            Callee::Lut(lut) => {
+                if written.insert(lut.name()) {
+                    modules.push(lut_module(lut));
+                }
+            }
+            Callee::Prim(primitive) => {
+                if written.insert(primitive.name()) {
+                    modules.push(primitive_module(&primitive)?);
+                }
+            }
+            Callee::Chip(chip_def) => {
+                if written.insert(chip_def.name()) {
+                    let resolved = resolve(chip_def, library)?;
+                    modules.push(chip_module(chip_def, &resolved));
+                    for (_, name, _, _) in resolved.parts.iter().rev() {
+                        if let Some(callee) = Callee::find(library, name) {
+                            queue.push(callee);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(modules.join("\n"))
+}
+
+/// writes a checked chip as a module with one instance per part
+fn chip_module(chip_def: &ChipDef, resolved: &Resolved) -> String {
+    let Resolved {
+        widths,
+        parts,
+        maps,
+        ..
+    } = resolved;
+    let width = |name: &str| widths.get(name).copied().unwrap_or(1);
+    let ports: Vec<(&str, String, usize)> = chip_def
+        .inputs()
+        .into_iter()
+        .map(|port| ("input", port))
+        .chain(chip_def.outputs().into_iter().map(|port| ("output", port)))
+        .map(|(direction, port)| {
+            let width = width(&port);
+            (direction, port, width)
+        })
+        .collect();
+    let base = |signal: &str| split_slice(signal).unwrap().0;
+
+    // `a`, `a[3]` or `a[0..7]` of a signal that is `width` bits wide as a verilog expression
+    let select = |name: &str, width: usize, range: Option<(usize, usize)>| match range {
+        _ if width == 1 => ident(name),
+        None => ident(name),
+        Some((start, end)) if start == end => format!("{}[{}]", ident(name), start),
+        Some((start, end)) => format!("{}[{}:{}]", ident(name), end, start),
+    };
+    let expr = |signal: &str| {
+        let (name, range) = split_slice(signal).unwrap();
+        select(&name, width(&name), range)
+    };
+    // the single bits of a signal, least significant bit first
+    let bits = |signal: &str| -> Vec<String> {
+        let (name, range) = split_slice(signal).unwrap();
+        let (start, end) = range.unwrap_or((0, width(&name) - 1));
+        (start..=end)
+            .map(|i| select(&name, width(&name), Some((i, i))))
+            .collect()
+    };
+
+    // (name, width) of the internal signals in the order they are first used
+    let mut wires: Vec<(String, usize)> = Vec::new();
+    let signals = parts
+        .iter()
+        .flat_map(|(_, _, _, pins)| pins.iter().map(|(_, wire)| wire))
+        .chain(maps.iter().flat_map(|(_, (a, b))| [a, b]));
+    for signal in signals {
+        let name = base(signal);
+        if !ports.iter().any(|(_, port, _)| port == &name)
+            && !wires.iter().any(|(wire, _)| wire == &name)
+        {
+            let width = width(&name);
+            wires.push((name, width));
+        }
+    }
+
+    let mut instances = String::new();
+    let mut assigns = String::new();
+    for (instance, name, callee, pins) in parts.iter() {
+        // the pins in the order they are first connected
+        let mut pin_names: Vec<String> = Vec::new();
+        for (pin, _) in pins.iter() {
+            let pin = base(pin);
+            if !pin_names.contains(&pin) {
+                pin_names.push(pin);
+            }
+        }
+
+        let mut connections = Vec::new();
+        for pin in pin_names {
+            let pin_width = callee.width(&pin).unwrap_or(1);
+            // (pin range, wire) of the connections to this pin
+            let entries: Vec<((usize, usize), &String)> = pins
+                .iter()
+                .filter(|(p, _)| base(p) == pin)
+                .map(|(p, wire)| {
+                    let (_, range) = split_slice(p).unwrap();
+                    (range.unwrap_or((0, pin_width - 1)), wire)
+                })
+                .collect();
+
+            let connection = if entries.len() == 1 && entries[0].0 == (0, pin_width - 1) {
+                expr(entries[0].1)
+            } else if callee.is_input(&pin) {
+                // the bits of a sliced input are put together, unconnected bits are 0
+                let mut concat = vec!["1'b0".to_string(); pin_width];
+                for ((start, _), wire) in entries {
+                    for (i, bit) in bits(wire).into_iter().enumerate() {
+                        concat[start + i] = bit;
+                    }
+                }
+                concat.reverse();
+                format!("{{{}}}", concat.join(", "))
+            } else {
+                // a sliced output drives a wire of its own that the slices are taken from
+                let wire = format!("{}_{}", instance, pin);
+                for (range, target) in entries {
+                    assigns += &format!(
+                        "    assign {} = {};\n",
+                        expr(target),
+                        select(&wire, pin_width, Some(range))
+                    );
+                }
+                wires.push((wire.clone(), pin_width));
+                ident(&wire)
+            };
+            connections.push(format!("        .{}({})", ident(&pin), connection));
+        }
+
+        instances += &format!(
+            "\n    {} {} (\n{}\n    );\n",
+            ident(name),
+            ident(instance),
+            connections.join(",\n")
+        );
+    }
+
+    // signals driven by an input of the chip or an output pin of a part
+    let mut driven: HashSet<String> = chip_def.inputs().into_iter().collect();
+    for (_, _, callee, pins) in parts.iter() {
+        for (pin, wire) in pins.iter() {
+            if !callee.is_input(&base(pin)) {
+                driven.insert(base(wire));
+            }
+        }
+    }
+    for (_, (a, b)) in maps.iter() {
+        // the first signal of a map drives the second one unless only the second one is driven
+        let (target, source) = if driven.contains(&base(b)) && !driven.contains(&base(a)) {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        assigns += &format!("    assign {} = {};\n", expr(target), expr(source));
+    }
+
+    let declarations: String = wires
+        .iter()
+        .map(|(wire, width)| match width {
+            1 => format!("    wire {};\n", ident(wire)),
+            width => format!("    wire [{}:0] {};\n", width - 1, ident(wire)),
+        })
+        .collect();
+
+    header(&chip_def.name(), &ports) + &declarations + &instances + &assigns + "endmodule\n"
+}
@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+
+use bool_algebra::{bool_to_u32, update_values};
+
+use crate::elaborate::{bit_name, pin_names};
+use crate::json::{self, Json, Value};
+use crate::{ChipDef, Component, Error, Library, LookupTable};
+
+/// the most inputs a `$lut` cell can have, its lookup table has 2^n rows
+const MAX_INPUTS: usize = 20;
+
+/// a bit of a yosys netlist
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bit {
+    Net(usize),
+    /// `"0"` and `"1"`, `"x"` and `"z"` return `Error::Unsupported`
+    Const(bool),
+}
+
+/// (input pins, function) of the yosys gate cell `kind`, the output is always `Y`
+fn gate(kind: &str) -> Option<(&'static [&'static str], fn(&[bool]) -> bool)> {
+    let gate: (&'static [&'static str], fn(&[bool]) -> bool) = match kind {
+        "$_BUF_" => (&["A"], |v| v[0]),
+        "$_NOT_" => (&["A"], |v| !v[0]),
+        "$_AND_" => (&["A", "B"], |v| v[0] && v[1]),
+        "$_NAND_" => (&["A", "B"], |v| !(v[0] && v[1])),
+        "$_OR_" => (&["A", "B"], |v| v[0] || v[1]),
+        "$_NOR_" => (&["A", "B"], |v| !(v[0] || v[1])),
+        "$_XOR_" => (&["A", "B"], |v| v[0] != v[1]),
+        "$_XNOR_" => (&["A", "B"], |v| v[0] == v[1]),
+        "$_ANDNOT_" => (&["A", "B"], |v| v[0] && !v[1]),
+        "$_ORNOT_" => (&["A", "B"], |v| v[0] || !v[1]),
+        "$_MUX_" => (&["A", "B", "S"], |v| if v[2] { v[1] } else { v[0] }),
+        "$_NMUX_" => (&["A", "B", "S"], |v| if v[2] { !v[1] } else { !v[0] }),
+        _ => return None,
+    };
+    Some(gate)
+}
+
+/// creates a lookup table with the output `Y`
+fn lut(name: &str, in_names: &[String], function: impl Fn(&[bool]) -> bool) -> LookupTable {
+    let mut values = vec![false; in_names.len()];
+    let mut table = vec![false; 2_usize.pow(in_names.len() as u32)];
+    loop {
+        table[bool_to_u32(values.clone()) as usize] = function(&values);
+        if !update_values(&mut values) {
+            break;
+        }
+    }
+    LookupTable::new(
+        vec![table],
+        in_names.iter().map(|s| s.as_str()).collect(),
+        vec!["Y"],
+        name,
+    )
+    .unwrap()
+}
+
+/// reads a list of bits like `[2, 3, "0"]`
+fn bits(value: &Value) -> Result<Vec<Bit>, Error> {
+    value
+        .elements()?
+        .iter()
+        .map(|bit| match &bit.json {
+            Json::Number(_) => bit.usize().map(Bit::Net),
+            Json::String(s) => match s.as_str() {
+                "0" => Ok(Bit::Const(false)),
+                "1" => Ok(Bit::Const(true)),
+                "x" | "z" => Err(Error::Unsupported {
+                    part: s.clone(),
+                    msg: "undefined and high impedance bits can not be simulated".to_string(),
+                }),
+                _ => Err(bit.error(format!("{:?} is not a constant bit", s))),
+            },
+            _ => Err(bit.error("expected a net id or a constant bit".to_string())),
+        })
+        .collect()
+}
+
+/// reads a parameter that is a number or a string of binary digits, least significant bit first
+fn parameter(cell: &Value, name: &str) -> Result<Vec<bool>, Error> {
+    let value = match cell.get("parameters").and_then(|params| params.get(name)) {
+        Some(value) => value,
+        None => return Err(cell.error(format!("parameter {} is missing", name))),
+    };
+    match &value.json {
+        Json::Number(_) => {
+            let n = value.usize()?;
+            Ok((0..usize::BITS).map(|i| n >> i & 1 == 1).collect())
+        }
+        Json::String(s) if s.chars().all(|c| "01xz".contains(c)) => {
+            Ok(s.chars().rev().map(|c| c == '1').collect())
+        }
+        _ => Err(value.error(format!("parameter {} is not a binary number", name))),
+    }
+}
+
+/// a name that can be used as a signal of a `ChipDef`
+fn is_simple(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// (input, output) port names of every module
+type Ports = HashMap<String, (Vec<String>, Vec<String>)>;
+
+/// adds every module of a yosys `write_json` netlist as a chip
+/// gate cells like `$_AND_` and `$lut` cells become lookup tables, `$_DFF_P_` and `$_DFF_N_` become `DFFC` parts clocked by `C`
+/// `$_FF_` becomes a `DFF` part clocked by the tick, undefined `x` and `z` bits return `Error::Unsupported`
+/// cells of other modules are connected by name, buses of their pins are split in to bits like `A[0]`
+pub(crate) fn add_yosys_json(library: &mut Library, src: &str) -> Result<(), Error> {
+    let root = json::parse(src)?;
+    let modules = match root.get("modules") {
+        Some(modules) => modules.members()?,
+        None => return Err(root.error("expected the member modules".to_string())),
+    };
+
+    let mut ports = Ports::new();
+    for (name, module) in modules.iter() {
+        let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+        if let Some(module_ports) = module.get("ports") {
+            for (port, value) in module_ports.members()? {
+                match value.get("direction").map(|d| d.str()).transpose()? {
+                    Some("input") => inputs.push(port.clone()),
+                    Some("output") => outputs.push(port.clone()),
+                    _ => {
+                        return Err(
+                            value.error(format!("port {} has to be an input or an output", port))
+                        )
+                    }
+                }
+            }
+        }
+        ports.insert(name.clone(), (inputs, outputs));
+    }
+
+    let mut luts: HashMap<String, LookupTable> = HashMap::new();
+    let mut chip_defs = Vec::new();
+    for (name, module) in modules.iter() {
+        let blackbox = module
+            .get("attributes")
+            .and_then(|attributes| attributes.get("blackbox"))
+            .is_some();
+        if !blackbox {
+            chip_defs.push(chip_def(name, module, &ports, library, &mut luts)?);
+        }
+    }
+
+    for (_, lut) in luts {
+        library.add_lut(lut);
+    }
+    for chip_def in chip_defs {
+        library.add_chip(chip_def);
+    }
+    Ok(())
+}
+
+fn chip_def(
+    name: &str,
+    module: &Value,
+    ports: &Ports,
+    library: &Library,
+    luts: &mut HashMap<String, LookupTable>,
+) -> Result<ChipDef, Error> {
+    let empty = Value {
+        json: Json::Object(Vec::new()),
+        line: module.line,
+        column: module.column,
+    };
+    let members = |key: &str| module.get(key).unwrap_or(&empty).members();
+
+    // the signal of every net, ports come first then the named nets
+    let mut signals: HashMap<usize, String> = HashMap::new();
+    // (port, width) of the inputs and outputs
+    let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+    let mut parts = Vec::new();
+    // (driving signal, output bit) of the output bits that are also another port or a constant
+    let mut aliases = Vec::new();
+
+    for (port, value) in members("ports")? {
+        let port_bits = bits(value.get("bits").unwrap_or(value))?;
+        let width = port_bits.len();
+        let input = ports[name].0.contains(port);
+        for (i, bit) in port_bits.into_iter().enumerate() {
+            let signal = bit_name(port, i, width);
+            match bit {
+                Bit::Net(net) if !signals.contains_key(&net) => {
+                    signals.insert(net, signal);
+                }
+                _ if input => return Err(value.error(format!("input {} can not share bits", port))),
+                bit => aliases.push((bit, signal)),
+            }
+        }
+        let decl = match width {
+            1 => port.clone(),
+            _ => format!("{}[{}]", port, width),
+        };
+        if input {
+            inputs.push(decl);
+        } else {
+            outputs.push(decl);
+        }
+    }
+    for (net, value) in members("netnames")? {
+        let hidden =
+            matches!(value.get("hide_name").map(|h| &h.json), Some(Json::Number(n)) if *n != 0.0);
+        if hidden || !is_simple(net) {
+            continue;
+        }
+        let net_bits = match value.get("bits") {
+            Some(net_bits) => bits(net_bits)?,
+            None => continue,
+        };
+        let width = net_bits.len();
+        for (i, bit) in net_bits.into_iter().enumerate() {
+            if let Bit::Net(id) = bit {
+                signals.entry(id).or_insert_with(|| bit_name(net, i, width));
+            }
+        }
+    }
+
+    // constants are driven by lookup tables without inputs
+    let mut constants = [false, false];
+    let mut signal = |bit: Bit| match bit {
+        Bit::Net(id) => signals
+            .entry(id)
+            .or_insert_with(|| format!("${}", id))
+            .clone(),
+        Bit::Const(value) => {
+            constants[value as usize] = true;
+            format!("${}", value)
+        }
+    };
+
+    for (instance, cell) in members("cells")? {
+        let kind_value = match cell.get("type") {
+            Some(kind) => kind,
+            None => return Err(cell.error(format!("cell {} has no type", instance))),
+        };
+        let kind = kind_value.str()?;
+        let hidden =
+            matches!(cell.get("hide_name").map(|h| &h.json), Some(Json::Number(n)) if *n != 0.0);
+        let connections = match cell.get("connections") {
+            Some(connections) => connections.members()?,
+            None => &[],
+        };
+        // the bits connected to a pin
+        let pin = |pin: &str| -> Result<Vec<Bit>, Error> {
+            match connections.iter().find(|(name, _)| name == pin) {
+                Some((_, value)) => bits(value),
+                None => {
+                    Err(cell.error(format!("pin {} of cell {} is not connected", pin, instance)))
+                }
+            }
+        };
+        let one = |pin_name: &str| -> Result<Bit, Error> {
+            match pin(pin_name)?.as_slice() {
+                [bit] => Ok(*bit),
+                _ => Err(cell.error(format!(
+                    "pin {} of cell {} has to be 1 bit wide",
+                    pin_name, instance
+                ))),
+            }
+        };
+
+        let (part, in_pins, out_pins): (String, Vec<(String, Bit)>, Vec<(String, Bit)>) =
+            if let Some((in_names, function)) = gate(kind) {
+                let in_names: Vec<String> = in_names.iter().map(|s| s.to_string()).collect();
+                luts.entry(kind.to_string())
+                    .or_insert_with(|| lut(kind, &in_names, function));
+                let in_pins = in_names
+                    .iter()
+                    .map(|name| one(name).map(|bit| (name.clone(), bit)))
+                    .collect::<Result<_, _>>()?;
+                (
+                    kind.to_string(),
+                    in_pins,
+                    vec![("Y".to_string(), one("Y")?)],
+                )
+            } else if kind == "$_FF_" {
+                // the global clock of yosys is the tick
+                (
+                    "DFF".to_string(),
+                    vec![("in".to_string(), one("D")?)],
+                    vec![("out".to_string(), one("Q")?)],
+                )
+            } else if matches!(kind, "$_DFF_P_" | "$_DFF_N_") {
+                (
+                    "DFFC".to_string(),
+                    vec![
+                        ("in".to_string(), one("D")?),
+                        ("clk".to_string(), one("C")?),
+                    ],
+                    vec![("out".to_string(), one("Q")?)],
+                )
+            } else if kind == "$lut" {
+                let width = parameter(cell, "WIDTH")?;
+                let too_wide = || Error::Unsupported {
+                    part: instance.clone(),
+                    msg: format!("a $lut can have at most {} inputs", MAX_INPUTS),
+                };
+                if width.iter().skip(8).any(|&bit| bit) {
+                    return Err(too_wide());
+                }
+                let width = width
+                    .iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, &bit)| acc | (bit as usize) << i);
+                if width > MAX_INPUTS {
+                    return Err(too_wide());
+                }
+                let mut table = parameter(cell, "LUT")?;
+                table.resize(2_usize.pow(width as u32), false);
+                let a = pin("A")?;
+                if a.len() != width {
+                    return Err(cell.error(format!(
+                        "pin A of cell {} has to be {} bits wide",
+                        instance, width
+                    )));
+                }
+
+                let hex: String = table
+                    .chunks(4)
+                    .rev()
+                    .map(|nibble| {
+                        let value = nibble
+                            .iter()
+                            .enumerate()
+                            .fold(0, |acc, (i, &bit)| acc | (bit as u32) << i);
+                        std::char::from_digit(value, 16).unwrap()
+                    })
+                    .collect();
+                let lut_name = format!("$lut{}_{}", width, hex);
+                let in_names: Vec<String> = (0..width).map(|i| format!("A{}", i)).collect();
+                luts.entry(lut_name.clone()).or_insert_with(|| {
+                    lut(&lut_name, &in_names, |values| {
+                        let index = values
+                            .iter()
+                            .enumerate()
+                            .fold(0, |acc, (i, &bit)| acc | (bit as usize) << i);
+                        table[index]
+                    })
+                });
+                (
+                    lut_name,
+                    in_names.into_iter().zip(a).collect(),
+                    vec![("Y".to_string(), one("Y")?)],
+                )
+            } else if kind.starts_with('$') {
+                return Err(kind_value.error(format!("{} cells are not supported", kind)));
+            } else {
+                // a cell of another module, pins are sorted by the port directions of the cell or the module
+                let directions = cell.get("port_directions");
+                let in_names = match ports.get(kind) {
+                    Some((in_names, _)) => Some(in_names.clone()),
+                    None => pin_names(library, kind).map(|(in_names, _)| in_names),
+                };
+                let (mut in_pins, mut out_pins) = (Vec::new(), Vec::new());
+                for (pin_name, value) in connections.iter() {
+                    let input = match directions.and_then(|d| d.get(pin_name)) {
+                        Some(direction) => direction.str()? != "output",
+                        None => in_names
+                            .as_ref()
+                            .is_none_or(|in_names| in_names.contains(pin_name)),
+                    };
+                    let pin_bits = bits(value)?;
+                    let width = pin_bits.len();
+                    for (i, bit) in pin_bits.into_iter().enumerate() {
+                        let pin = (bit_name(pin_name, i, width), bit);
+                        if input {
+                            in_pins.push(pin);
+                        } else {
+                            out_pins.push(pin);
+                        }
+                    }
+                }
+                (kind.to_string(), in_pins, out_pins)
+            };
+
+        let mut connect = |pins: Vec<(String, Bit)>| -> Vec<(String, String)> {
+            pins.into_iter()
+                .map(|(pin, bit)| (pin, signal(bit)))
+                .collect()
+        };
+        let mut inputs = connect(in_pins);
+        let outputs = connect(out_pins);
+
+        // the falling edge of the clock is the rising edge of the inverted clock
+        if kind == "$_DFF_N_" {
+            let (in_names, function) = gate("$_NOT_").unwrap();
+            let in_names: Vec<String> = in_names.iter().map(|s| s.to_string()).collect();
+            luts.entry("$_NOT_".to_string())
+                .or_insert_with(|| lut("$_NOT_", &in_names, function));
+            let inverted = format!("$not_{}", parts.len());
+            parts.push((
+                Component::Def {
+                    inputs: vec![("A".to_string(), inputs[1].1.clone())],
+                    outputs: vec![("Y".to_string(), inverted.clone())],
+                    name: "$_NOT_".to_string(),
+                },
+                None,
+            ));
+            inputs[1].1 = inverted;
+        }
+
+        parts.push((
+            Component::Def {
+                inputs,
+                outputs,
+                name: part,
+            },
+            (!hidden).then(|| instance.clone()),
+        ));
+    }
+
+    for (bit, output) in aliases {
+        parts.push((
+            Component::Map {
+                var_map: vec![(signal(bit), output)],
+                name: "assign".to_string(),
+            },
+            None,
+        ));
+    }
+    for value in [false, true] {
+        if constants[value as usize] {
+            let lut_name = format!("${}", value);
+            luts.entry(lut_name.clone())
+                .or_insert_with(|| lut(&lut_name, &[], |_| value));
+            parts.push((
+                Component::Def {
+                    inputs: Vec::new(),
+                    outputs: vec![("Y".to_string(), lut_name.clone())],
+                    name: lut_name.clone(),
+                },
+                Some(lut_name),
+            ));
+        }
+    }
+
+    Ok(ChipDef::with_instances(
+        name.to_string(),
+        inputs,
+        outputs,
+        parts,
+    ))
+}
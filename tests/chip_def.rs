@@ -1,4 +1,4 @@
-use hardware_sim::{ChipDef, LookupTable};
+use hardware_sim::{ChipDef, Circuit, Component, Diagnostic, Error, Library, LookupTable};
 
 #[test]
 fn common_from_nand() {
@@ -56,4 +56,876 @@ fn common_from_nand() {
             ),
         ],
     );
+
+    let and_def = ChipDef::new(
+        "And",
+        vec!["a", "b"],
+        vec!["out"],
+        vec![
+            (vec![("a", "a"), ("b", "b")], vec![("out", "nand")], "Nand"),
+            (vec![("a", "nand")], vec![("out", "out")], "Not"),
+        ],
+    );
+
+    let mut library = Library::new();
+    library.add_lut(nand);
+    library.add_chip(not_def);
+    library.add_chip(and_def);
+    library.add_chip(or_def);
+
+    let mut xor = Circuit::with_library(xor_def, &library).unwrap();
+
+    for (a, b, out) in [
+        (false, false, false),
+        (true, false, true),
+        (false, true, true),
+        (true, true, false),
+    ] {
+        assert_eq!(xor.set("a", a), Ok(()));
+        assert_eq!(xor.set("b", b), Ok(()));
+        for _ in 0..8 {
+            assert_eq!(xor.tick(), Ok(()));
+        }
+        assert_eq!(xor.get("out"), Ok(out));
+    }
+}
+
+#[test]
+fn recursive_chip() {
+    let loop_def = ChipDef::new(
+        "Loop",
+        vec!["a"],
+        vec!["out"],
+        vec![(vec![("a", "a")], vec![("out", "out")], "Loop")],
+    );
+
+    let mut library = Library::new();
+    library.add_chip(loop_def.clone());
+
+    assert!(Circuit::with_library(loop_def, &library).is_err());
+}
+
+#[test]
+fn missing_part() {
+    let def = ChipDef::new(
+        "Not",
+        vec!["a"],
+        vec!["out"],
+        vec![(vec![("a", "a"), ("b", "a")], vec![("out", "out")], "Nand")],
+    );
+
+    match Circuit::with_library(def, &Library::new()) {
+        Err(Error::Invalid(diagnostics)) => {
+            assert!(diagnostics.contains(&Diagnostic::MissingPart {
+                chip: "Not".to_string(),
+                instance: "nand_0".to_string(),
+                part: "Nand".to_string(),
+            }))
+        }
+        _ => panic!("expected a missing part"),
+    }
+}
+
+#[test]
+fn map_and_io() {
+    let nand = LookupTable::new(
+        vec![vec![true, true, true, false]],
+        vec!["a", "b"],
+        vec!["out"],
+        "Nand",
+    )
+    .unwrap();
+
+    // Not(a) = Nand(a, a) connected by position and the result renamed to out
+    let not_def = ChipDef::new_string(
+        "Not".to_string(),
+        vec!["a".to_string()],
+        vec!["out".to_string()],
+        vec![
+            Component::IO {
+                inputs: vec!["a".to_string(), "a".to_string()],
+                outputs: vec!["nand".to_string()],
+                name: "Nand".to_string(),
+            },
+            Component::Map {
+                var_map: vec![("nand".to_string(), "out".to_string())],
+                name: "rename".to_string(),
+            },
+        ],
+    );
+
+    let mut library = Library::new();
+    library.add_lut(nand);
+
+    let mut not = Circuit::with_library(not_def, &library).unwrap();
+    assert_eq!(not.tick(), Ok(()));
+    assert_eq!(not.get("out"), Ok(true));
+    assert_eq!(not.set("a", true), Ok(()));
+    assert_eq!(not.tick(), Ok(()));
+    assert_eq!(not.get("out"), Ok(false));
+
+    let wrong_size = ChipDef::new_string(
+        "Not".to_string(),
+        vec!["a".to_string()],
+        vec!["out".to_string()],
+        vec![Component::IO {
+            inputs: vec!["a".to_string()],
+            outputs: vec!["out".to_string()],
+            name: "Nand".to_string(),
+        }],
+    );
+    assert!(Circuit::with_library(wrong_size, &library).is_err());
+
+    let short_circuit = ChipDef::new_string(
+        "Short".to_string(),
+        vec!["a".to_string(), "b".to_string()],
+        vec![],
+        vec![Component::Map {
+            var_map: vec![("a".to_string(), "b".to_string())],
+            name: "short".to_string(),
+        }],
+    );
+    assert!(Circuit::with_library(short_circuit, &library).is_err());
+}
+
+#[test]
+fn diagnostics() {
+    let nand = LookupTable::new(
+        vec![vec![true, true, true, false]],
+        vec!["a", "b"],
+        vec!["out"],
+        "Nand",
+    )
+    .unwrap();
+    let mut library = Library::new();
+    library.add_lut(nand);
+
+    let def = ChipDef::new(
+        "Broken",
+        vec!["a", "b", "unused"],
+        vec!["out", "never"],
+        vec![
+            (vec![("a", "a"), ("c", "b")], vec![("out", "x")], "Nand"),
+            (vec![("a", "a"), ("b", "y")], vec![("out", "out")], "Nand"),
+            (vec![("a", "a"), ("b", "b")], vec![("out", "out")], "Nand"),
+            (vec![("a", "a"), ("b", "b")], vec![("out", "z")], "Nand"),
+            (vec![("in", "a")], vec![("out", "w")], "Foo"),
+        ],
+    );
+
+    let diagnostics = def.check(&library);
+    let chip = "Broken".to_string();
+    for expected in [
+        Diagnostic::UnknownPin {
+            chip: chip.clone(),
+            instance: "nand_0".to_string(),
+            part: "Nand".to_string(),
+            pin: "c".to_string(),
+        },
+        Diagnostic::PinCount {
+            chip: chip.clone(),
+            instance: "nand_0".to_string(),
+            part: "Nand".to_string(),
+            expected: vec!["a".to_string(), "b".to_string()],
+            connected: vec!["a".to_string()],
+        },
+        Diagnostic::MissingPart {
+            chip: chip.clone(),
+            instance: "foo_0".to_string(),
+            part: "Foo".to_string(),
+        },
+        Diagnostic::MultipleDrivers {
+            chip: chip.clone(),
+            wire: "out".to_string(),
+            drivers: vec!["nand_1.out".to_string(), "nand_2.out".to_string()],
+        },
+        Diagnostic::OutputNotDriven {
+            chip: chip.clone(),
+            output: "never".to_string(),
+        },
+        Diagnostic::Undriven {
+            chip: chip.clone(),
+            wire: "y".to_string(),
+        },
+        Diagnostic::UnusedInput {
+            chip: chip.clone(),
+            input: "unused".to_string(),
+        },
+        Diagnostic::UnusedWire {
+            chip: chip.clone(),
+            wire: "z".to_string(),
+        },
+    ] {
+        assert!(diagnostics.contains(&expected), "missing {}", expected);
+    }
+    assert_eq!(diagnostics.len(), 8);
+    assert_eq!(diagnostics.iter().filter(|d| d.is_error()).count(), 6);
+
+    let errors = diagnostics.into_iter().filter(|d| d.is_error()).collect();
+    assert_eq!(
+        Circuit::with_library(def, &library).err(),
+        Some(Error::Invalid(errors))
+    );
+}
+
+#[test]
+fn hdl() {
+    let src = "
+        /** the only builtin chip */
+        CHIP Nand {
+            IN a, b;
+            OUT out;
+            BUILTIN Nand;
+        }
+
+        CHIP Not {
+            IN in;
+            OUT out;
+            PARTS:
+            Nand(a=in, b=in, out=out);
+        }
+
+        // bitwise not of the upper half
+        CHIP NotHigh {
+            IN in[4];
+            OUT out[2];
+            PARTS:
+            Not(in=in[2], out=out[0]);
+            Not(in=in[3], out=out[1]);
+        }
+
+        CHIP Xor {
+            IN a, b;
+            OUT out;
+            PARTS:
+            Not(in=a, out=nota);
+            Not(in=b, out=notb);
+            Nand(a=a, b=notb, out=x);
+            Nand(a=nota, b=b, out=y);
+            Nand(a=x, b=y, out=out);
+        }
+    ";
+
+    let mut library = Library::new();
+    assert_eq!(library.add_hdl(src), Ok(()));
+    assert!(library.lut("Nand").is_some());
+    assert_eq!(library.chip("NotHigh").unwrap().width("in"), 4);
+
+    let xor = library.chip("Xor").unwrap().clone();
+    assert_eq!(
+        xor.parts()[2],
+        Component::Def {
+            inputs: vec![
+                ("a".to_string(), "a".to_string()),
+                ("b".to_string(), "notb".to_string())
+            ],
+            outputs: vec![("out".to_string(), "x".to_string())],
+            name: "Nand".to_string(),
+        }
+    );
+
+    let mut circuit = Circuit::with_library(xor, &library).unwrap();
+    for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+        assert_eq!(circuit.set("a", a), Ok(()));
+        assert_eq!(circuit.set("b", b), Ok(()));
+        for _ in 0..8 {
+            assert_eq!(circuit.tick(), Ok(()));
+        }
+        assert_eq!(circuit.get("out"), Ok(a != b));
+    }
+
+    let mut circuit =
+        Circuit::with_library(library.chip("NotHigh").unwrap().clone(), &library).unwrap();
+    assert_eq!(circuit.set_bus("in", 0b0110), Ok(()));
+    for _ in 0..4 {
+        assert_eq!(circuit.tick(), Ok(()));
+    }
+    assert_eq!(circuit.get_bus("out"), Ok(0b10));
+
+    let not = ChipDef::from_hdl("CHIP Not { IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }");
+    assert_eq!(
+        not,
+        Ok(ChipDef::new(
+            "Not",
+            vec!["in"],
+            vec!["out"],
+            vec![(vec![("a", "in"), ("b", "in")], vec![("out", "out")], "Nand")],
+        ))
+    );
+}
+
+#[test]
+fn hdl_errors() {
+    let parse = |line, column| {
+        Err(Error::Parse {
+            line,
+            column,
+            msg: String::new(),
+        })
+    };
+    let position = |result: Result<ChipDef, Error>| match result {
+        Err(Error::Parse { line, column, .. }) => parse(line, column),
+        other => other,
+    };
+
+    assert_eq!(
+        position(ChipDef::from_hdl(
+            "CHIP Not {\n    IN in;\n    OUT out\n    PARTS:\n}"
+        )),
+        parse(4, 5)
+    );
+    assert_eq!(
+        position(ChipDef::from_hdl(
+            "CHIP Not { IN in; OUT out; PARTS: Nand(a=in b=in); }"
+        )),
+        parse(1, 45)
+    );
+    assert_eq!(
+        position(ChipDef::from_hdl(
+            "CHIP Nand { IN a, b; OUT out; BUILTIN Nand; }"
+        )),
+        parse(1, 1)
+    );
+    assert_eq!(
+        position(ChipDef::from_hdl("/* open comment\nCHIP Nand { }")),
+        parse(1, 1)
+    );
+    assert_eq!(
+        position(ChipDef::from_hdl("CHIP A { PARTS: }\nCHIP B { PARTS: }")),
+        parse(2, 1)
+    );
+
+    let mut library = Library::new();
+    assert!(matches!(
+        library.add_hdl("CHIP Nand { IN x, y; OUT out; BUILTIN Nand; }"),
+        Err(Error::Parse {
+            line: 1,
+            column: 39,
+            ..
+        })
+    ));
+    assert!(matches!(
+        library.add_hdl("CHIP Mux16 { IN a, b; OUT out; BUILTIN Mux16; }"),
+        Err(Error::Parse {
+            line: 1,
+            column: 40,
+            ..
+        })
+    ));
+    assert!(matches!(
+        library.add_hdl("CHIP Nand { IN a, b; OUT out; BUILTIN Nand; CLOCKED a; }"),
+        Err(Error::Parse {
+            line: 1,
+            column: 39,
+            ..
+        })
+    ));
+    assert!(!library.contains("Nand"));
+}
+
+#[test]
+fn verilog() {
+    let src = "
+        // gate level full adder
+        module full_adder(input a, b, cin, output sum, cout);
+            wire s1, c1, c2;
+            xor x1 (s1, a, b);
+            xor (sum, s1, cin);
+            and a1 (c1, a, b), a2 (c2, s1, cin);
+            or (cout, c1, c2);
+        endmodule
+
+        /* non ansi ports */
+        module add4(x, y, s, c);
+            input [3:0] x, y;
+            output [3:0] s;
+            output c;
+            wire [4:1] carry;
+
+            half_adder h0 (.a(x[0]), .b(y[0]), .sum(s[0]), .carry(carry[1]));
+            full_adder f1 (x[1], y[1], carry[1], s[1], carry[2]);
+            full_adder f2 (x[2], y[2], carry[2], s[2], carry[3]);
+            full_adder f3 (.a(x[3]), .b(y[3]), .cin(carry[3]), .sum(s[3]), .cout(carry[4]));
+            assign c = carry[4];
+        endmodule
+
+        module half_adder(a, b, sum, carry);
+            input a, b;
+            output sum, carry;
+            xor (sum, a, b);
+            and (carry, a, b);
+        endmodule
+    ";
+
+    let mut library = Library::new();
+    assert_eq!(library.add_verilog(src), Ok(()));
+    assert_eq!(library.lut("$xor2").unwrap().in_names(), vec!["a", "b"]);
+    assert!(!library.contains("Xor"));
+
+    let add4 = library.chip("add4").unwrap().clone();
+    assert_eq!(add4.inputs(), vec!["x", "y"]);
+    assert_eq!(add4.outputs(), vec!["s", "c"]);
+    assert_eq!(add4.width("x"), 4);
+    assert_eq!(
+        add4.instance_names(),
+        vec!["h0", "f1", "f2", "f3", "assign_0"]
+    );
+    assert_eq!(
+        add4.parts()[1],
+        Component::Def {
+            inputs: vec![
+                ("a".to_string(), "x[1]".to_string()),
+                ("b".to_string(), "y[1]".to_string()),
+                ("cin".to_string(), "carry[0]".to_string()),
+            ],
+            outputs: vec![
+                ("sum".to_string(), "s[1]".to_string()),
+                ("cout".to_string(), "carry[1]".to_string()),
+            ],
+            name: "full_adder".to_string(),
+        }
+    );
+    assert_eq!(add4.instance_names()[1], "f1");
+
+    let mut circuit = Circuit::with_library(add4, &library).unwrap();
+    for x in 0..16 {
+        for y in 0..16 {
+            assert_eq!(circuit.set_bus("x", x), Ok(()));
+            assert_eq!(circuit.set_bus("y", y), Ok(()));
+            for _ in 0..12 {
+                assert_eq!(circuit.tick(), Ok(()));
+            }
+            assert_eq!(circuit.get_bus("s"), Ok((x + y) % 16));
+            assert_eq!(circuit.get("c"), Ok(x + y >= 16));
+        }
+    }
+}
+
+#[test]
+fn verilog_errors() {
+    let error = |src: &str| match Library::new().add_verilog(src) {
+        Err(Error::Parse { line, column, .. }) => Some((line, column)),
+        _ => None,
+    };
+
+    assert_eq!(
+        error("module m(input a, output y);\n  assign y = 1'b0;\nendmodule"),
+        Some((2, 14))
+    );
+    assert_eq!(
+        error("module m(input a, output y);\n  reg r;\nendmodule"),
+        Some((2, 3))
+    );
+    assert_eq!(
+        error("module m(input a, output y);\n  not (y, a, a);\nendmodule"),
+        Some((2, 7))
+    );
+    assert_eq!(
+        error("module m(input a, output y);\n  other o (a, y);\nendmodule"),
+        Some((2, 9))
+    );
+    assert_eq!(
+        error("module m(a, y);\n  input a;\nendmodule"),
+        Some((3, 10))
+    );
+    assert_eq!(
+        error("module m(input [0:3] a, output y);\nendmodule"),
+        Some((1, 17))
+    );
+    assert_eq!(
+        error("module m(input a, output y)\nendmodule"),
+        Some((2, 1))
+    );
+}
+
+#[test]
+fn verilog_export() {
+    let builtins = "
+        CHIP Nand { IN a, b; OUT out; BUILTIN Nand; }
+        CHIP FullAdder { IN a, b, c; OUT sum, carry; BUILTIN FullAdder; }
+    ";
+    let mut library = Library::new();
+    assert_eq!(library.add_hdl(builtins), Ok(()));
+    assert_eq!(
+        library.add_hdl(
+            "
+            CHIP Not { IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }
+            CHIP Pair { IN x[2], y; OUT out[2]; PARTS:
+                Nand(a=x[0], b=y, out=out[0]);
+                Nand(a=x[1], b=y, out=out[1]);
+            }
+            CHIP Add2 { IN a[2], b[2]; OUT out[2], low; PARTS:
+                FullAdder(a=a[0], b=b[0], c=a[1], sum=out[0], carry=c0);
+                FullAdder(a=a[1], b=b[1], c=c0, sum=s1, carry=unused);
+                Not(in=s1, out=ns);
+                Not(in=ns, out=out[1]);
+                Pair(x=a, y=b[1], out[0]=low, out[1]=high);
+            }
+            "
+        ),
+        Ok(())
+    );
+    let add2 = library.chip("Add2").unwrap().clone();
+
+    let verilog = add2.to_verilog(&library).unwrap();
+    assert!(verilog.starts_with(
+        "module Add2 (
+    input [1:0] a,
+    input [1:0] b,
+    output [1:0] out,
+    output low
+);
+    wire c0;
+    wire s1;
+"
+    ));
+    assert!(verilog.contains(
+        "    Pair pair_0 (
+        .x(a),
+        .y(b[1]),
+        .out(pair_0_out)
+    );
+    assign low = pair_0_out[0];
+    assign high = pair_0_out[1];
+endmodule
+"
+    ));
+    for module in ["Add2", "Not", "Pair", "Nand", "FullAdder"] {
+        assert_eq!(verilog.matches(&format!("module {} (", module)).count(), 1);
+    }
+
+    // the chip modules can be read back, the lut modules are not structural
+    let chips: String = verilog
+        .split_inclusive("endmodule\n")
+        .filter(|module| !module.contains("_table"))
+        .collect();
+    let mut imported = Library::new();
+    assert_eq!(imported.add_hdl(builtins), Ok(()));
+    assert_eq!(imported.add_verilog(&chips), Ok(()));
+
+    let mut expected = Circuit::with_library(add2, &library).unwrap();
+    let mut circuit =
+        Circuit::with_library(imported.chip("Add2").unwrap().clone(), &imported).unwrap();
+    for a in 0..4 {
+        for b in 0..4 {
+            for circuit in [&mut expected, &mut circuit] {
+                assert_eq!(circuit.set_bus("a", a), Ok(()));
+                assert_eq!(circuit.set_bus("b", b), Ok(()));
+                for _ in 0..8 {
+                    assert_eq!(circuit.tick(), Ok(()));
+                }
+            }
+            assert_eq!(circuit.get_bus("out"), expected.get_bus("out"));
+            assert_eq!(circuit.get("low"), expected.get("low"));
+        }
+    }
+
+    let broken = ChipDef::new("Broken", vec!["a"], vec!["out"], vec![]);
+    assert!(matches!(
+        broken.to_verilog(&library),
+        Err(Error::Invalid(_))
+    ));
+}
+
+#[test]
+fn blif() {
+    let src = "
+        # 2 bit counter
+        .model counter
+        .inputs en
+        .outputs q[0] q[1]
+        .subckt inc en=en a[0]=q[0] a[1]=q[1] y[0]=d[0] y[1]=d[1]
+        .latch d[0] q[0] 0
+        .latch d[1] q[1] 2
+        .end
+
+        .model inc
+        .inputs en a[0] a[1]
+        .outputs y[0] y[1]
+        .names en a[0] y[0]
+        10 1
+        01 1
+        .names en a[0] a[1] \\
+            y[1]
+        0-1 1
+        -01 1
+        110 1
+        .end
+    ";
+
+    let mut library = Library::new();
+    assert_eq!(library.add_blif(src), Ok(()));
+    assert_eq!(
+        library.lut("inc.y[1]").unwrap().in_names(),
+        vec!["in0", "in1", "in2"]
+    );
+
+    let counter = library.chip("counter").unwrap().clone();
+    assert_eq!(counter.inputs(), vec!["en"]);
+    assert_eq!(counter.outputs(), vec!["q"]);
+    assert_eq!(counter.width("q"), 2);
+    assert_eq!(counter.instance_names(), vec!["inc_0", "q[0]", "q[1]"]);
+    assert_eq!(
+        counter.parts()[2],
+        Component::Def {
+            inputs: vec![("in".to_string(), "d[1]".to_string())],
+            outputs: vec![("out".to_string(), "q[1]".to_string())],
+            name: "DFF".to_string(),
+        }
+    );
+
+    // the latches show the value they stored in the previous tick
+    let mut circuit = Circuit::with_library(counter, &library).unwrap();
+    assert_eq!(circuit.set("en", true), Ok(()));
+    for i in 0..6 {
+        assert_eq!(circuit.tick(), Ok(()));
+        assert_eq!(circuit.get_bus("q"), Ok(i % 4));
+    }
+    assert_eq!(circuit.set("en", false), Ok(()));
+    for _ in 0..4 {
+        assert_eq!(circuit.tick(), Ok(()));
+        assert_eq!(circuit.get_bus("q"), Ok(2));
+    }
+}
+
+#[test]
+fn blif_errors() {
+    let error = |src: &str| match Library::new().add_blif(src) {
+        Err(Error::Parse { line, column, .. }) => Some((line, column)),
+        _ => None,
+    };
+
+    assert_eq!(error(".inputs a\n.model m"), Some((1, 1)));
+    assert_eq!(error(".model m\n.inputs a\n.gate and2 A=a"), Some((3, 1)));
+    assert_eq!(error(".model m\n.names a b y\n1- 1\n0 1"), Some((4, 1)));
+    assert_eq!(error(".model m\n.names a y\n1 1\n0 0"), Some((4, 3)));
+    assert_eq!(error(".model m\n.names a y\n2 1"), Some((3, 1)));
+    assert_eq!(error(".model m\n.latch d q xx clk 0"), Some((2, 12)));
+    assert_eq!(error(".model m\n.latch d q 4"), Some((2, 12)));
+    assert_eq!(error(".model m\n.subckt s a"), Some((2, 11)));
+    assert_eq!(error(".model m\n.inputs a[x]"), Some((2, 9)));
+    assert_eq!(error(".model m\n.end\n.model m"), Some((3, 8)));
+
+    // latches and ports that can not be read without changing them
+    let unsupported = |src: &str| match Library::new().add_blif(src) {
+        Err(Error::Unsupported { part, .. }) => Some(part),
+        _ => None,
+    };
+    let latches = [
+        ".model m\n.inputs d c\n.latch d q fe c 0",
+        ".model m\n.inputs d c\n.latch d q re c 1",
+        ".model m\n.inputs d\n.latch d q re NIL 0",
+        ".model m\n.inputs d\n.latch d q ah c 0",
+    ];
+    for src in latches {
+        assert_eq!(unsupported(src), Some("q".to_string()));
+    }
+    assert_eq!(
+        unsupported(".model m\n.inputs a[0] a[2]"),
+        Some("m".to_string())
+    );
+    assert_eq!(unsupported(".model m\n.inputs a[3]"), Some("m".to_string()));
+}
+
+#[test]
+fn yosys() {
+    let src = r#"{
+        "creator": "Yosys 0.38",
+        "modules": {
+            "counter": {
+                "attributes": { "top": "00000000000000000000000000000001" },
+                "ports": {
+                    "clk": { "direction": "input", "bits": [ 2 ] },
+                    "en": { "direction": "input", "bits": [ 3 ] },
+                    "q": { "direction": "output", "bits": [ 4, 5 ] },
+                    "en_out": { "direction": "output", "bits": [ 3 ] },
+                    "zero": { "direction": "output", "bits": [ "0" ] }
+                },
+                "cells": {
+                    "$abc$12$auto$blifparse.cc:396:parse_blif$13": {
+                        "hide_name": 1,
+                        "type": "$lut",
+                        "parameters": { "LUT": "0110", "WIDTH": 2 },
+                        "connections": { "A": [ 4, 3 ], "Y": [ 6 ] }
+                    },
+                    "carry": {
+                        "hide_name": 0,
+                        "type": "$_AND_",
+                        "connections": { "A": [ 3 ], "B": [ 4 ], "Y": [ 7 ] }
+                    },
+                    "ha": {
+                        "hide_name": 0,
+                        "type": "half_adder",
+                        "port_directions": { "a": "input", "b": "input", "s": "output", "c": "output" },
+                        "connections": { "a": [ 5 ], "b": [ 7 ], "s": [ 8 ], "c": [ 9 ] }
+                    },
+                    "q0": {
+                        "hide_name": 0,
+                        "type": "$_DFF_P_",
+                        "connections": { "C": [ 2 ], "D": [ 6 ], "Q": [ 4 ] }
+                    },
+                    "q1": {
+                        "hide_name": 0,
+                        "type": "$_DFF_P_",
+                        "connections": { "C": [ 2 ], "D": [ 8 ], "Q": [ 5 ] }
+                    }
+                },
+                "netnames": {
+                    "next": { "hide_name": 0, "bits": [ 6, 8 ] },
+                    "$abc$12$t": { "hide_name": 1, "bits": [ 7 ] }
+                }
+            },
+            "half_adder": {
+                "ports": {
+                    "a": { "direction": "input", "bits": [ 2 ] },
+                    "b": { "direction": "input", "bits": [ 3 ] },
+                    "s": { "direction": "output", "bits": [ 4 ] },
+                    "c": { "direction": "output", "bits": [ 5 ] }
+                },
+                "cells": {
+                    "xor": { "type": "$_XOR_", "connections": { "A": [ 2 ], "B": [ 3 ], "Y": [ 4 ] } },
+                    "and": { "type": "$_AND_", "connections": { "A": [ 2 ], "B": [ 3 ], "Y": [ 5 ] } }
+                }
+            }
+        }
+    }"#;
+
+    let mut library = Library::new();
+    assert_eq!(library.add_yosys_json(src), Ok(()));
+    assert_eq!(library.lut("$lut2_6").unwrap().in_names(), vec!["A0", "A1"]);
+    assert_eq!(library.lut("$_AND_").unwrap().out_names(), vec!["Y"]);
+
+    let counter = library.chip("counter").unwrap().clone();
+    assert_eq!(counter.inputs(), vec!["clk", "en"]);
+    assert_eq!(counter.outputs(), vec!["q", "en_out", "zero"]);
+    assert_eq!(counter.width("q"), 2);
+    assert_eq!(
+        counter.parts()[0],
+        Component::Def {
+            inputs: vec![
+                ("A0".to_string(), "q[0]".to_string()),
+                ("A1".to_string(), "en".to_string())
+            ],
+            outputs: vec![("Y".to_string(), "next[0]".to_string())],
+            name: "$lut2_6".to_string(),
+        }
+    );
+    assert_eq!(
+        counter.parts()[3],
+        Component::Def {
+            inputs: vec![
+                ("in".to_string(), "next[0]".to_string()),
+                ("clk".to_string(), "clk".to_string())
+            ],
+            outputs: vec![("out".to_string(), "q[0]".to_string())],
+            name: "DFFC".to_string(),
+        }
+    );
+    assert_eq!(counter.instance_names()[3], "q0");
+
+    // the flip-flops are clocked by clk
+    let mut circuit = Circuit::with_library(counter, &library).unwrap();
+    let clock = |circuit: &mut Circuit| {
+        assert!(circuit.settle().is_ok());
+        assert_eq!(circuit.set("clk", true), Ok(()));
+        assert!(circuit.settle().is_ok());
+        assert_eq!(circuit.set("clk", false), Ok(()));
+        assert!(circuit.settle().is_ok());
+    };
+    assert_eq!(circuit.set("en", true), Ok(()));
+    for i in 1..7 {
+        clock(&mut circuit);
+        assert_eq!(circuit.get_bus("q"), Ok(i % 4));
+        assert_eq!(circuit.get("en_out"), Ok(true));
+        assert_eq!(circuit.get("zero"), Ok(false));
+    }
+    assert_eq!(circuit.set("en", false), Ok(()));
+    for _ in 0..4 {
+        clock(&mut circuit);
+        assert_eq!(circuit.get_bus("q"), Ok(2));
+        assert_eq!(circuit.get("en_out"), Ok(false));
+    }
+
+    // a falling edge flip-flop and one clocked by the tick
+    let src = r#"{"modules": {"m": {
+        "ports": {
+            "c": { "direction": "input", "bits": [ 2 ] },
+            "d": { "direction": "input", "bits": [ 3 ] },
+            "n": { "direction": "output", "bits": [ 4 ] },
+            "t": { "direction": "output", "bits": [ 5 ] }
+        },
+        "cells": {
+            "neg": { "type": "$_DFF_N_", "connections": { "C": [ 2 ], "D": [ 3 ], "Q": [ 4 ] } },
+            "ff": { "type": "$_FF_", "connections": { "D": [ 3 ], "Q": [ 5 ] } }
+        }
+    }}}"#;
+    let mut library = Library::new();
+    assert_eq!(library.add_yosys_json(src), Ok(()));
+    let mut circuit = Circuit::with_library(library.chip("m").unwrap().clone(), &library).unwrap();
+    assert_eq!(circuit.set("c", true), Ok(()));
+    assert_eq!(circuit.set("d", true), Ok(()));
+    assert!(circuit.settle().is_ok());
+    assert_eq!(circuit.get("n"), Ok(false));
+    assert_eq!(circuit.set("c", false), Ok(()));
+    assert!(circuit.settle().is_ok());
+    assert_eq!(circuit.get("n"), Ok(true));
+    assert_eq!(circuit.get("t"), Ok(false));
+    for _ in 0..2 {
+        assert_eq!(circuit.tick(), Ok(()));
+    }
+    assert_eq!(circuit.get("t"), Ok(true));
+}
+
+#[test]
+fn yosys_errors() {
+    let error = |src: &str| match Library::new().add_yosys_json(src) {
+        Err(Error::Parse { line, column, .. }) => Some((line, column)),
+        _ => None,
+    };
+    let module = |cells: &str| {
+        format!(
+            "{{\"modules\": {{\"m\": {{\n\"ports\": {{\"a\": {{\"direction\": \"input\", \"bits\": [2]}}}},\n\"cells\": {{{}}}}}}}}}",
+            cells
+        )
+    };
+
+    assert_eq!(error("{\"modules\": {}"), Some((1, 15)));
+    assert_eq!(error("{\"creator\": \"Yosys\"}"), Some((1, 1)));
+    assert_eq!(error("{\"modules\": {\"m\": {\"ports\": {\"a\": {\"direction\": \"inout\", \"bits\": [2]}}}}}"), Some((1, 35)));
+    assert_eq!(error(&module("\"c\": {\"type\": \"$add\"}")), Some((3, 25)));
+    assert_eq!(
+        error(&module(
+            "\"c\": {\"type\": \"$_AND_\", \"connections\": {\"A\": [2]}}"
+        )),
+        Some((3, 16))
+    );
+    assert_eq!(
+        error(&module(
+            "\"c\": {\"type\": \"$_NOT_\", \"connections\": {\"A\": [2, 3], \"Y\": [4]}}"
+        )),
+        Some((3, 16))
+    );
+    assert_eq!(
+        error(&module(
+            "\"c\": {\"type\": \"$_NOT_\", \"connections\": {\"A\": [\"q\"], \"Y\": [4]}}"
+        )),
+        Some((3, 57))
+    );
+    assert_eq!(error(&module("\"c\": {\"type\": \"$lut\", \"parameters\": {\"WIDTH\": 1}, \"connections\": {\"A\": [2], \"Y\": [4]}}")), Some((3, 16)));
+    assert!(matches!(
+        Library::new().add_yosys_json(&module(
+            "\"c\": {\"type\": \"$_NOT_\", \"connections\": {\"A\": [\"x\"], \"Y\": [4]}}"
+        )),
+        Err(Error::Unsupported { .. })
+    ));
+    // the table of a wide $lut would have too many rows
+    for width in ["64", "4000000000", &format!("\"{}\"", "1".repeat(70))] {
+        assert!(matches!(
+            Library::new().add_yosys_json(&module(&format!(
+                "\"c\": {{\"type\": \"$lut\", \"parameters\": {{\"LUT\": \"01\", \"WIDTH\": {}}}, \"connections\": {{\"A\": [2], \"Y\": [4]}}}}",
+                width
+            ))),
+            Err(Error::Unsupported { part, .. }) if part == "c"
+        ));
+    }
 }
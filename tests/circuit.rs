@@ -1,4 +1,6 @@
-use hardware_sim::{ChipDef, Circuit, LookupTable};
+use hardware_sim::{
+    ChipDef, Circuit, CircuitBuilder, Component, Diagnostic, Error, Library, LookupTable, Mode,
+};
 use std::collections::HashMap;
 
 #[test]
@@ -116,3 +118,1163 @@ fn and_from_nand() {
     assert_eq!(circuit.tick(), Ok(()));
     assert_eq!(circuit.get("out"), Ok(false));
 }
+
+#[test]
+fn settle() {
+    let lut = LookupTable::new(
+        vec![vec![true, true, true, false]],
+        vec!["a", "b"],
+        vec!["out"],
+        "Nand",
+    )
+    .unwrap();
+
+    let mut lut_map = HashMap::new();
+    lut_map.insert("Nand".to_string(), lut);
+
+    let def = ChipDef::new(
+        "And",
+        vec!["a", "b"],
+        vec!["out"],
+        vec![
+            (
+                vec![("a", "nand"), ("b", "nand")],
+                vec![("out", "out")],
+                "Nand",
+            ),
+            (vec![("a", "a"), ("b", "b")], vec![("out", "nand")], "Nand"),
+        ],
+    );
+
+    let mut circuit = Circuit::new(def, lut_map).unwrap();
+
+    // a chain settles in one delta cycle and nothing changes after that
+    assert_eq!(circuit.settle(), Ok(1));
+    assert_eq!(circuit.get("out"), Ok(false));
+    assert_eq!(circuit.settle(), Ok(0));
+    assert_eq!(circuit.set("a", true), Ok(()));
+    assert_eq!(circuit.set("b", true), Ok(()));
+    assert_eq!(circuit.settle(), Ok(1));
+    assert_eq!(circuit.get("out"), Ok(true));
+    assert_eq!(circuit.set("b", false), Ok(()));
+    assert_eq!(circuit.settle(), Ok(1));
+    assert_eq!(circuit.get("out"), Ok(false));
+
+    let mut adder = ripple_adder(4);
+    for (a, b) in [(3, 5), (15, 1), (7, 7), (0, 0)] {
+        assert_eq!(adder.set_bus("a", a), Ok(()));
+        assert_eq!(adder.set_bus("b", b), Ok(()));
+        assert!(adder.settle().unwrap() <= 1);
+        assert_eq!(adder.get_bus("out"), Ok((a + b) % 16));
+        assert_eq!(adder.get("carry"), Ok(a + b >= 16));
+    }
+}
+
+#[test]
+fn settle_loop() {
+    let not = LookupTable::new(vec![vec![true, false]], vec!["in"], vec!["out"], "Not").unwrap();
+    let or = LookupTable::new(
+        vec![vec![false, true, true, true]],
+        vec!["in1", "in2"],
+        vec!["out"],
+        "Or",
+    )
+    .unwrap();
+    let mut lut_map = HashMap::new();
+    lut_map.insert("Not".to_string(), not);
+    lut_map.insert("Or".to_string(), or);
+
+    let def = ChipDef::new(
+        "Clock",
+        vec!["stop"],
+        vec!["out"],
+        vec![
+            (vec![("in", "or")], vec![("out", "out")], "Not"),
+            (
+                vec![("in1", "stop"), ("in2", "out")],
+                vec![("out", "or")],
+                "Or",
+            ),
+        ],
+    );
+    let mut circuit = Circuit::new(def, lut_map).unwrap();
+    assert_eq!(circuit.settle_limit(), 1000);
+    circuit.set_settle_limit(10);
+
+    // the not and the or oscillate until stop is set
+    assert_eq!(
+        circuit.settle(),
+        Err(Error::CombinationalLoop(circuit.instance_names()))
+    );
+    assert_eq!(circuit.set("stop", true), Ok(()));
+    assert!(circuit.settle().is_ok());
+    assert_eq!(circuit.get("out"), Ok(false));
+    assert_eq!(circuit.settle(), Ok(0));
+}
+
+fn latch(reversed: bool) -> Circuit {
+    let mut library = Library::new();
+    library.add_lut(LookupTable::from_expr("out = !in", vec!["in"], "Not").unwrap());
+    library.add_lut(LookupTable::from_expr("out = a | b", vec!["a", "b"], "Or").unwrap());
+    library.add_lut(LookupTable::from_expr("out = a & b", vec!["a", "b"], "And").unwrap());
+
+    let mut parts = vec![
+        (vec![("in", "reset")], vec![("out", "keep")], "Not"),
+        (
+            vec![("a", "output"), ("b", "set")],
+            vec![("out", "held")],
+            "Or",
+        ),
+        (
+            vec![("a", "keep"), ("b", "held")],
+            vec![("out", "output")],
+            "And",
+        ),
+    ];
+    if reversed {
+        parts.reverse();
+    }
+    let def = ChipDef::new("Latch", vec!["set", "reset"], vec!["output"], parts);
+    Circuit::with_library(def, &library).unwrap()
+}
+
+#[test]
+fn synchronous() {
+    let mut traces = Vec::new();
+    for reversed in [false, true] {
+        let mut circuit = latch(reversed);
+        assert_eq!(circuit.mode(), Mode::Event);
+        circuit.set_mode(Mode::Synchronous);
+
+        let mut trace = Vec::new();
+        for (set, reset) in [(false, false), (true, false), (false, false), (false, true)] {
+            assert_eq!(circuit.set("set", set), Ok(()));
+            assert_eq!(circuit.set("reset", reset), Ok(()));
+            for _ in 0..3 {
+                assert_eq!(circuit.tick(), Ok(()));
+                trace.push(circuit.get("output").unwrap());
+            }
+        }
+        traces.push(trace);
+    }
+    // every lookup table reads the previous step so the order of the parts dose not matter
+    assert_eq!(traces[0], traces[1]);
+    assert_eq!(
+        traces[0],
+        vec![false, false, false, false, false, true, true, true, true, true, true, false]
+    );
+
+    // set reaches the output in 3 steps and the or reads the output in a 4th
+    let mut circuit = latch(false);
+    circuit.set_mode(Mode::Synchronous);
+    assert_eq!(circuit.settle(), Ok(2));
+    assert_eq!(circuit.set("set", true), Ok(()));
+    assert_eq!(circuit.settle(), Ok(4));
+    assert_eq!(circuit.get("output"), Ok(true));
+}
+
+#[test]
+fn run_until() {
+    let mut library = Library::new();
+    library.add_lut(LookupTable::from_expr("out = !in", vec!["in"], "Not").unwrap());
+    library.add_lut(LookupTable::from_expr("out = a & b", vec!["a", "b"], "And").unwrap());
+    let def = ChipDef::new(
+        "Hazard",
+        vec!["a"],
+        vec!["out"],
+        vec![
+            (vec![("in", "a")], vec![("out", "not_a")], "Not"),
+            (
+                vec![("a", "a"), ("b", "not_a")],
+                vec![("out", "out")],
+                "And",
+            ),
+        ],
+    );
+    let mut circuit = Circuit::with_library(def, &library).unwrap();
+    let names = circuit.instance_names();
+
+    assert_eq!(
+        circuit.set_delay("nand", 2),
+        Err(Error::UnknownInstance("nand".to_string()))
+    );
+    assert_eq!(
+        circuit.set_delay(&names[0], 0),
+        Err(Error::ZeroDelay(names[0].clone()))
+    );
+    assert_eq!(circuit.set_delay(&names[0], 2), Ok(()));
+
+    assert_eq!(circuit.run_until(10), Ok(vec![]));
+    assert_eq!(circuit.time(), 10);
+
+    // a & !a is always 0 but the not is slower than the wire
+    assert_eq!(circuit.set("a", true), Ok(()));
+    assert_eq!(
+        circuit.run_until(20),
+        Ok(vec![
+            (12, "out".to_string(), true),
+            (14, "out".to_string(), false)
+        ])
+    );
+    assert_eq!(circuit.set("a", false), Ok(()));
+    assert_eq!(circuit.run_until(30), Ok(vec![]));
+    assert_eq!(circuit.time(), 30);
+
+    // the changes after `time` are kept for the next call
+    assert_eq!(circuit.set("a", true), Ok(()));
+    assert_eq!(
+        circuit.run_until(32),
+        Ok(vec![(32, "out".to_string(), true)])
+    );
+    assert_eq!(
+        circuit.run_until(40),
+        Ok(vec![(34, "out".to_string(), false)])
+    );
+
+    // without delays the pulse is not visible
+    assert_eq!(circuit.set("a", false), Ok(()));
+    for _ in 0..4 {
+        assert_eq!(circuit.tick(), Ok(()));
+        assert_eq!(circuit.get("out"), Ok(false));
+    }
+    assert_eq!(circuit.set("a", true), Ok(()));
+    for _ in 0..4 {
+        assert_eq!(circuit.tick(), Ok(()));
+        assert_eq!(circuit.get("out"), Ok(false));
+    }
+}
+
+#[test]
+fn clocked_primitives() {
+    let src = "
+        CHIP Not { IN in; OUT out; BUILTIN Not; }
+        CHIP And { IN a, b; OUT out; BUILTIN And; }
+        CHIP Or { IN a, b; OUT out; BUILTIN Or; }
+        CHIP Xor { IN a, b; OUT out; BUILTIN Xor; }
+        CHIP Mux { IN a, b, sel; OUT out; BUILTIN Mux; }
+        CHIP HalfAdder { IN a, b; OUT sum, carry; BUILTIN HalfAdder; }
+
+        CHIP Bit {
+            IN in, load, clk;
+            OUT out;
+            PARTS:
+            Mux(a=q, b=in, sel=load, out=d);
+            DFFC(in=d, clk=clk, out=q, out=out);
+        }
+
+        CHIP Register {
+            IN in[4], load, clk;
+            OUT out[4];
+            PARTS:
+            Bit(in=in[0], load=load, clk=clk, out=out[0]);
+            Bit(in=in[1], load=load, clk=clk, out=out[1]);
+            Bit(in=in[2], load=load, clk=clk, out=out[2]);
+            Bit(in=in[3], load=load, clk=clk, out=out[3]);
+        }
+
+        // the output of a chip drives two wires
+        CHIP Copy {
+            IN in[4], load, clk;
+            OUT out[4], copy[4];
+            PARTS:
+            Register(in=in, load=load, clk=clk, out=out, out=copy);
+        }
+
+        CHIP DFF { IN in; OUT out; BUILTIN DFF; CLOCKED in; }
+        CHIP Delay { IN in; OUT out; BUILTIN DFF; CLOCKED in; }
+
+        CHIP Nine {
+            IN load, clk;
+            OUT out[4];
+            PARTS:
+            Register(in[0]=true, in[1..2]=false, in[3]=true, load=load, clk=clk, out=out);
+        }
+
+        CHIP Inc4 {
+            IN in[4];
+            OUT out[4];
+            PARTS:
+            HalfAdder(a=in[0], b=true, sum=out[0], carry=c0);
+            HalfAdder(a=in[1], b=c0, sum=out[1], carry=c1);
+            HalfAdder(a=in[2], b=c1, sum=out[2], carry=c2);
+            Xor(a=in[3], b=c2, out=out[3]);
+        }
+
+        // the enable register loads when reset, load or inc is set
+        CHIP PC {
+            IN in[4], load, inc, reset, clk;
+            OUT out[4];
+            PARTS:
+            Inc4(in[0]=q0, in[1]=q1, in[2]=q2, in[3]=q3, out[0]=i0, out[1]=i1, out[2]=i2, out[3]=i3);
+            Mux(a=i0, b=in[0], sel=load, out=m0);
+            Mux(a=i1, b=in[1], sel=load, out=m1);
+            Mux(a=i2, b=in[2], sel=load, out=m2);
+            Mux(a=i3, b=in[3], sel=load, out=m3);
+            Mux(a=m0, b=false, sel=reset, out=d0);
+            Mux(a=m1, b=false, sel=reset, out=d1);
+            Mux(a=m2, b=false, sel=reset, out=d2);
+            Mux(a=m3, b=false, sel=reset, out=d3);
+            Or(a=load, b=inc, out=change);
+            Or(a=change, b=reset, out=enable);
+            DFFE(in=d0, load=enable, clk=clk, out=q0, out=out[0]);
+            DFFE(in=d1, load=enable, clk=clk, out=q1, out=out[1]);
+            DFFE(in=d2, load=enable, clk=clk, out=q2, out=out[2]);
+            DFFE(in=d3, load=enable, clk=clk, out=q3, out=out[3]);
+        }
+    ";
+    let mut library = Library::new();
+    assert_eq!(library.add_hdl(src), Ok(()));
+    let circuit = |name: &str| {
+        let mut circuit =
+            Circuit::with_library(library.chip(name).unwrap().clone(), &library).unwrap();
+        assert!(circuit.settle().is_ok());
+        circuit
+    };
+    // the inputs settle before the rising and the falling edge of the clock
+    let clock = |circuit: &mut Circuit| {
+        assert!(circuit.settle().is_ok());
+        assert_eq!(circuit.set("clk", true), Ok(()));
+        assert!(circuit.settle().is_ok());
+        assert_eq!(circuit.set("clk", false), Ok(()));
+        assert!(circuit.settle().is_ok());
+    };
+
+    let mut bit = circuit("Bit");
+    assert_eq!(bit.set("in", true), Ok(()));
+    assert_eq!(bit.set("load", true), Ok(()));
+    assert!(bit.settle().is_ok());
+    assert_eq!(bit.get("out"), Ok(false));
+    clock(&mut bit);
+    assert_eq!(bit.get("out"), Ok(true));
+    assert_eq!(bit.set("load", false), Ok(()));
+    assert_eq!(bit.set("in", false), Ok(()));
+    clock(&mut bit);
+    assert_eq!(bit.get("out"), Ok(true));
+    // only the rising edge stores a value
+    assert_eq!(bit.set("load", true), Ok(()));
+    assert_eq!(bit.set("clk", true), Ok(()));
+    assert!(bit.settle().is_ok());
+    assert_eq!(bit.get("out"), Ok(false));
+    assert_eq!(bit.set("in", true), Ok(()));
+    assert!(bit.settle().is_ok());
+    assert_eq!(bit.get("out"), Ok(false));
+
+    let mut register = circuit("Register");
+    assert_eq!(register.set_bus("in", 9), Ok(()));
+    clock(&mut register);
+    assert_eq!(register.get_bus("out"), Ok(0));
+    assert_eq!(register.set("load", true), Ok(()));
+    clock(&mut register);
+    assert_eq!(register.get_bus("out"), Ok(9));
+
+    let mut copy = circuit("Copy");
+    assert_eq!(copy.set_bus("in", 5), Ok(()));
+    assert_eq!(copy.set("load", true), Ok(()));
+    clock(&mut copy);
+    assert_eq!(copy.get_bus("out"), Ok(5));
+    assert_eq!(copy.get_bus("copy"), Ok(5));
+
+    let mut nine = circuit("Nine");
+    assert_eq!(nine.set("load", true), Ok(()));
+    clock(&mut nine);
+    assert_eq!(nine.get_bus("out"), Ok(9));
+
+    // the clocked builtin DFF is the primitive clocked by the tick
+    assert!(library.chip("DFF").is_none());
+    let mut delay = circuit("Delay");
+    assert_eq!(delay.set("in", true), Ok(()));
+    assert_eq!(delay.tick(), Ok(()));
+    assert_eq!(delay.get("out"), Ok(false));
+    assert_eq!(delay.tick(), Ok(()));
+    assert_eq!(delay.get("out"), Ok(true));
+
+    // the synchronous mode gives the same counts
+    for mode in [Mode::Event, Mode::Synchronous] {
+        let mut pc = circuit("PC");
+        pc.set_mode(mode);
+        assert_eq!(pc.set("inc", true), Ok(()));
+        for i in 1..20 {
+            clock(&mut pc);
+            assert_eq!(pc.get_bus("out"), Ok(i % 16));
+        }
+        assert_eq!(pc.set_bus("in", 7), Ok(()));
+        assert_eq!(pc.set("load", true), Ok(()));
+        clock(&mut pc);
+        assert_eq!(pc.get_bus("out"), Ok(7));
+        assert_eq!(pc.set("load", false), Ok(()));
+        clock(&mut pc);
+        assert_eq!(pc.get_bus("out"), Ok(8));
+        assert_eq!(pc.set("reset", true), Ok(()));
+        clock(&mut pc);
+        assert_eq!(pc.get_bus("out"), Ok(0));
+        assert_eq!(pc.set("inc", false), Ok(()));
+        assert_eq!(pc.set("reset", false), Ok(()));
+        clock(&mut pc);
+        assert_eq!(pc.get_bus("out"), Ok(0));
+    }
+
+    // the enable registers are clocked by the clk input and no clock is added
+    let pc = circuit("PC");
+    let verilog = pc.to_verilog().unwrap();
+    assert_eq!(verilog.matches("always @(posedge clk) if (").count(), 4);
+    assert!(!verilog.contains("clk_"));
+    let def = library.chip("Bit").unwrap();
+    let verilog = def.to_verilog(&library).unwrap();
+    assert!(verilog.contains("module DFFC (\n"));
+    assert!(verilog.contains("    always @(posedge clk) state <= in;\n"));
+
+    // the blif latches are clocked by the clk input
+    let blif = pc.to_blif().unwrap();
+    assert_eq!(blif.matches(" re clk 0\n").count(), 4);
+    let mut imported = Library::new();
+    assert_eq!(imported.add_blif(&blif), Ok(()));
+    let mut expected = pc;
+    let mut pc = Circuit::with_library(imported.chip("PC").unwrap().clone(), &imported).unwrap();
+    for (load, inc, reset) in [
+        (false, true, false),
+        (true, false, false),
+        (false, true, true),
+    ] {
+        for pc in [&mut expected, &mut pc] {
+            assert_eq!(pc.set_bus("in", 5), Ok(()));
+            assert_eq!(pc.set("load", load), Ok(()));
+            assert_eq!(pc.set("inc", inc), Ok(()));
+            assert_eq!(pc.set("reset", reset), Ok(()));
+            for _ in 0..3 {
+                clock(pc);
+            }
+        }
+        assert_eq!(pc.get_bus("out"), expected.get_bus("out"));
+    }
+
+    // the latches are transparent
+    let def = ChipDef::new(
+        "Latches",
+        vec!["s", "r", "in", "en"],
+        vec!["sr", "d"],
+        vec![
+            (vec![("s", "s"), ("r", "r")], vec![("out", "sr")], "SRLatch"),
+            (
+                vec![("in", "in"), ("en", "en")],
+                vec![("out", "d")],
+                "DLatch",
+            ),
+        ],
+    );
+    let mut latches = Circuit::with_library(def, &Library::new()).unwrap();
+    let mut outputs = Vec::new();
+    for (s, r, d, en) in [
+        (true, false, true, false),
+        (false, false, true, true),
+        (false, false, false, true),
+        (true, true, true, false),
+        (false, false, false, false),
+    ] {
+        assert_eq!(latches.set("s", s), Ok(()));
+        assert_eq!(latches.set("r", r), Ok(()));
+        assert_eq!(latches.set("in", d), Ok(()));
+        assert_eq!(latches.set("en", en), Ok(()));
+        assert!(latches.settle().is_ok());
+        outputs.push((latches.get("sr").unwrap(), latches.get("d").unwrap()));
+    }
+    assert_eq!(
+        outputs,
+        vec![
+            (true, false),
+            (true, true),
+            (true, false),
+            (false, false),
+            (false, false)
+        ]
+    );
+
+    // the blif latches are enabled by their control and give the same outputs
+    let blif = latches.to_blif().unwrap();
+    assert!(blif.contains(".latch in d ah en 0\n"));
+    let mut library = Library::new();
+    assert_eq!(library.add_blif(&blif), Ok(()));
+    let mut imported =
+        Circuit::with_library(library.chip("Latches").unwrap().clone(), &library).unwrap();
+    for (s, r, d, en) in [
+        (true, false, true, false),
+        (false, false, true, true),
+        (false, false, false, true),
+        (true, true, true, false),
+        (false, false, false, false),
+    ] {
+        assert_eq!(imported.set("s", s), Ok(()));
+        assert_eq!(imported.set("r", r), Ok(()));
+        assert_eq!(imported.set("in", d), Ok(()));
+        assert_eq!(imported.set("en", en), Ok(()));
+        assert!(imported.settle().is_ok());
+        outputs.remove(0);
+        outputs.push((imported.get("sr").unwrap(), imported.get("d").unwrap()));
+    }
+    assert_eq!(
+        outputs,
+        vec![
+            (true, false),
+            (true, true),
+            (true, false),
+            (false, false),
+            (false, false)
+        ]
+    );
+    let verilog = latches.to_verilog().unwrap();
+    assert!(verilog.contains("always @(*) if (en) \\dlatch_0.out  = in;\n"));
+}
+
+#[test]
+fn instance_names() {
+    let lut = LookupTable::new(
+        vec![vec![true, true, true, false]],
+        vec!["a", "b"],
+        vec!["out"],
+        "Nand",
+    )
+    .unwrap();
+
+    let mut lut_map = HashMap::new();
+    lut_map.insert("Nand".to_string(), lut);
+
+    let mut def = ChipDef::new(
+        "And",
+        vec!["a", "b"],
+        vec!["out"],
+        vec![
+            (
+                vec![("a", "nand"), ("b", "nand")],
+                vec![("out", "out")],
+                "Nand",
+            ),
+            (vec![("a", "a"), ("b", "b")], vec![("out", "nand")], "Nand"),
+        ],
+    );
+
+    assert_eq!(def.instance_names(), vec!["nand_0", "nand_1"]);
+    assert_eq!(def.set_instance(0, "not"), Ok(()));
+    assert!(def.set_instance(2, "missing").is_err());
+    assert_eq!(def.instance_names(), vec!["not", "nand_0"]);
+
+    let mut circuit = Circuit::new(def, lut_map).unwrap();
+    assert_eq!(circuit.instance_names(), vec!["not", "nand_0"]);
+
+    assert_eq!(circuit.set("a", true), Ok(()));
+    assert_eq!(circuit.set("b", true), Ok(()));
+    assert_eq!(circuit.tick(), Ok(()));
+    assert_eq!(circuit.tick(), Ok(()));
+    assert_eq!(circuit.instance("nand_0").unwrap().outputs(), vec![false]);
+    assert_eq!(circuit.instance("not").unwrap().outputs(), vec![true]);
+    assert!(circuit.instance("nand_1").is_err());
+}
+
+#[test]
+fn add4() {
+    // the sum and carry of a full adder do not depend on the order of the inputs
+    let full_adder = LookupTable::new(
+        vec![
+            vec![false, true, true, false, true, false, false, true],
+            vec![false, false, false, true, false, true, true, true],
+        ],
+        vec!["a", "b", "c"],
+        vec!["sum", "carry"],
+        "FullAdder",
+    )
+    .unwrap();
+    let half_adder = LookupTable::new(
+        vec![
+            vec![false, true, true, false],
+            vec![false, false, false, true],
+        ],
+        vec!["a", "b"],
+        vec!["sum", "carry"],
+        "HalfAdder",
+    )
+    .unwrap();
+
+    let add4 = ChipDef::new(
+        "Add4",
+        vec!["a[4]", "b[4]"],
+        vec!["out[4]"],
+        vec![
+            (
+                vec![("a", "a[0]"), ("b", "b[0]")],
+                vec![("sum", "out[0]"), ("carry", "c0")],
+                "HalfAdder",
+            ),
+            (
+                vec![("a", "a[1]"), ("b", "b[1]"), ("c", "c0")],
+                vec![("sum", "out[1]"), ("carry", "c1")],
+                "FullAdder",
+            ),
+            (
+                vec![("a", "a[2]"), ("b", "b[2]"), ("c", "c1")],
+                vec![("sum", "out[2]"), ("carry", "c2")],
+                "FullAdder",
+            ),
+            (
+                vec![("a", "a[3]"), ("b", "b[3]"), ("c", "c2")],
+                vec![("sum", "out[3]")],
+                "FullAdder",
+            ),
+        ],
+    );
+    assert_eq!(add4.inputs(), vec!["a", "b"]);
+    assert_eq!(add4.width("a"), 4);
+    assert_eq!(add4.width("c0"), 1);
+
+    // only keeps the upper two bits of the sum
+    let high2 = ChipDef::new_string(
+        "High2".to_string(),
+        vec!["x[4]".to_string(), "y[4]".to_string()],
+        vec!["out[2]".to_string(), "low".to_string()],
+        vec![
+            Component::Def {
+                inputs: vec![
+                    ("a".to_string(), "x".to_string()),
+                    ("b".to_string(), "y".to_string()),
+                ],
+                outputs: vec![("out".to_string(), "sum".to_string())],
+                name: "Add4".to_string(),
+            },
+            Component::Map {
+                var_map: vec![
+                    ("sum[2..3]".to_string(), "out".to_string()),
+                    ("sum[0]".to_string(), "low".to_string()),
+                ],
+                name: "high".to_string(),
+            },
+        ],
+    );
+
+    let mut library = Library::new();
+    library.add_lut(full_adder);
+    library.add_lut(half_adder);
+    library.add_chip(add4.clone());
+
+    let mut adder = Circuit::with_library(add4, &library).unwrap();
+    let mut high = Circuit::with_library(high2, &library).unwrap();
+    assert_eq!(adder.in_names().len(), 8);
+
+    for a in 0..16 {
+        for b in 0..16 {
+            assert_eq!(adder.set_bus("a", a), Ok(()));
+            assert_eq!(adder.set_bus("b", b), Ok(()));
+            assert_eq!(high.set_bus("x", a), Ok(()));
+            assert_eq!(high.set_bus("y", b), Ok(()));
+            for _ in 0..8 {
+                assert_eq!(adder.tick(), Ok(()));
+                assert_eq!(high.tick(), Ok(()));
+            }
+            assert_eq!(adder.get_bus("out"), Ok((a + b) % 16));
+            assert_eq!(high.get_bus("out"), Ok(((a + b) % 16) >> 2));
+            assert_eq!(high.get("low"), Ok((a + b) % 2 == 1));
+        }
+    }
+
+    assert!(adder.set_bus("a", 16).is_err());
+    assert!(adder.set_bus("out", 1).is_err());
+    assert!(adder.get_bus("a").is_err());
+}
+
+#[test]
+fn bus_width_mismatch() {
+    let not = LookupTable::new(vec![vec![true, false]], vec!["in"], vec!["out"], "Not").unwrap();
+    let mut lut_map = HashMap::new();
+    lut_map.insert("Not".to_string(), not);
+
+    let def = ChipDef::new(
+        "Not2",
+        vec!["in[2]"],
+        vec!["out[2]"],
+        vec![(vec![("in", "in")], vec![("out", "out[0]")], "Not")],
+    );
+    assert!(Circuit::new(def, lut_map.clone()).is_err());
+
+    let def = ChipDef::new(
+        "Not2",
+        vec!["in[2]"],
+        vec!["out[2]"],
+        vec![(vec![("in", "in[2]")], vec![("out", "out[0]")], "Not")],
+    );
+    assert!(Circuit::new(def, lut_map).is_err());
+}
+
+#[test]
+fn name_order() {
+    let lut = LookupTable::new(vec![vec![false, true]], vec!["in"], vec!["out"], "Buffer").unwrap();
+
+    let mut lut_map = HashMap::new();
+    lut_map.insert("Buffer".to_string(), lut);
+
+    let names = vec!["z", "y", "x", "w", "v", "u"];
+    let def = ChipDef::new(
+        "Buffers",
+        names.iter().map(|name| &name[..]).collect(),
+        vec!["out[6]"],
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let out = ["out[0]", "out[1]", "out[2]", "out[3]", "out[4]", "out[5]"][i];
+                (vec![("in", *name)], vec![("out", out)], "Buffer")
+            })
+            .collect(),
+    );
+
+    let circuit = Circuit::new(def, lut_map).unwrap();
+    assert_eq!(circuit.in_names(), names);
+    assert_eq!(
+        circuit.out_names(),
+        vec!["out[0]", "out[1]", "out[2]", "out[3]", "out[4]", "out[5]"]
+    );
+
+    let lut = circuit.to_lut().unwrap().unwrap();
+    assert_eq!(lut.in_names(), names);
+    assert_eq!(
+        lut.out_names(),
+        vec!["out[0]", "out[1]", "out[2]", "out[3]", "out[4]", "out[5]"]
+    );
+}
+
+fn ripple_adder(width: usize) -> Circuit {
+    let full_adder = LookupTable::new(
+        vec![
+            vec![false, true, true, false, true, false, false, true],
+            vec![false, false, false, true, false, true, true, true],
+        ],
+        vec!["a", "b", "c"],
+        vec!["sum", "carry"],
+        "FullAdder",
+    )
+    .unwrap();
+    let zero = LookupTable::new(vec![vec![false, false]], vec!["in"], vec!["out"], "Zero").unwrap();
+
+    let mut builder = CircuitBuilder::new("Adder");
+    let a = builder.input_bus("a", width);
+    let b = builder.input_bus("b", width);
+    let out = builder.output_bus("out", width);
+    let carry = builder.output("carry");
+
+    let zero = builder.add_lut("zero", zero);
+    builder.connect(a[0], (zero, "in"));
+
+    let mut carry_in = (zero, "out");
+    for i in 0..width {
+        let adder = builder.add_lut(&format!("adder_{}", i), full_adder.clone());
+        builder
+            .connect(a[i], (adder, "a"))
+            .connect(b[i], (adder, "b"))
+            .connect(carry_in, (adder, "c"))
+            .connect((adder, "sum"), out[i]);
+        carry_in = (adder, "carry");
+    }
+    builder.connect(carry_in, carry);
+
+    builder.build().unwrap()
+}
+
+#[test]
+fn builder() {
+    let mut adder = ripple_adder(3);
+    assert_eq!(adder.in_names().len(), 6);
+    assert_eq!(adder.instance_names().len(), 4);
+
+    for a in 0..8 {
+        for b in 0..8 {
+            assert_eq!(adder.set_bus("a", a), Ok(()));
+            assert_eq!(adder.set_bus("b", b), Ok(()));
+            for _ in 0..8 {
+                assert_eq!(adder.tick(), Ok(()));
+            }
+            assert_eq!(adder.get_bus("out"), Ok((a + b) % 8));
+            assert_eq!(adder.get("carry"), Ok(a + b >= 8));
+        }
+    }
+}
+
+#[test]
+fn builder_errors() {
+    let nand = LookupTable::new(
+        vec![vec![true, true, true, false]],
+        vec!["a", "b"],
+        vec!["out"],
+        "Nand",
+    )
+    .unwrap();
+
+    let mut builder = CircuitBuilder::new("Broken");
+    let a = builder.input("a");
+    let b = builder.input("b");
+    let out = builder.output("out");
+    builder.output("unused");
+    let nand = builder.add_lut("nand", nand);
+    builder
+        .connect(a, (nand, "a"))
+        .connect(b, (nand, "a"))
+        .connect(a, (nand, "c"))
+        .connect((nand, "out"), out);
+
+    let errors = match builder.build() {
+        Err(Error::Invalid(errors)) => errors,
+        _ => panic!("expected Error::Invalid"),
+    };
+    assert_eq!(errors.len(), 4);
+    assert!(errors.contains(&Diagnostic::UnknownPin {
+        chip: "Broken".to_string(),
+        instance: "nand".to_string(),
+        part: "Nand".to_string(),
+        pin: "c".to_string(),
+    }));
+    assert!(errors.contains(&Diagnostic::MultipleDrivers {
+        chip: "Broken".to_string(),
+        wire: "nand.a".to_string(),
+        drivers: vec!["a".to_string(), "b".to_string()],
+    }));
+    assert!(errors.contains(&Diagnostic::PinCount {
+        chip: "Broken".to_string(),
+        instance: "nand".to_string(),
+        part: "Nand".to_string(),
+        expected: vec!["a".to_string(), "b".to_string()],
+        connected: vec!["a".to_string()],
+    }));
+    assert!(errors.contains(&Diagnostic::OutputNotDriven {
+        chip: "Broken".to_string(),
+        output: "unused".to_string(),
+    }));
+
+    // handles of another builder are reported even if their index exists in this builder
+    let mut other = CircuitBuilder::new("Other");
+    let x = other.input("x");
+    let y = other.input("y");
+    let mut builder = CircuitBuilder::new("Foreign");
+    builder.input("a");
+    let out = builder.output("out");
+    let copy = builder.output("copy");
+    builder.connect(x, out).connect(y, copy);
+    let errors = match builder.build() {
+        Err(Error::Invalid(errors)) => errors,
+        _ => panic!("expected Error::Invalid"),
+    };
+    assert_eq!(
+        errors
+            .iter()
+            .filter(|error| matches!(
+                error,
+                Diagnostic::InvalidConnection { chip, .. } if chip == "Foreign"
+            ))
+            .count(),
+        2
+    );
+
+    // ports with the same name are reported instead of replacing each other
+    let mut builder = CircuitBuilder::new("Twice");
+    let a = builder.input("a");
+    builder.input("a");
+    let out = builder.output("a");
+    builder.connect(a, out);
+    let errors = match builder.build() {
+        Err(Error::Invalid(errors)) => errors,
+        _ => panic!("expected Error::Invalid"),
+    };
+    assert_eq!(
+        errors,
+        vec![
+            Diagnostic::DuplicatePort {
+                chip: "Twice".to_string(),
+                port: "a".to_string(),
+            };
+            2
+        ]
+    );
+}
+
+#[test]
+fn verilog() {
+    let mut circuit = ripple_adder(2);
+    assert_eq!(circuit.set_bus("a", 1), Ok(()));
+
+    let verilog = circuit.to_verilog().unwrap();
+    assert!(verilog.starts_with(
+        "module Adder (
+    input [1:0] a,
+    input [1:0] b,
+    output [1:0] out,
+    output carry
+);
+    wire \\zero.out ;
+    wire \\adder_0.sum ;
+    wire \\adder_0.carry ;
+"
+    ));
+    assert!(verilog.contains(
+        "    FullAdder adder_1 (
+        .a(a[1]),
+        .b(b[1]),
+        .c(\\adder_0.carry ),
+        .sum(\\adder_1.sum ),
+        .carry(\\adder_1.carry )
+    );
+"
+    ));
+    assert!(verilog.contains("    assign out[1] = \\adder_1.sum ;\n"));
+    assert!(verilog.contains("    assign carry = \\adder_1.carry ;\n"));
+    assert!(verilog.contains("module Zero (\n"));
+    assert_eq!(verilog.matches("module FullAdder (").count(), 1);
+
+    // a DFF is a register that is clocked by an added input
+    let src = ".model Toggle
+.inputs en
+.outputs q
+.names en q d
+01 1
+10 1
+.latch d q 1
+.end
+";
+    let mut library = Library::new();
+    assert_eq!(library.add_blif(src), Ok(()));
+    let toggle = Circuit::with_library(library.chip("Toggle").unwrap().clone(), &library).unwrap();
+    let verilog = toggle.to_verilog().unwrap();
+    assert!(verilog.starts_with(
+        "module Toggle (
+    input en,
+    output q,
+    input clk
+);
+    reg \\q.out ;
+"
+    ));
+    assert!(verilog.contains(
+        "    initial \\q.out  = 1'b1;
+    always @(posedge clk) \\q.out  <= \\d.out ;
+"
+    ));
+
+    // one module is written per table name so different tables need different names
+    let table = |table: Vec<bool>| {
+        LookupTable::new(vec![table], vec!["a", "b"], vec!["out"], "Gate").unwrap()
+    };
+    let mut builder = CircuitBuilder::new("Gates");
+    let a = builder.input("a");
+    let x = builder.output("x");
+    let y = builder.output("y");
+    let and = builder.add_lut("and", table(vec![false, false, false, true]));
+    let or = builder.add_lut("or", table(vec![false, true, true, true]));
+    for (gate, out) in [(and, x), (or, y)] {
+        builder
+            .connect(a, (gate, "a"))
+            .connect(a, (gate, "b"))
+            .connect((gate, "out"), out);
+    }
+    assert!(matches!(
+        builder.build().unwrap().to_verilog(),
+        Err(Error::Unsupported { part, .. }) if part == "Gate"
+    ));
+}
+
+#[test]
+fn blif() {
+    let src = ".model Acc
+.inputs a b
+.outputs y q
+.names a b t
+01 1
+10 1
+.names t q y
+11 1
+.latch y q 1
+.end
+";
+    let mut library = Library::new();
+    assert_eq!(library.add_blif(src), Ok(()));
+    let circuit = Circuit::with_library(library.chip("Acc").unwrap().clone(), &library).unwrap();
+    assert_eq!(circuit.to_blif(), Ok(src.to_string()));
+
+    // constants, multi output tables and outputs that share a driver
+    let mut adder = ripple_adder(2);
+    let blif = adder.to_blif().unwrap();
+    assert!(blif.starts_with(
+        ".model Adder
+.inputs a[0] a[1] b[0] b[1]
+.outputs out[0] out[1] carry
+.names a[0] zero
+.names a[0] b[0] zero out[0]
+001 1
+010 1
+100 1
+111 1
+.names a[0] b[0] zero adder_0.carry
+011 1
+101 1
+110 1
+111 1
+"
+    ));
+    assert!(blif.contains(".names a[1] b[1] adder_0.carry carry\n"));
+
+    let mut library = Library::new();
+    assert_eq!(library.add_blif(&blif), Ok(()));
+    let mut circuit =
+        Circuit::with_library(library.chip("Adder").unwrap().clone(), &library).unwrap();
+    for a in 0..4 {
+        for b in 0..4 {
+            for circuit in [&mut adder, &mut circuit] {
+                assert_eq!(circuit.set_bus("a", a), Ok(()));
+                assert_eq!(circuit.set_bus("b", b), Ok(()));
+                for _ in 0..4 {
+                    assert_eq!(circuit.tick(), Ok(()));
+                }
+            }
+            assert_eq!(circuit.get_bus("out"), adder.get_bus("out"));
+            assert_eq!(circuit.get("carry"), adder.get("carry"));
+            assert_eq!(circuit.get_bus("out"), Ok((a + b) % 4));
+        }
+    }
+}
+
+#[test]
+fn aiger() {
+    let half_adder = "aag 7 2 0 2 3
+2
+4
+6
+12
+6 13 15
+12 2 4
+14 3 5
+i0 x
+i1 y
+o0 s
+o1 c
+c
+half adder
+";
+    let mut circuit = Circuit::from_aiger(half_adder.as_bytes(), "HalfAdder").unwrap();
+    assert_eq!(circuit.in_names(), vec!["x", "y"]);
+    assert_eq!(circuit.instance_names(), vec!["n3", "n6", "n7"]);
+    for (x, y) in [(false, false), (false, true), (true, false), (true, true)] {
+        assert_eq!(circuit.set("x", x), Ok(()));
+        assert_eq!(circuit.set("y", y), Ok(()));
+        for _ in 0..3 {
+            assert_eq!(circuit.tick(), Ok(()));
+        }
+        assert_eq!(circuit.get("s"), Ok(x != y));
+        assert_eq!(circuit.get("c"), Ok(x && y));
+    }
+
+    // line breaks may be written as \r\n
+    let crlf = half_adder.replace('\n', "\r\n");
+    let windows = Circuit::from_aiger(crlf.as_bytes(), "HalfAdder").unwrap();
+    assert_eq!(windows.in_names(), vec!["x", "y"]);
+    assert_eq!(windows.out_names(), vec!["s", "c"]);
+
+    // only names written like a bit of a bus become a bus
+    let src = "aag 2 2 0 0 0\n2\n4\ni0 a[0]\ni1 a[01]\n";
+    let bits = Circuit::from_aiger(src.as_bytes(), "Bits").unwrap();
+    assert_eq!(bits.in_names(), vec!["a[0]", "a[01]"]);
+
+    // every and is kept, the inverters are part of the ands
+    assert_eq!(
+        circuit.to_aag(),
+        Ok("aag 5 2 0 2 3
+2
+4
+10
+6
+6 4 2
+8 5 3
+10 9 7
+i0 x
+i1 y
+o0 s
+o1 c
+"
+        .to_string())
+    );
+    let aig = circuit.to_aig().unwrap();
+    assert!(aig.starts_with(b"aig 5 2 0 2 3\n10\n6\n\x02\x02\x03\x02\x01\x02i0 x\n"));
+    let mut circuit = Circuit::from_aiger(&aig, "HalfAdder").unwrap();
+    assert_eq!(circuit.set("x", true), Ok(()));
+    for _ in 0..3 {
+        assert_eq!(circuit.tick(), Ok(()));
+    }
+    assert_eq!(circuit.get("s"), Ok(true));
+    assert_eq!(circuit.get("c"), Ok(false));
+
+    // a toggle flip flop with an unnamed output
+    let mut toggle = Circuit::from_aiger(b"aag 1 0 1 2 0\n2 3\n2\n3\nl0 Q\no0 Q\n", "T").unwrap();
+    assert_eq!(toggle.out_names(), vec!["Q", "o1"]);
+    for i in 0..4 {
+        assert_eq!(toggle.tick(), Ok(()));
+        assert_eq!(toggle.get("Q"), Ok(i % 2 == 1));
+        assert_eq!(toggle.get("o1"), Ok(i % 2 == 0));
+    }
+    assert_eq!(
+        toggle.to_aag(),
+        Ok("aag 1 0 1 2 0\n2 3\n2\n3\nl0 Q\no0 Q\no1 o1\n".to_string())
+    );
+
+    let mut adder = ripple_adder(2);
+    let mut circuit = Circuit::from_aiger(&adder.to_aig().unwrap(), "Adder").unwrap();
+    for a in 0..4 {
+        for b in 0..4 {
+            for circuit in [&mut adder, &mut circuit] {
+                assert_eq!(circuit.set_bus("a", a), Ok(()));
+                assert_eq!(circuit.set_bus("b", b), Ok(()));
+                for _ in 0..8 {
+                    assert_eq!(circuit.tick(), Ok(()));
+                }
+            }
+            assert_eq!(circuit.get_bus("out"), adder.get_bus("out"));
+            assert_eq!(circuit.get("carry"), adder.get("carry"));
+        }
+    }
+}
+
+#[test]
+fn aiger_errors() {
+    let error = |src: &str| match Circuit::from_aiger(src.as_bytes(), "m") {
+        Err(Error::Parse { line, column, .. }) => Some((line, column)),
+        Err(_) => Some((0, 0)),
+        Ok(_) => None,
+    };
+
+    assert_eq!(error("aag 1 1 0 1 0\n2\n2\n"), None);
+    assert_eq!(error("aig2 1 1 0 1 0\n"), Some((1, 1)));
+    assert_eq!(error("aag 1 x 0 1 0\n"), Some((1, 7)));
+    assert_eq!(error("aag 1 1 0 1 0\n3\n2\n"), Some((2, 1)));
+    assert_eq!(error("aag 1 1 0 1 0\n2\n"), Some((3, 1)));
+    assert_eq!(error("aag 2 1 0 1 0\n2\n4\n"), Some((3, 1)));
+    assert_eq!(error("aag 1 1 0 1 0\n2\n6\n"), Some((3, 1)));
+    assert_eq!(error("aag 3 1 0 1 1\n2\n6\n6 2 8\n"), Some((4, 5)));
+    assert_eq!(error("aag 1 0 1 0 0\n2 2 5\n"), Some((2, 5)));
+    assert_eq!(error("aag 1 1 0 0 0\n2\ni1 x\n"), Some((3, 1)));
+    assert_eq!(error("aig 1 1 0 0 1\n"), Some((1, 1)));
+    assert_eq!(error("aag 1 1 0 0 0 0 0 1\n2\n"), Some((1, 1)));
+    // the second delta of a binary and is missing
+    assert!(matches!(
+        Circuit::from_aiger(b"aig 3 2 0 1 1\n6\n\x02", "m"),
+        Err(Error::Parse { msg, .. }) if msg == "the ands end too early"
+    ));
+
+    let mut builder = CircuitBuilder::new("Loop");
+    let out = builder.output("out");
+    let or = LookupTable::new(
+        vec![vec![false, true, true, true]],
+        vec!["a", "b"],
+        vec!["out"],
+        "Or",
+    )
+    .unwrap();
+    let input = builder.input("in");
+    let or = builder.add_lut("or", or);
+    builder
+        .connect(input, (or, "a"))
+        .connect((or, "out"), (or, "b"))
+        .connect((or, "out"), out);
+    assert_eq!(
+        builder.build().unwrap().to_aag(),
+        Err(Error::CombinationalLoop(vec!["or".to_string()]))
+    );
+}
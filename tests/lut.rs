@@ -1,4 +1,4 @@
-use hardware_sim::LookupTable;
+use hardware_sim::{ChipDef, Circuit, DontCarePolicy, Error, Form, Library, LookupTable};
 
 #[test]
 fn nand() {
@@ -57,3 +57,475 @@ fn common_3_in_1() {
     assert_eq!(common.get("or"), Ok(true));
     assert_eq!(common.get("xor"), Ok(false));
 }
+
+#[test]
+fn errors() {
+    assert_eq!(
+        LookupTable::new(vec![vec![true, false]], vec!["a"], vec![], "Empty"),
+        Err(Error::Shape {
+            expected: 0,
+            found: 1
+        })
+    );
+    assert_eq!(
+        LookupTable::new(vec![vec![true, false]], vec!["a", "b"], vec!["out"], "Nand"),
+        Err(Error::Shape {
+            expected: 4,
+            found: 2
+        })
+    );
+
+    let mut not = LookupTable::new(vec![vec![true, false]], vec!["a"], vec!["out"], "Not").unwrap();
+    let err = not.set("b", true).unwrap_err();
+    assert_eq!(err, Error::UnknownPort("b".to_string()));
+    assert_eq!(err.to_string(), "no port named b");
+    assert_eq!(not.set_id(1, true), Err(Error::Index { index: 1, len: 1 }));
+    assert_eq!(not.get_id(1), Err(Error::Index { index: 1, len: 1 }));
+
+    let err: Box<dyn std::error::Error> = Box::new(err);
+    assert!(err.source().is_none());
+}
+
+#[test]
+fn name_order() {
+    let names = vec!["e", "d", "c", "b", "a"];
+    let lut = LookupTable::new(
+        vec![vec![false; 32]; 5],
+        names.clone(),
+        names.clone(),
+        "Order",
+    )
+    .unwrap();
+
+    assert_eq!(lut.in_names(), names);
+    assert_eq!(lut.out_names(), names);
+}
+
+#[test]
+fn verilog() {
+    let not = LookupTable::new(vec![vec![true, false]], vec!["in"], vec!["out"], "Not").unwrap();
+    assert_eq!(
+        not.to_verilog(),
+        "module Not (
+    input in,
+    output out
+);
+    wire [1:0] out_table = 2'b01;
+    assign out = out_table[in];
+endmodule
+"
+    );
+
+    let lut = LookupTable::new(
+        vec![vec![true, true, true, false], vec![false, true, true, true]],
+        vec!["a", "b"],
+        vec!["out[0]", "out[1]"],
+        "Gates",
+    )
+    .unwrap();
+    let verilog = lut.to_verilog();
+    assert!(verilog.contains("    output \\out[0] ,\n"));
+    assert!(verilog.contains("    wire [3:0] \\out[0]_table  = 4'b0111;\n"));
+    assert!(verilog.contains("    wire [3:0] \\out[1]_table  = 4'b1110;\n"));
+}
+
+#[test]
+fn from_expr() {
+    let mut lut = LookupTable::from_expr(
+        "out = a & !b | c; carry = (a ^ b) & ~0;\nnone = 0;",
+        vec!["a", "b", "c"],
+        "Expr",
+    )
+    .unwrap();
+    assert_eq!(lut.in_names(), vec!["a", "b", "c"]);
+    assert_eq!(lut.out_names(), vec!["out", "carry", "none"]);
+
+    for i in 0..8 {
+        let (a, b, c) = (i & 1 == 1, i & 2 == 2, i & 4 == 4);
+        assert_eq!(lut.set("a", a), Ok(()));
+        assert_eq!(lut.set("b", b), Ok(()));
+        assert_eq!(lut.set("c", c), Ok(()));
+        assert_eq!(lut.get("out"), Ok(a && !b || c));
+        assert_eq!(lut.get("carry"), Ok(a != b));
+        assert_eq!(lut.get("none"), Ok(false));
+    }
+
+    // `&` binds tighter than `^` and `^` tighter than `|`
+    let mut lut = LookupTable::from_expr("y = a | b ^ a & b", vec!["a", "b"], "Prec").unwrap();
+    assert_eq!(lut.set("a", true), Ok(()));
+    assert_eq!(lut.set("b", true), Ok(()));
+    assert_eq!(lut.get("y"), Ok(true));
+
+    // true and false are constants like in hdl unless an input has their name
+    let lut = LookupTable::from_expr("y = a & true | false", vec!["a"], "T").unwrap();
+    assert_eq!(lut.get_table(), vec![vec![false, true]]);
+    let lut = LookupTable::from_expr("y = !true", vec!["true"], "N").unwrap();
+    assert_eq!(lut.get_table(), vec![vec![true, false]]);
+}
+
+#[test]
+fn from_expr_errors() {
+    let error = |src: &str| match LookupTable::from_expr(src, vec!["a", "b"], "E") {
+        Err(Error::Parse { line, column, .. }) => Some((line, column)),
+        _ => None,
+    };
+
+    assert_eq!(error(""), Some((1, 1)));
+    assert_eq!(error("y = a & c"), Some((1, 9)));
+    assert_eq!(error("y = a &"), Some((1, 8)));
+    assert_eq!(error("y = (a | b"), Some((1, 11)));
+    assert_eq!(error("y = a b"), Some((1, 7)));
+    assert_eq!(error("y = 2"), Some((1, 5)));
+    assert_eq!(error("y = a;\ny = b"), Some((2, 1)));
+    assert_eq!(error("a = b"), Some((1, 1)));
+    assert_eq!(error("y = a + b"), Some((1, 7)));
+}
+
+#[test]
+fn from_fn() {
+    // 2 to 4 decoder
+    let mut decoder = LookupTable::from_fn(
+        vec!["a", "b"],
+        vec!["y0", "y1", "y2", "y3"],
+        "Decoder",
+        |inputs| {
+            let n = inputs[0] as usize + 2 * inputs[1] as usize;
+            (0..4).map(|i| i == n).collect()
+        },
+    )
+    .unwrap();
+    assert_eq!(decoder.set("b", true), Ok(()));
+    assert_eq!(decoder.get("y2"), Ok(true));
+    assert_eq!(decoder.get("y1"), Ok(false));
+
+    assert_eq!(
+        LookupTable::from_fn(vec!["a"], vec!["x", "y"], "Wrong", |inputs| vec![inputs[0]]),
+        Err(Error::Shape {
+            expected: 2,
+            found: 1
+        })
+    );
+}
+
+#[test]
+fn from_word_fn() {
+    // a 2 bit comparator
+    let mut less = LookupTable::from_word_fn(
+        vec!["a0", "a1", "b0", "b1"],
+        vec!["lt", "eq"],
+        "Compare",
+        |word| {
+            let (a, b) = (word & 3, word >> 2);
+            (a < b) as u64 | ((a == b) as u64) << 1
+        },
+    )
+    .unwrap();
+    for (a, b) in [(0, 0), (1, 2), (3, 1), (2, 3), (3, 3)] {
+        assert_eq!(less.set("a0", a & 1 == 1), Ok(()));
+        assert_eq!(less.set("a1", a & 2 == 2), Ok(()));
+        assert_eq!(less.set("b0", b & 1 == 1), Ok(()));
+        assert_eq!(less.set("b1", b & 2 == 2), Ok(()));
+        assert_eq!(less.get("lt"), Ok(a < b));
+        assert_eq!(less.get("eq"), Ok(a == b));
+    }
+
+    // passing 3 inputs through to 2 outputs drops a bit
+    assert_eq!(
+        LookupTable::from_word_fn(vec!["a", "b", "c"], vec!["y0", "y1"], "Wide", |word| word),
+        Err(Error::Width {
+            name: "Wide".to_string(),
+            expected: 2,
+            found: 3
+        })
+    );
+}
+
+#[test]
+fn minimize() {
+    let adder = LookupTable::from_expr(
+        "sum = a ^ b ^ c; carry = a & b | a & c | b & c | a & b & c; none = a & !a; all = a | !a",
+        vec!["a", "b", "c"],
+        "FullAdder",
+    )
+    .unwrap();
+
+    let carry = adder.sop("carry").unwrap();
+    assert_eq!(carry.form(), Form::Sop);
+    assert_eq!(carry.to_string(), "a & b | a & c | b & c");
+    assert_eq!(carry.literal_count(), 6);
+    assert_eq!(
+        adder.pos("carry").unwrap().to_string(),
+        "(a | b) & (a | c) & (b | c)"
+    );
+    assert_eq!(adder.sop("sum").unwrap().terms().len(), 4);
+    assert_eq!(adder.sop("none").unwrap().to_string(), "0");
+    assert_eq!(adder.sop("all").unwrap().to_string(), "1");
+    assert_eq!(adder.pos("none").unwrap().to_string(), "0");
+    assert_eq!(adder.pos("all").unwrap().to_string(), "1");
+    assert_eq!(
+        adder.sop("cout"),
+        Err(Error::UnknownPort("cout".to_string()))
+    );
+
+    // the printed expressions can be read back
+    for form in [Form::Sop, Form::Pos] {
+        let src: Vec<String> = adder
+            .out_names()
+            .iter()
+            .map(|out| {
+                let expr = match form {
+                    Form::Sop => adder.sop(out).unwrap(),
+                    Form::Pos => adder.pos(out).unwrap(),
+                };
+                format!("{} = {};", out, expr)
+            })
+            .collect();
+        let read = LookupTable::from_expr(&src.join("\n"), vec!["a", "b", "c"], "FullAdder");
+        assert_eq!(read, Ok(adder.clone()));
+    }
+
+    // 10 inputs use the heuristic
+    let in_names: Vec<String> = (0..10).map(|i| format!("i{}", i)).collect();
+    let wide = LookupTable::from_word_fn(
+        in_names.iter().map(|s| s.as_str()).collect(),
+        vec!["y"],
+        "Wide",
+        |word| (word & 0b11 == 0b11 || word >> 8 == 0b10) as u64,
+    )
+    .unwrap();
+    let y = wide.sop("y").unwrap();
+    assert_eq!(y.to_string(), "i0 & i1 | !i8 & i9");
+    for word in 0..1024_u64 {
+        let inputs: Vec<bool> = (0..10).map(|i| word >> i & 1 == 1).collect();
+        assert_eq!(y.eval(&inputs), word & 3 == 3 || word >> 8 == 2);
+    }
+}
+
+#[test]
+fn to_gates() {
+    let lut = LookupTable::from_expr(
+        "sum = a ^ b ^ c; carry = a & b | c & (a | b); one = 1; same = a",
+        vec!["a", "b", "c"],
+        "FullAdder",
+    )
+    .unwrap();
+
+    for form in [Form::Sop, Form::Pos] {
+        let (chip_def, library) = lut.to_gates(form);
+        assert_eq!(chip_def.inputs(), vec!["a", "b", "c"]);
+        assert_eq!(chip_def.outputs(), vec!["sum", "carry", "one", "same"]);
+        assert!(chip_def
+            .parts()
+            .iter()
+            .all(|part| ["And", "Or", "Not", "True", "assign"].contains(&part.name().as_str())));
+
+        let mut circuit = Circuit::with_library(chip_def, &library).unwrap();
+        let mut lut = lut.clone();
+        for i in 0..8 {
+            for (bit, name) in ["a", "b", "c"].iter().enumerate() {
+                assert_eq!(circuit.set(name, i >> bit & 1 == 1), Ok(()));
+                assert_eq!(lut.set(name, i >> bit & 1 == 1), Ok(()));
+            }
+            // a tick visits each gate once, paths of different length need more ticks to settle
+            for _ in 0..4 {
+                assert_eq!(circuit.tick(), Ok(()));
+            }
+            for out in ["sum", "carry", "one", "same"] {
+                assert_eq!(circuit.get(out), lut.get(out));
+            }
+        }
+    }
+}
+
+#[test]
+fn dont_care() {
+    // a BCD digit is at least 5, the codes 10 to 15 are not used
+    let in_names = vec!["d0", "d1", "d2", "d3"];
+    let mut bcd = LookupTable::from_word_fn(in_names.clone(), vec!["y"], "Bcd", |d| {
+        (5..10).contains(&d) as u64
+    })
+    .unwrap();
+    let unused =
+        LookupTable::from_word_fn(in_names.clone(), vec!["y"], "Unused", |d| (d >= 10) as u64)
+            .unwrap()
+            .get_table()
+            .remove(0);
+    assert_eq!(bcd.sop("y").unwrap().literal_count(), 9);
+
+    assert_eq!(
+        bcd.set_dont_care("y", vec![true]),
+        Err(Error::Shape {
+            expected: 16,
+            found: 1
+        })
+    );
+    assert_eq!(
+        bcd.set_dont_care("x", unused.clone()),
+        Err(Error::UnknownPort("x".to_string()))
+    );
+    assert_eq!(bcd.set_dont_care("y", unused.clone()), Ok(()));
+    assert_eq!(bcd.dont_care("y"), Ok(unused));
+    assert_eq!(bcd.sop("y").unwrap().to_string(), "d0 & d2 | d1 & d2 | d3");
+    // there are 2 minimal products of sums
+    let pos = bcd.pos("y").unwrap();
+    assert_eq!((pos.terms().len(), pos.literal_count()), (2, 5));
+    for d in 0..10 {
+        let inputs: Vec<bool> = (0..4).map(|i| d >> i & 1 == 1).collect();
+        assert_eq!(pos.eval(&inputs), d >= 5);
+    }
+
+    // any values for the codes 10 to 15 are equivalent
+    let digit = LookupTable::from_word_fn(in_names.clone(), vec!["y"], "Digit", |d| {
+        ((5..10).contains(&d) || d == 13) as u64
+    })
+    .unwrap();
+    assert!(bcd.equivalent(&digit));
+    assert!(digit.equivalent(&bcd));
+    let wrong =
+        LookupTable::from_word_fn(in_names.clone(), vec!["y"], "Wrong", |d| (d >= 6) as u64)
+            .unwrap();
+    assert!(!bcd.equivalent(&wrong));
+    let reordered =
+        LookupTable::from_expr("y = d3 | d2 & (d1 | d0)", vec!["d3", "d2", "d1", "d0"], "R")
+            .unwrap();
+    assert!(bcd.equivalent(&reordered));
+    assert!(!bcd.equivalent(&LookupTable::from_expr("y = d3", vec!["d3", "d2"], "S").unwrap()));
+
+    // 12 is a don't care
+    assert_eq!(bcd.set("d2", true), Ok(()));
+    assert_eq!(bcd.set("d3", true), Ok(()));
+    assert_eq!(bcd.policy(), DontCarePolicy::Zero);
+    assert_eq!(bcd.get("y"), Ok(false));
+    bcd.set_policy(DontCarePolicy::One);
+    assert_eq!(bcd.get("y"), Ok(true));
+    bcd.set_policy(DontCarePolicy::X);
+    let x = Err(Error::DontCare {
+        name: "Bcd".to_string(),
+        output: "y".to_string(),
+    });
+    assert_eq!(bcd.get("y"), x.clone());
+    assert_eq!(bcd.try_outputs(), x.clone().map(|y| vec![y]));
+    assert_eq!(bcd.set("d3", false), Ok(()));
+    assert_eq!(bcd.set("d0", true), Ok(()));
+    assert_eq!(bcd.get("y"), Ok(true));
+
+    // a circuit stops at the first don't care it reads with the X policy
+    let mut library = Library::new();
+    library.add_lut(bcd);
+    let chip_def = ChipDef::new(
+        "Top",
+        in_names.clone(),
+        vec!["y"],
+        vec![(
+            in_names.iter().map(|&name| (name, name)).collect(),
+            vec![("y", "y")],
+            "Bcd",
+        )],
+    );
+    let mut circuit = Circuit::with_library(chip_def, &library).unwrap();
+    assert_eq!(circuit.set("d2", true), Ok(()));
+    assert_eq!(circuit.tick(), Ok(()));
+    assert_eq!(circuit.get("y"), Ok(false));
+    assert_eq!(circuit.set("d3", true), Ok(()));
+    assert_eq!(circuit.tick(), x.map(|_| ()));
+}
+
+#[test]
+fn pla() {
+    let src = "
+        # full adder
+        .i 3
+        .o 2
+        .ilb a b c
+        .ob sum carry
+        .p 7
+        100 10
+        010 10
+        001 10
+        111 11
+        11- 01
+        -1 1|01
+        1-1 01
+        .e
+    ";
+    let adder = LookupTable::from_pla(src, "FullAdder").unwrap();
+    assert_eq!(adder.in_names(), vec!["a", "b", "c"]);
+    assert_eq!(adder.out_names(), vec!["sum", "carry"]);
+    assert_eq!(
+        adder,
+        LookupTable::from_expr(
+            "sum = a ^ b ^ c; carry = a & b | b & c | a & c",
+            vec!["a", "b", "c"],
+            "FullAdder"
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        LookupTable::from_pla(&adder.to_pla(), "FullAdder"),
+        Ok(adder)
+    );
+
+    // the codes 10 to 15 of a BCD digit are don't cares
+    let src = "
+        .i 4
+        .o 1
+        .p 4
+        1-1- -
+        11-- -
+        100- 1
+        011- 1
+        0101 1
+    ";
+    let bcd = LookupTable::from_pla(src, "Bcd").unwrap();
+    assert_eq!(bcd.in_names(), vec!["in0", "in1", "in2", "in3"]);
+    assert_eq!(bcd.sop("out0").unwrap().literal_count(), 5);
+    assert_eq!(
+        bcd.dont_care("out0")
+            .unwrap()
+            .iter()
+            .filter(|&&dc| dc)
+            .count(),
+        6
+    );
+    assert_eq!(LookupTable::from_pla(&bcd.to_pla(), "Bcd"), Ok(bcd));
+
+    // only the rows in the on set or the off set are specified
+    let partial = LookupTable::from_pla(".i 2\n.o 1\n.type fr\n11 1\n0- 0\n", "Partial").unwrap();
+    assert_eq!(
+        partial
+            .dont_care("out0")
+            .unwrap()
+            .iter()
+            .filter(|&&dc| dc)
+            .count(),
+        1
+    );
+    assert_eq!(partial.sop("out0").unwrap().to_string(), "in0");
+
+    let and = LookupTable::from_expr("out = a & b", vec!["a", "b"], "And").unwrap();
+    assert_eq!(
+        and.to_pla(),
+        ".i 2\n.o 1\n.ilb a b\n.ob out\n.p 1\n11 1\n.e\n"
+    );
+}
+
+#[test]
+fn pla_errors() {
+    let error = |src: &str| match LookupTable::from_pla(src, "E") {
+        Err(Error::Parse { line, column, .. }) => Some((line, column)),
+        _ => None,
+    };
+
+    assert_eq!(error(""), Some((1, 1)));
+    assert_eq!(error("11 1"), Some((1, 1)));
+    assert_eq!(error(".i x"), Some((1, 4)));
+    assert_eq!(error(".i 21"), Some((1, 4)));
+    assert_eq!(error(".i 2\n.o 1\n.ilb a"), Some((3, 1)));
+    assert_eq!(error(".i 2\n.o 1\n1 1"), Some((3, 1)));
+    assert_eq!(error(".i 2\n.o 1\n1x 1"), Some((3, 1)));
+    assert_eq!(error(".i 2\n.o 1\n10 x"), Some((3, 1)));
+    assert_eq!(error(".i 2\n.o 1\n.type fr\n1- 1\n10 0"), Some((5, 1)));
+    assert_eq!(error(".i 2\n.o 1\n.type rf"), Some((3, 1)));
+    assert_eq!(error(".i 2\n.o 1\n.mv 3 0 2 2"), Some((3, 1)));
+    assert_eq!(error(".i 2\n.o 1\n.e\n11 1"), Some((4, 1)));
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "serde")]
+
+use hardware_sim::{ChipDef, Component, DontCarePolicy, LookupTable};
+
+#[test]
+fn lut() {
+    let nand = LookupTable::new(
+        vec![vec![true, true, true, false]],
+        vec!["a", "b"],
+        vec!["out"],
+        "Nand",
+    )
+    .unwrap();
+
+    let json = serde_json::to_string(&nand).unwrap();
+    assert_eq!(
+        json,
+        r#"{"name":"Nand","inputs":["a","b"],"outputs":["out"],"table":["7"]}"#
+    );
+    assert_eq!(serde_json::from_str::<LookupTable>(&json).unwrap(), nand);
+
+    let full_adder = LookupTable::new(
+        vec![
+            vec![false, true, true, false, true, false, false, true],
+            vec![false, false, false, true, false, true, true, true],
+        ],
+        vec!["a", "b", "c"],
+        vec!["sum", "carry"],
+        "FullAdder",
+    )
+    .unwrap();
+    let json = serde_json::to_string(&full_adder).unwrap();
+    assert!(json.contains(r#""table":["96","e8"]"#));
+    assert_eq!(
+        serde_json::from_str::<LookupTable>(&json).unwrap(),
+        full_adder
+    );
+
+    let not = LookupTable::new(vec![vec![true, false]], vec!["in"], vec!["out"], "Not").unwrap();
+    let json = serde_json::to_string(&not).unwrap();
+    assert!(json.contains(r#""table":["1"]"#));
+    assert_eq!(serde_json::from_str::<LookupTable>(&json).unwrap(), not);
+}
+
+#[test]
+fn lut_errors() {
+    let lut = |table: &str| {
+        serde_json::from_str::<LookupTable>(&format!(
+            r#"{{"name":"Nand","inputs":["a","b"],"outputs":["out"],"table":{}}}"#,
+            table
+        ))
+    };
+    assert!(lut(r#"["7"]"#).is_ok());
+    assert!(lut(r#"["07"]"#).is_err());
+    assert!(lut(r#"["x"]"#).is_err());
+    // the column of a bad digit is its position in the string
+    let err = serde_json::from_str::<LookupTable>(
+        r#"{"name":"Maj","inputs":["a","b","c"],"outputs":["out"],"table":["xx"]}"#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("1:2:"));
+    assert!(lut(r#"["7", "1"]"#).is_err());
+    assert!(serde_json::from_str::<LookupTable>(
+        r#"{"name":"Not","inputs":["in"],"outputs":["out"],"table":["5"]}"#
+    )
+    .is_err());
+}
+
+#[test]
+fn chip_def() {
+    let mut add = ChipDef::new_string(
+        "Add".to_string(),
+        vec!["a[4]".to_string(), "b[4]".to_string()],
+        vec!["out[4]".to_string(), "zero".to_string()],
+        vec![
+            Component::Def {
+                inputs: vec![
+                    ("a".to_string(), "a".to_string()),
+                    ("b".to_string(), "b".to_string()),
+                ],
+                outputs: vec![("out".to_string(), "sum".to_string())],
+                name: "Add4".to_string(),
+            },
+            Component::IO {
+                inputs: vec!["sum".to_string()],
+                outputs: vec!["zero".to_string()],
+                name: "IsZero".to_string(),
+            },
+            Component::Map {
+                var_map: vec![("sum".to_string(), "out".to_string())],
+                name: "out".to_string(),
+            },
+        ],
+    );
+    assert_eq!(add.set_instance(0, "adder"), Ok(()));
+
+    let json = serde_json::to_string(&add).unwrap();
+    assert!(json.contains(r#""inputs":["a[4]","b[4]"],"outputs":["out[4]","zero"]"#));
+    assert!(json.contains(r#""kind":"IO""#));
+    assert!(json.contains(r#""instances":[{"part":0,"name":"adder"}]"#));
+
+    let loaded: ChipDef = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, add);
+    assert_eq!(loaded.width("a"), 4);
+
+    // toml uses the same form
+    let src = toml::to_string(&add).unwrap();
+    assert!(src.contains(r#"inputs = ["a[4]", "b[4]"]"#));
+    assert_eq!(toml::from_str::<ChipDef>(&src).unwrap(), add);
+
+    let loaded: ChipDef = serde_json::from_str(
+        r#"{
+            "name": "Not",
+            "inputs": ["in"],
+            "outputs": ["out"],
+            "parts": [
+                {"kind": "Def", "inputs": [["a", "in"], ["b", "in"]], "outputs": [["out", "out"]], "name": "Nand"}
+            ]
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        loaded,
+        ChipDef::new(
+            "Not",
+            vec!["in"],
+            vec!["out"],
+            vec![(vec![("a", "in"), ("b", "in")], vec![("out", "out")], "Nand")],
+        )
+    );
+}
+
+#[test]
+fn lut_dont_care() {
+    let mut and = LookupTable::new(
+        vec![vec![false, false, false, true]],
+        vec!["a", "b"],
+        vec!["out"],
+        "And",
+    )
+    .unwrap();
+    assert_eq!(
+        and.set_dont_care("out", vec![false, false, false, true]),
+        Ok(())
+    );
+    and.set_policy(DontCarePolicy::X);
+
+    let json = serde_json::to_string(&and).unwrap();
+    assert_eq!(
+        json,
+        r#"{"name":"And","inputs":["a","b"],"outputs":["out"],"table":["8"],"dont_care":["8"],"policy":"x"}"#
+    );
+    assert_eq!(serde_json::from_str::<LookupTable>(&json).unwrap(), and);
+    let src = toml::to_string(&and).unwrap();
+    assert!(src.contains(r#"policy = "x""#));
+    assert_eq!(toml::from_str::<LookupTable>(&src).unwrap(), and);
+    assert!(serde_json::from_str::<LookupTable>(
+        r#"{"name":"And","inputs":["a","b"],"outputs":["out"],"table":["8"],"dont_care":["8", "1"]}"#
+    )
+    .is_err());
+}